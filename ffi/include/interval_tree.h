@@ -0,0 +1,41 @@
+/* C interface of the interval_tree crate, built by the interval_tree_ffi crate. See ffi/src/lib.rs. */
+#ifndef INTERVAL_TREE_H
+#define INTERVAL_TREE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* status codes, negative values are errors */
+#define IT_OK 0
+#define IT_NULL (-1)
+#define IT_INVALID_RANGE (-2)
+#define IT_NOT_FOUND (-3)
+#define IT_PANIC (-4)
+#define IT_STOPPED 1
+
+typedef struct CIntervalTree interval_tree;
+
+/* called for every pair of a query in ascending order of the keys, a non zero result stops the iteration */
+typedef int (*interval_tree_visitor)(void *ctx, uint64_t min, uint64_t max, void *data);
+
+interval_tree *interval_tree_new(void);
+void interval_tree_free(interval_tree *tree);
+size_t interval_tree_len(const interval_tree *tree);
+
+/* keys are the inclusive ranges [min, max] */
+int interval_tree_insert(interval_tree *tree, uint64_t min, uint64_t max, void *data);
+int interval_tree_remove(interval_tree *tree, uint64_t min, uint64_t max, void **out);
+int interval_tree_get(const interval_tree *tree, uint64_t min, uint64_t max, void **out);
+
+int interval_tree_stab(const interval_tree *tree, uint64_t point, interval_tree_visitor visitor, void *ctx);
+int interval_tree_find_overlaps(const interval_tree *tree, uint64_t min, uint64_t max, interval_tree_visitor visitor, void *ctx);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
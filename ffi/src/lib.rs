@@ -0,0 +1,198 @@
+//! A C interface to `interval_tree::IntervalTree`. This crate is built as a cdylib, and
+//! `include/interval_tree.h` declares the functions below. It is a separate crate so that users of
+//! the Rust library do not build a shared library as well.
+//!
+//! A tree is an opaque handle created with `interval_tree_new` and released with
+//! `interval_tree_free`. Keys are inclusive ranges `[min, max]` of u64, the data is an untyped
+//! pointer that the tree only stores and hands back, never dereferences or frees. Every function
+//! except `interval_tree_new`, `interval_tree_free` and `interval_tree_len` returns one of the
+//! `IT_*` status codes: `IT_OK` (0) on success, a negative code otherwise. Results are written
+//! through out pointers, which may be null if the result is not needed. Overlap queries call a
+//! callback for every pair in ascending order of the keys; a non zero return value of the callback
+//! stops the iteration. The tree must not be changed from within the callback.
+//!
+//! No panic crosses the interface: a panic inside the tree is reported as `IT_PANIC`, after which
+//! the tree should only be freed.
+//! ```c
+//! static int print(void *ctx, uint64_t min, uint64_t max, void *data) {
+//!     printf("[%llx, %llx] %s\n", min, max, (const char *)data);
+//!     return 0;
+//! }
+//!
+//! interval_tree *t = interval_tree_new();
+//! interval_tree_insert(t, 0x1000, 0x1fff, "text");
+//! interval_tree_insert(t, 0x3000, 0x3fff, "data");
+//! interval_tree_stab(t, 0x1800, print, NULL);
+//! interval_tree_free(t);
+//! ```
+
+extern crate interval_tree;
+extern crate memrange;
+
+use memrange::Range;
+use interval_tree::IntervalTree;
+use std::os::raw::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// The opaque tree behind a handle of the C interface.
+pub struct CIntervalTree {
+    tree: IntervalTree<*mut c_void>,
+}
+
+/// A callback receiving the context pointer, the key and the data of one pair. A non zero return
+/// value stops the iteration.
+pub type CVisitor = extern "C" fn(*mut c_void, u64, u64, *mut c_void) -> c_int;
+
+/// the call succeeded
+pub const IT_OK: c_int = 0;
+/// the tree handle or a required pointer was null
+pub const IT_NULL: c_int = -1;
+/// min was bigger than max
+pub const IT_INVALID_RANGE: c_int = -2;
+/// the key is not part of the tree
+pub const IT_NOT_FOUND: c_int = -3;
+/// the tree panicked, the handle should only be freed
+pub const IT_PANIC: c_int = -4;
+/// the iteration was stopped by the callback
+pub const IT_STOPPED: c_int = 1;
+
+/// runs f on the tree behind handle, turning a null handle, an inverted key and panics into status codes
+unsafe fn with_tree<F: FnOnce(&mut IntervalTree<*mut c_void>, Range) -> c_int>(handle: *mut CIntervalTree, min: u64, max: u64, f: F) -> c_int {
+    if handle.is_null() { return IT_NULL }
+    if min > max { return IT_INVALID_RANGE }
+    let tree = &mut (*handle).tree;
+    panic::catch_unwind(AssertUnwindSafe(|| f(tree, Range::new(min, max)))).unwrap_or(IT_PANIC)
+}
+
+/// runs f on the tree behind handle like `with_tree`, for queries that do not change the tree
+unsafe fn with_tree_ref<F: FnOnce(&IntervalTree<*mut c_void>, Range) -> c_int>(handle: *const CIntervalTree, min: u64, max: u64, f: F) -> c_int {
+    if handle.is_null() { return IT_NULL }
+    if min > max { return IT_INVALID_RANGE }
+    let tree = &(*handle).tree;
+    panic::catch_unwind(AssertUnwindSafe(|| f(tree, Range::new(min, max)))).unwrap_or(IT_PANIC)
+}
+
+unsafe fn store(out: *mut *mut c_void, data: *mut c_void) {
+    if !out.is_null() { *out = data }
+}
+
+fn visit(tree: &IntervalTree<*mut c_void>, key: Range, visitor: CVisitor, ctx: *mut c_void) -> c_int {
+    for (range, &data) in tree.range(key) {
+        if visitor(ctx, range.min, range.max, data) != 0 { return IT_STOPPED }
+    }
+    IT_OK
+}
+
+/// This function will return the handle of a new empty tree, or null if it could not be created.
+#[no_mangle]
+pub extern "C" fn interval_tree_new() -> *mut CIntervalTree {
+    panic::catch_unwind(|| Box::into_raw(Box::new(CIntervalTree{tree: IntervalTree::new()}))).unwrap_or(ptr::null_mut())
+}
+
+/// This function will free the tree behind handle, but not the stored data. Null is ignored.
+/// # Safety
+/// handle has to be null or a handle returned by `interval_tree_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn interval_tree_free(handle: *mut CIntervalTree) {
+    if !handle.is_null() { drop(Box::from_raw(handle)) }
+}
+
+/// This function will return the number of pairs in the tree, 0 for a null handle.
+/// # Safety
+/// handle has to be null or a handle returned by `interval_tree_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn interval_tree_len(handle: *const CIntervalTree) -> usize {
+    if handle.is_null() { return 0 }
+    (*handle).tree.len()
+}
+
+/// This function will insert the pair `[min, max]`, data, overwriting the data of the key if it is
+/// already part of the tree.
+/// # Safety
+/// handle has to be null or a handle returned by `interval_tree_new` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn interval_tree_insert(handle: *mut CIntervalTree, min: u64, max: u64, data: *mut c_void) -> c_int {
+    with_tree(handle, min, max, |tree, key| { tree.insert(key, data); IT_OK })
+}
+
+/// This function will remove the key `[min, max]` and store its data in out, or return
+/// `IT_NOT_FOUND` if the key is not part of the tree.
+/// # Safety
+/// handle has to be null or a handle returned by `interval_tree_new` that has not been freed,
+/// out has to be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn interval_tree_remove(handle: *mut CIntervalTree, min: u64, max: u64, out: *mut *mut c_void) -> c_int {
+    with_tree(handle, min, max, |tree, key| match tree.remove_entry(key) {
+        Some((_, data)) => { store(out, data); IT_OK }
+        None => IT_NOT_FOUND
+    })
+}
+
+/// This function will store the data of exactly the key `[min, max]` in out, or return
+/// `IT_NOT_FOUND` if the key is not part of the tree.
+/// # Safety
+/// handle has to be null or a handle returned by `interval_tree_new` that has not been freed,
+/// out has to be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn interval_tree_get(handle: *const CIntervalTree, min: u64, max: u64, out: *mut *mut c_void) -> c_int {
+    with_tree_ref(handle, min, max, |tree, key| match tree.get(key) {
+        Some(&data) => { store(out, data); IT_OK }
+        None => IT_NOT_FOUND
+    })
+}
+
+/// This function will call visitor with ctx for every pair whose key contains point. Returns
+/// `IT_STOPPED` if the visitor stopped the iteration.
+/// # Safety
+/// handle has to be null or a handle returned by `interval_tree_new` that has not been freed.
+/// ctx is only passed on to visitor.
+#[no_mangle]
+pub unsafe extern "C" fn interval_tree_stab(handle: *const CIntervalTree, point: u64, visitor: Option<CVisitor>, ctx: *mut c_void) -> c_int {
+    interval_tree_find_overlaps(handle, point, point, visitor, ctx)
+}
+
+/// This function will call visitor with ctx for every pair whose key intersects `[min, max]`.
+/// Returns `IT_STOPPED` if the visitor stopped the iteration.
+/// # Safety
+/// handle has to be null or a handle returned by `interval_tree_new` that has not been freed.
+/// ctx is only passed on to visitor.
+#[no_mangle]
+pub unsafe extern "C" fn interval_tree_find_overlaps(handle: *const CIntervalTree, min: u64, max: u64, visitor: Option<CVisitor>, ctx: *mut c_void) -> c_int {
+    let visitor = match visitor { Some(visitor) => visitor, None => return IT_NULL };
+    with_tree_ref(handle, min, max, |tree, key| visit(tree, key, visitor, ctx))
+}
+
+#[cfg(test)]
+extern "C" fn collect(ctx: *mut c_void, min: u64, max: u64, data: *mut c_void) -> c_int {
+    let found = unsafe { &mut *(ctx as *mut Vec<(u64, u64, usize)>) };
+    found.push((min, max, data as usize));
+    if found.len() == 3 { 1 } else { 0 }
+}
+
+#[test]
+fn test_ffi(){
+    unsafe {
+        let t = interval_tree_new();
+        for i in 1..100usize {
+            assert_eq!(interval_tree_insert(t, i as u64 * 10, i as u64 * 10 + 15, i as *mut c_void), IT_OK);
+        }
+        assert_eq!(interval_tree_len(t), 99);
+        assert_eq!(interval_tree_insert(t, 5, 4, ptr::null_mut()), IT_INVALID_RANGE);
+        let mut data = ptr::null_mut();
+        assert_eq!(interval_tree_get(t, 100, 115, &mut data), IT_OK);
+        assert_eq!(data as usize, 10);
+        assert_eq!(interval_tree_remove(t, 100, 115, &mut data), IT_OK);
+        assert_eq!(interval_tree_remove(t, 100, 115, ptr::null_mut()), IT_NOT_FOUND);
+        let mut found: Vec<(u64, u64, usize)> = vec![];
+        assert_eq!(interval_tree_stab(t, 112, Some(collect), &mut found as *mut _ as *mut c_void), IT_OK);
+        assert_eq!(found, vec![(110, 125, 11)]);
+        found.clear();
+        assert_eq!(interval_tree_find_overlaps(t, 85, 1000, Some(collect), &mut found as *mut _ as *mut c_void), IT_STOPPED);
+        assert_eq!(found, vec![(70, 85, 7), (80, 95, 8), (90, 105, 9)]);
+        assert_eq!(interval_tree_stab(t, 112, None, ptr::null_mut()), IT_NULL);
+        interval_tree_free(t);
+        assert_eq!(interval_tree_insert(ptr::null_mut(), 1, 2, ptr::null_mut()), IT_NULL);
+        assert_eq!(interval_tree_len(ptr::null()), 0);
+    }
+}
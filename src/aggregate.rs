@@ -0,0 +1,123 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use std::fmt;
+use std::ops::Add;
+
+/// A monoid over the stored (key,value) pairs that a tree maintains for every subtree, in
+/// addition to the cached maximum end used for overlap queries. This allows to fold the values
+/// of all keys intersecting a window in O(log n) via `IntervalTree::aggregate`, e.g. to sum up
+/// the sizes of all mappings overlapping a range.
+///
+/// The aggregate is chosen as second type parameter of the tree, e.g.
+/// `IntervalTree<u64, Sum>`, and defaults to `NoAggregate`, which costs nothing. Since the cached
+/// values would go stale, trees with an aggregate do not offer the functions that hand out
+/// mutable references to stored data (`values_mut`, `extract_if`); use `insert` to change data.
+///
+/// `combine` has to be associative with `empty` as identity. Values are combined in ascending
+/// order of their keys, so combine does not need to be commutative. Values are compared by
+/// `IntervalTree::check_invariants` to verify the cached aggregates.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::{Aggregate, IntervalTree};
+///
+/// #[derive(Debug)]
+/// struct MappedBytes;
+///
+/// impl<D> Aggregate<D> for MappedBytes {
+///     type Value = u64;
+///     fn empty() -> u64 { 0 }
+///     fn lift(key: &Range, _: &D) -> u64 { key.len() }
+///     fn combine(left: &u64, right: &u64) -> u64 { left + right }
+/// }
+///
+/// let mut t = IntervalTree::<&str, MappedBytes>::with_aggregate(MappedBytes);
+/// t.insert(Range::new(0x1000,0x1fff), "text");
+/// t.insert(Range::new(0x3000,0x37ff), "data");
+/// t.insert(Range::new(0x8000,0x8fff), "stack");
+/// assert_eq!(t.aggregate(Range::new(0,0x4000)), 0x1800);
+/// ```
+pub trait Aggregate<D> {
+    /// the aggregated value cached for every subtree
+    type Value: Clone + PartialEq + fmt::Debug;
+    /// returns the aggregate of no pairs at all
+    fn empty() -> Self::Value;
+    /// returns the aggregate of a single pair
+    fn lift(key: &Range, data: &D) -> Self::Value;
+    /// combines the aggregates of two runs of pairs, where all keys of left are smaller than the
+    /// keys of right
+    fn combine(left: &Self::Value, right: &Self::Value) -> Self::Value;
+}
+
+/// The default aggregate of a tree, which maintains nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NoAggregate;
+
+impl<D> Aggregate<D> for NoAggregate {
+    type Value = ();
+    fn empty() {}
+    fn lift(_: &Range, _: &D) {}
+    fn combine(_: &(), _: &()) {}
+}
+
+/// Sums up the stored values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sum;
+
+impl<D: Copy + Default + PartialEq + Add<Output=D> + fmt::Debug> Aggregate<D> for Sum {
+    type Value = D;
+    fn empty() -> D { D::default() }
+    fn lift(_: &Range, data: &D) -> D { *data }
+    fn combine(left: &D, right: &D) -> D { *left + *right }
+}
+
+/// Finds the smallest stored value, None if there are no values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Min;
+
+impl<D: Copy + Ord + fmt::Debug> Aggregate<D> for Min {
+    type Value = Option<D>;
+    fn empty() -> Option<D> { None }
+    fn lift(_: &Range, data: &D) -> Option<D> { Some(*data) }
+    fn combine(left: &Option<D>, right: &Option<D>) -> Option<D> {
+        match (*left, *right) {
+            (Some(l), Some(r)) => Some(if r < l { r } else { l }),
+            (l, None) => l,
+            (None, r) => r
+        }
+    }
+}
+
+/// Finds the biggest stored value, None if there are no values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Max;
+
+impl<D: Copy + Ord + fmt::Debug> Aggregate<D> for Max {
+    type Value = Option<D>;
+    fn empty() -> Option<D> { None }
+    fn lift(_: &Range, data: &D) -> Option<D> { Some(*data) }
+    fn combine(left: &Option<D>, right: &Option<D>) -> Option<D> {
+        match (*left, *right) {
+            (Some(l), Some(r)) => Some(if r > l { r } else { l }),
+            (l, None) => l,
+            (None, r) => r
+        }
+    }
+}
+
+/// Sums up the lengths of the stored keys, i.e. the number of values they cover, with values
+/// covered by several keys counted for every key. The sum is a u128, as the full key space holds
+/// 2^64 values. A tree with this aggregate can pick pairs weighted by the length of their keys,
+/// see `IntervalTree::sample_weighted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LenSum;
+
+impl<D> Aggregate<D> for LenSum {
+    type Value = u128;
+    fn empty() -> u128 { 0 }
+    fn lift(key: &Range, _: &D) -> u128 { (key.max - key.min) as u128 + 1 }
+    fn combine(left: &u128, right: &u128) -> u128 { left + right }
+}
@@ -0,0 +1,111 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use iterators::RangePairIter;
+use bounds::IntoRange;
+
+/// Hands out non intersecting ranges of a fixed space, e.g. virtual addresses or ids, and keeps
+/// the data attached to every allocation. Allocations are placed first-fit.
+#[derive(Debug)]
+pub struct RangeAllocator<D> {
+    space: Range,
+    used: IntervalTree<D>
+}
+
+impl<D> RangeAllocator<D> {
+
+/// This function will construct a new RangeAllocator that hands out ranges within space.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let a=interval_tree::RangeAllocator::<i32>::new(memrange::Range::new(0x1000,0xffff));
+/// assert_eq!(a.space(), memrange::Range::new(0x1000,0xffff));
+/// ```
+    pub fn new(space: Range) -> RangeAllocator<D> {
+        RangeAllocator{space: space, used: IntervalTree::new()}
+    }
+
+/// This function will return the space this allocator hands out ranges from.
+    pub fn space(&self) -> Range { self.space }
+
+/// This function will allocate the lowest free range of size values and attach data to it. If no
+/// free range of this size is left (or size is 0), data is returned as error.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut a=interval_tree::RangeAllocator::new(Range::new(0,99));
+/// assert_eq!(a.allocate(10, "a"), Ok(Range::new(0,9)));
+/// assert_eq!(a.allocate(10, "b"), Ok(Range::new(10,19)));
+/// assert_eq!(a.allocate(90, "c"), Err("c"));
+/// ```
+    pub fn allocate(&mut self, size: u64, data: D) -> Result<Range, D> {
+        match self.used.find_free_gap(size, self.space) {
+            Some(range) => { self.used.insert(range, data); Ok(range) },
+            None => Err(data)
+        }
+    }
+
+/// This function will allocate exactly the given range and attach data to it. If the range is
+/// not completely within the space of the allocator or intersects an existing allocation, data
+/// is returned as error.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut a=interval_tree::RangeAllocator::new(Range::new(0,99));
+/// assert_eq!(a.allocate_at(Range::new(10,19), "a"), Ok(Range::new(10,19)));
+/// assert_eq!(a.allocate_at(Range::new(15,25), "b"), Err("b"));
+/// assert_eq!(a.allocate(20, "c"), Ok(Range::new(20,39)));
+/// ```
+    pub fn allocate_at<K: IntoRange>(&mut self, range: K, data: D) -> Result<Range, D> {
+        let range = match range.into_range() { Some(range) => range, None => return Err(data) };
+        if !self.is_free(range) { return Err(data) }
+        self.used.insert(range, data);
+        Ok(range)
+    }
+
+/// This function will release the allocation with exactly the given range and return its data,
+/// or None if there is no such allocation.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut a=interval_tree::RangeAllocator::new(Range::new(0,99));
+/// let range = a.allocate(10, "a").unwrap();
+/// assert_eq!(a.free(range), Some("a"));
+/// assert_eq!(a.free(range), None);
+/// assert_eq!(a.allocate(10, "b"), Ok(range));
+/// ```
+    pub fn free<K: IntoRange>(&mut self, range: K) -> Option<D> {
+        self.used.remove_entry(range).map(|(_, data)| data)
+    }
+
+/// This function will return true if range lies within the space of the allocator and does not
+/// intersect any allocation.
+    pub fn is_free<K: IntoRange>(&self, range: K) -> bool {
+        match range.into_range() {
+            Some(range) => self.space.get_union(&range) == self.space && self.used.first_overlap(range).is_none(),
+            None => true
+        }
+    }
+
+/// This function will return the data attached to the allocation with exactly the given range.
+    pub fn get<K: IntoRange>(&self, range: K) -> Option<&D> {
+        self.used.get(range)
+    }
+
+/// This function will return an iterator over all allocations and their data in ascending order.
+    pub fn iter(&self) -> RangePairIter<D> {
+        self.used.iter()
+    }
+}
@@ -0,0 +1,68 @@
+extern crate memrange;
+extern crate quickcheck;
+
+use self::memrange::Range;
+use self::quickcheck::{Arbitrary, Gen};
+use tree::IntervalTree;
+use std::cmp;
+
+/// A `Range` that implements `quickcheck::Arbitrary`. `Range` is defined in the memrange crate,
+/// so the trait cannot be implemented for it directly. Half of the generated ranges lie in a key
+/// space proportional to the size of the generator, so that they overlap frequently, the other
+/// half may span the whole u64 key space. Only available with the `quickcheck` feature.
+/// # Examples
+/// ```
+/// extern crate quickcheck;
+/// extern crate interval_tree;
+/// use quickcheck::{Arbitrary, Gen};
+/// use interval_tree::ArbitraryRange;
+///
+/// let ArbitraryRange(range) = ArbitraryRange::arbitrary(&mut Gen::new(10));
+/// assert!(range.min <= range.max);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ArbitraryRange(pub Range);
+
+impl Arbitrary for ArbitraryRange {
+    fn arbitrary(g: &mut Gen) -> ArbitraryRange {
+        let (a, b) = if bool::arbitrary(g) {
+            let space = cmp::max(g.size() as u64, 1).saturating_mul(4);
+            (u64::arbitrary(g) % space, u64::arbitrary(g) % space)
+        } else {
+            (u64::arbitrary(g), u64::arbitrary(g))
+        };
+        ArbitraryRange(Range::new(cmp::min(a, b), cmp::max(a, b)))
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=ArbitraryRange>> {
+        let range = self.0;
+        let shrunk = (range.min, range.max).shrink().filter(|&(min, max)| min <= max);
+        Box::new(shrunk.map(|(min, max)| ArbitraryRange(Range::new(min, max))))
+    }
+}
+
+/// Generates structurally valid trees from an arbitrary list of key,value pairs, shrinking
+/// removes pairs or shrinks single keys or values. Only available with the `quickcheck` feature.
+/// # Examples
+/// ```
+/// extern crate quickcheck;
+/// extern crate interval_tree;
+/// use quickcheck::{Arbitrary, Gen};
+///
+/// let t=interval_tree::IntervalTree::<u8>::arbitrary(&mut Gen::new(50));
+/// assert_eq!(t.check_invariants(), Ok(()));
+/// assert!(t.shrink().all(|s| s.len() <= t.len()));
+/// ```
+impl<D: Arbitrary> Arbitrary for IntervalTree<D> {
+    fn arbitrary(g: &mut Gen) -> IntervalTree<D> {
+        let pairs = Vec::<(ArbitraryRange, D)>::arbitrary(g);
+        IntervalTree::from_vec(pairs.into_iter().map(|(ArbitraryRange(key), data)| (key, data)).collect())
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item=IntervalTree<D>>> {
+        let pairs = self.iter().map(|(key, data)| (ArbitraryRange(*key), data.clone())).collect::<Vec<_>>();
+        Box::new(pairs.shrink().map(|pairs| {
+            IntervalTree::from_vec(pairs.into_iter().map(|(ArbitraryRange(key), data)| (key, data)).collect())
+        }))
+    }
+}
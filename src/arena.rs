@@ -0,0 +1,550 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use bounds::IntoRange;
+use std::cmp;
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::Arc;
+
+/// A node of an `ArenaIntervalTree`, successors are indices into the node vector of the tree.
+#[derive(Debug, Clone)]
+struct ArenaNode<D> {
+    key: Range,
+    data: D,
+    height: u32,
+    max: u64,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+/// A slot of the node storage of an `ArenaIntervalTree`, holding either a node or the link to the
+/// next free slot.
+#[derive(Debug, Clone)]
+pub struct ArenaSlot<D>(Slot<D>);
+
+#[derive(Debug, Clone)]
+enum Slot<D> {
+    Used(ArenaNode<D>),
+    Free(Option<usize>),
+}
+
+/// The storage an `ArenaIntervalTree` takes its node slots from, addressed by index. It is
+/// implemented for `Vec`; implement it for a pool or a preallocated buffer to control where the
+/// nodes are allocated. The tree only ever appends slots and reuses the slots of removed nodes
+/// itself, so a storage that never grows beyond a fixed capacity never allocates. Storages that
+/// cannot take another slot may panic in `push`.
+pub trait NodeAlloc<T> {
+    /// returns the number of slots
+    fn len(&self) -> usize;
+    /// appends a slot, whose index is the previous `len`
+    fn push(&mut self, slot: T);
+    /// returns the slot with the given index
+    fn slot(&self, index: usize) -> &T;
+    /// returns the slot with the given index for modification
+    fn slot_mut(&mut self, index: usize) -> &mut T;
+    /// removes all slots
+    fn clear(&mut self);
+    /// returns the number of slots that can be stored without allocating
+    fn capacity(&self) -> usize { self.len() }
+}
+
+impl<T> NodeAlloc<T> for Vec<T> {
+    fn len(&self) -> usize { Vec::len(self) }
+    fn push(&mut self, slot: T) { Vec::push(self, slot) }
+    fn slot(&self, index: usize) -> &T { &self[index] }
+    fn slot_mut(&mut self, index: usize) -> &mut T { &mut self[index] }
+    fn clear(&mut self) { Vec::clear(self) }
+    fn capacity(&self) -> usize { Vec::capacity(self) }
+}
+
+/// number of slots in a chunk of `SharedChunks`
+const CHUNK: usize = 256;
+
+/// A node storage made of fixed-size chunks that are shared between clones and copied on the
+/// first write, so `ArenaIntervalTree::fork` only copies one pointer per chunk up front, and every
+/// chunk holding a node that is changed afterwards once.
+#[derive(Debug, Clone)]
+pub struct SharedChunks<T> {
+    chunks: Vec<Arc<Vec<T>>>,
+    len: usize,
+}
+
+impl<T> SharedChunks<T> {
+    /// returns an empty storage
+    pub fn new() -> SharedChunks<T> {
+        SharedChunks{chunks: Vec::new(), len: 0}
+    }
+}
+
+impl<T: Clone> NodeAlloc<T> for SharedChunks<T> {
+    fn len(&self) -> usize { self.len }
+    fn push(&mut self, slot: T) {
+        if self.len % CHUNK == 0 { self.chunks.push(Arc::new(Vec::with_capacity(CHUNK))) }
+        Arc::make_mut(self.chunks.last_mut().expect("chunk for the slot")).push(slot);
+        self.len += 1;
+    }
+    fn slot(&self, index: usize) -> &T { &self.chunks[index / CHUNK][index % CHUNK] }
+    fn slot_mut(&mut self, index: usize) -> &mut T { &mut Arc::make_mut(&mut self.chunks[index / CHUNK])[index % CHUNK] }
+    fn clear(&mut self) { self.chunks.clear(); self.len = 0 }
+    fn capacity(&self) -> usize { self.chunks.len() * CHUNK }
+}
+
+/// An interval map with the same AVL balancing and max-endpoint augmentation as `IntervalTree`,
+/// but all nodes live in a single storage (a `Vec` by default, see `NodeAlloc`) and refer to
+/// their successors by index instead of one `Box` per node. Slots of removed nodes are reused by
+/// later inserts, so inserting does not allocate once the storage has grown large enough (see
+/// `with_capacity`), and traversals touch memory that lies close together.
+#[derive(Debug)]
+pub struct ArenaIntervalTree<D, S: NodeAlloc<ArenaSlot<D>> = Vec<ArenaSlot<D>>> {
+    slots: S,
+    free: Option<usize>,
+    free_len: usize,
+    root: Option<usize>,
+    len: usize,
+    marker: PhantomData<D>,
+}
+
+/// bound on the height of an AVL tree with fewer than 2^64 nodes, which needs at least F(h+2)-1
+/// nodes for height h with F the Fibonacci numbers
+const MAX_HEIGHT: usize = 92;
+
+/// An iterator over the key,value pairs of an `ArenaIntervalTree` whose keys intersect a range,
+/// in ascending order of the keys. The path to the current node is kept in a fixed-size array
+/// bounded by the maximal height of the tree, so the iterator does not allocate.
+pub struct ArenaIter<'a, D: 'a, S: 'a + NodeAlloc<ArenaSlot<D>> = Vec<ArenaSlot<D>>> {
+    tree: &'a ArenaIntervalTree<D, S>,
+    range: Range,
+    stack: [usize; MAX_HEIGHT],
+    depth: usize,
+    current: Option<usize>,
+}
+
+impl<'a, D: 'a, S: 'a + NodeAlloc<ArenaSlot<D>>> Iterator for ArenaIter<'a, D, S> {
+
+    type Item = (&'a Range, &'a D);
+
+    fn next(&mut self) -> Option<(&'a Range, &'a D)> {
+        loop {
+            while let Some(i) = self.current {
+                let node = self.tree.node(i);
+                if node.max < self.range.min { break }
+                self.stack[self.depth] = i;
+                self.depth += 1;
+                self.current = node.left;
+            }
+            if self.depth == 0 { return None }
+            self.depth -= 1;
+            let node = self.tree.node(self.stack[self.depth]);
+            if node.key.min > self.range.max {
+                self.depth = 0;
+                return None
+            }
+            self.current = node.right;
+            if node.key.intersect(&self.range) { return Some((&node.key, &node.data)) }
+        }
+    }
+}
+
+impl<D> ArenaIntervalTree<D> {
+
+/// This function will construct a new empty ArenaIntervalTree.
+/// # Examples
+/// ```
+/// let t=interval_tree::ArenaIntervalTree::<i32>::new();
+/// assert!(t.empty());
+/// ```
+    pub fn new() -> ArenaIntervalTree<D> {
+        ArenaIntervalTree::with_capacity(0)
+    }
+
+/// This function will construct a new empty ArenaIntervalTree that can hold capacity pairs
+/// without allocating.
+    pub fn with_capacity(capacity: usize) -> ArenaIntervalTree<D> {
+        ArenaIntervalTree::with_alloc(Vec::with_capacity(capacity))
+    }
+}
+
+impl<D, S: NodeAlloc<ArenaSlot<D>>> ArenaIntervalTree<D, S> {
+
+/// This function will construct a new empty ArenaIntervalTree that stores its nodes in slots,
+/// which is cleared first.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use interval_tree::{ArenaIntervalTree, ArenaSlot, NodeAlloc};
+///
+/// // a storage that is allocated once and refuses to grow
+/// struct Fixed<T>(Vec<T>);
+///
+/// impl<T> NodeAlloc<T> for Fixed<T> {
+///     fn len(&self) -> usize { self.0.len() }
+///     fn push(&mut self, slot: T) {
+///         assert!(self.0.len() < self.0.capacity(), "node storage exhausted");
+///         self.0.push(slot)
+///     }
+///     fn slot(&self, index: usize) -> &T { &self.0[index] }
+///     fn slot_mut(&mut self, index: usize) -> &mut T { &mut self.0[index] }
+///     fn clear(&mut self) { self.0.clear() }
+///     fn capacity(&self) -> usize { self.0.capacity() }
+/// }
+///
+/// let mut t: ArenaIntervalTree<u64, Fixed<ArenaSlot<u64>>> = ArenaIntervalTree::with_alloc(Fixed(Vec::with_capacity(16)));
+/// for round in 0..100 {
+///     for i in 0..16 { t.insert(memrange::Range::new(i,i+round), i); }
+///     for i in 0..16 { t.remove(memrange::Range::new(i,i+round)); }
+/// }
+/// assert_eq!(t.capacity(), 16);
+/// ```
+    pub fn with_alloc(mut slots: S) -> ArenaIntervalTree<D, S> {
+        slots.clear();
+        ArenaIntervalTree{slots: slots, free: None, free_len: 0, root: None, len: 0, marker: PhantomData}
+    }
+
+/// This function will return an independent copy of the tree for speculative changes that can
+/// be thrown away again, e.g. to check whether a batch of new keys would overlap the existing
+/// ones. The copy clones the storage, so with a `Vec` every node is copied, while with
+/// `SharedChunks` both trees keep sharing the chunks until one of them writes to a chunk, which is
+/// then copied for the writer.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::{ArenaIntervalTree, SharedChunks};
+///
+/// let mut t = ArenaIntervalTree::with_alloc(SharedChunks::new());
+/// for i in 0..10000 { t.insert(Range::new(i*10, i*10+4), i); }
+/// let mut what_if = t.fork();
+/// let conflicts = (0..100).filter(|i| what_if.range(Range::new(i*95, i*95+2)).next().is_some()).count();
+/// what_if.insert(Range::new(5, 8), 0);
+/// assert_eq!((t.len(), what_if.len(), conflicts), (10000, 10001, 50));
+/// ```
+    pub fn fork(&self) -> ArenaIntervalTree<D, S> where S: Clone {
+        ArenaIntervalTree{slots: self.slots.clone(), free: self.free, free_len: self.free_len, root: self.root, len: self.len, marker: PhantomData}
+    }
+
+/// This function will return the number of pairs the tree can hold without allocating.
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity() - self.slots.len() + self.free_len
+    }
+
+/// This function will return the number of key,value pairs in the tree.
+    pub fn len(&self) -> usize { self.len }
+
+/// This function will return true if the tree is empty, false otherwise.
+    pub fn empty(&self) -> bool { self.root.is_none() }
+
+/// This function will return the height of the tree, 0 for an empty tree.
+    pub fn height(&self) -> usize { self.height_of(self.root) as usize }
+
+/// This function will remove all pairs from the tree, keeping the allocated memory.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.free = None;
+        self.free_len = 0;
+        self.root = None;
+        self.len = 0;
+    }
+
+/// This function will insert the key,value pair into the tree and return the data previously
+/// stored under key, if any. Empty keys are not inserted.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::ArenaIntervalTree::new();
+/// assert_eq!(t.insert(Range::new(2,8),25), None);
+/// assert_eq!(t.insert(Range::new(2,8),30), Some(25));
+/// assert_eq!(t.get(2..=8), Some(&30));
+/// ```
+    pub fn insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let root = self.root;
+        let (root, old) = self.insert_at(root, key, data);
+        self.root = Some(root);
+        old
+    }
+
+/// This function will remove the key,value pair from the tree and return its data, or None if
+/// the key is not part of the tree. The slot of the node is reused by the next insert.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::ArenaIntervalTree::new();
+/// t.insert(Range::new(2,8),25);
+/// assert_eq!(t.remove(Range::new(2,8)), Some(25));
+/// assert_eq!(t.remove(Range::new(2,8)), None);
+/// assert!(t.empty());
+/// ```
+    pub fn remove<K: IntoRange>(&mut self, key: K) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let root = self.root;
+        let (root, removed) = self.remove_at(root, &key);
+        self.root = root;
+        removed.map(|i| self.release(i))
+    }
+
+/// This function will return the data stored under exactly key.
+    pub fn get<K: IntoRange>(&self, key: K) -> Option<&D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let mut current = self.root;
+        while let Some(i) = current {
+            let node = self.node(i);
+            current = match node.key.cmp(&key) {
+                Ordering::Equal => return Some(&node.data),
+                Ordering::Less => node.right,
+                Ordering::Greater => node.left
+            };
+        }
+        None
+    }
+
+/// This function will return an iterator over all key,value pairs in ascending order of the keys.
+    pub fn iter(&self) -> ArenaIter<D, S> {
+        self.range(..)
+    }
+
+/// This function will return an iterator over all key,value pairs whose keys intersect the
+/// bounds, in ascending order of the keys.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::ArenaIntervalTree::new();
+/// t.insert(Range::new(2,8),25);
+/// t.insert(Range::new(5,6),50);
+/// t.insert(Range::new(12,14),75);
+/// assert_eq!(t.range(7..=12).map(|(_,v)| *v).collect::<Vec<i32>>(), vec![25,75]);
+/// ```
+    pub fn range<K: IntoRange>(&self, bounds: K) -> ArenaIter<D, S> {
+        let (range, current) = match bounds.into_range() {
+            Some(range) => (range, self.root),
+            None => (Range::new(0,0), None)
+        };
+        ArenaIter{tree: self, range: range, stack: [0; MAX_HEIGHT], depth: 0, current: current}
+    }
+
+    fn node(&self, i: usize) -> &ArenaNode<D> {
+        match self.slots.slot(i).0 {
+            Slot::Used(ref node) => node,
+            Slot::Free(_) => panic!("index of a free slot")
+        }
+    }
+
+    fn node_mut(&mut self, i: usize) -> &mut ArenaNode<D> {
+        match self.slots.slot_mut(i).0 {
+            Slot::Used(ref mut node) => node,
+            Slot::Free(_) => panic!("index of a free slot")
+        }
+    }
+
+    fn height_of(&self, i: Option<usize>) -> u32 {
+        i.map_or(0, |i| self.node(i).height)
+    }
+
+    fn max_of(&self, i: Option<usize>) -> u64 {
+        i.map_or(0, |i| self.node(i).max)
+    }
+
+    /// stores node in a free slot (or a new one) and returns its index
+    fn allocate(&mut self, key: Range, data: D) -> usize {
+        let node = ArenaNode{key: key, data: data, height: 1, max: key.max, left: None, right: None};
+        self.len += 1;
+        match self.free {
+            Some(i) => {
+                let slot = mem::replace(self.slots.slot_mut(i), ArenaSlot(Slot::Used(node)));
+                self.free = match slot.0 { Slot::Free(next) => next, Slot::Used(_) => panic!("free list broken") };
+                self.free_len -= 1;
+                i
+            },
+            None => { self.slots.push(ArenaSlot(Slot::Used(node))); self.slots.len() - 1 }
+        }
+    }
+
+    /// frees the slot of the detached node i and returns its data
+    fn release(&mut self, i: usize) -> D {
+        self.len -= 1;
+        self.free_len += 1;
+        let slot = mem::replace(self.slots.slot_mut(i), ArenaSlot(Slot::Free(self.free)));
+        self.free = Some(i);
+        match slot.0 { Slot::Used(node) => node.data, Slot::Free(_) => panic!("index of a free slot") }
+    }
+
+    /// updates the cached height and max of i from its successors
+    fn update(&mut self, i: usize) {
+        let (left, right, own) = { let node = self.node(i); (node.left, node.right, node.key.max) };
+        let height = cmp::max(self.height_of(left), self.height_of(right)) + 1;
+        let max = cmp::max(own, cmp::max(self.max_of(left), self.max_of(right)));
+        let node = self.node_mut(i);
+        node.height = height;
+        node.max = max;
+    }
+
+    fn rotate_right(&mut self, i: usize) -> usize {
+        let l = self.node(i).left.expect("Avl broken");
+        let moved = self.node(l).right;
+        self.node_mut(i).left = moved;
+        self.update(i);
+        self.node_mut(l).right = Some(i);
+        self.update(l);
+        l
+    }
+
+    fn rotate_left(&mut self, i: usize) -> usize {
+        let r = self.node(i).right.expect("Avl broken");
+        let moved = self.node(r).left;
+        self.node_mut(i).right = moved;
+        self.update(i);
+        self.node_mut(r).left = Some(i);
+        self.update(r);
+        r
+    }
+
+    /// updates i and applies the rotations necessary to keep the subtree balanced, returns the
+    /// index of the new root of the subtree
+    fn rebalance(&mut self, i: usize) -> usize {
+        self.update(i);
+        let (left, right) = { let node = self.node(i); (node.left, node.right) };
+        let diff = self.height_of(left) as i32 - self.height_of(right) as i32;
+        if diff > 1 {
+            let l = left.expect("Avl broken");
+            if self.height_of(self.node(l).left) < self.height_of(self.node(l).right) {
+                let rotated = self.rotate_left(l);
+                self.node_mut(i).left = Some(rotated);
+            }
+            return self.rotate_right(i)
+        }
+        if diff < -1 {
+            let r = right.expect("Avl broken");
+            if self.height_of(self.node(r).left) > self.height_of(self.node(r).right) {
+                let rotated = self.rotate_right(r);
+                self.node_mut(i).right = Some(rotated);
+            }
+            return self.rotate_left(i)
+        }
+        i
+    }
+
+    fn insert_at(&mut self, root: Option<usize>, key: Range, data: D) -> (usize, Option<D>) {
+        let i = match root { Some(i) => i, None => return (self.allocate(key, data), None) };
+        let (old, dir) = match self.node(i).key.cmp(&key) {
+            Ordering::Equal => {
+                let old = mem::replace(&mut self.node_mut(i).data, data);
+                return (i, Some(old))
+            },
+            Ordering::Less => { let right = self.node(i).right; (self.insert_at(right, key, data), Ordering::Less) },
+            Ordering::Greater => { let left = self.node(i).left; (self.insert_at(left, key, data), Ordering::Greater) }
+        };
+        let (succ, old) = old;
+        match dir {
+            Ordering::Less => self.node_mut(i).right = Some(succ),
+            _ => self.node_mut(i).left = Some(succ)
+        }
+        (self.rebalance(i), old)
+    }
+
+    /// detaches the minimal node of the subtree i, returns the remaining subtree and the index of
+    /// the detached node
+    fn remove_min(&mut self, i: usize) -> (Option<usize>, usize) {
+        match self.node(i).left {
+            None => {
+                let right = self.node_mut(i).right.take();
+                (right, i)
+            },
+            Some(l) => {
+                let (left, min) = self.remove_min(l);
+                self.node_mut(i).left = left;
+                (Some(self.rebalance(i)), min)
+            }
+        }
+    }
+
+    /// detaches the node with key from the subtree root, returns the remaining subtree and the
+    /// index of the detached node
+    fn remove_at(&mut self, root: Option<usize>, key: &Range) -> (Option<usize>, Option<usize>) {
+        let i = match root { Some(i) => i, None => return (None, None) };
+        match self.node(i).key.cmp(key) {
+            Ordering::Less => {
+                let right = self.node(i).right;
+                let (right, removed) = self.remove_at(right, key);
+                self.node_mut(i).right = right;
+                (Some(self.rebalance(i)), removed)
+            },
+            Ordering::Greater => {
+                let left = self.node(i).left;
+                let (left, removed) = self.remove_at(left, key);
+                self.node_mut(i).left = left;
+                (Some(self.rebalance(i)), removed)
+            },
+            Ordering::Equal => {
+                let (left, right) = { let node = self.node_mut(i); (node.left.take(), node.right.take()) };
+                let replacement = match (left, right) {
+                    (None, r) => r,
+                    (l, None) => l,
+                    (l, Some(r)) => {
+                        let (right, min) = self.remove_min(r);
+                        let node = self.node_mut(min);
+                        node.left = l;
+                        node.right = right;
+                        Some(self.rebalance(min))
+                    }
+                };
+                (replacement, Some(i))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn is_arena_tree<D, S: NodeAlloc<ArenaSlot<D>>>(t: &ArenaIntervalTree<D, S>, i: Option<usize>) -> bool {
+    i.map_or(true, |i| {
+        let node = t.node(i);
+        let balanced = (t.height_of(node.left) as i32 - t.height_of(node.right) as i32).abs() <= 1 &&
+            node.height == cmp::max(t.height_of(node.left), t.height_of(node.right)) + 1;
+        let sorted = node.left.map_or(true, |l| t.node(l).key < node.key) && node.right.map_or(true, |r| t.node(r).key > node.key);
+        let proper_max = node.max == cmp::max(node.key.max, cmp::max(t.max_of(node.left), t.max_of(node.right)));
+        balanced && sorted && proper_max && is_arena_tree(t, node.left) && is_arena_tree(t, node.right)
+    })
+}
+
+#[test]
+fn test_arena(){
+    let mut t = ArenaIntervalTree::with_capacity(100);
+    for i in 0..100 { assert_eq!(t.insert(Range::new(i,i+5), i), None) }
+    assert!(is_arena_tree(&t, t.root));
+    assert_eq!(t.height(), 7);
+    for i in 0..100 { if i % 2 == 0 { assert_eq!(t.remove(Range::new(i,i+5)), Some(i)) } }
+    assert!(is_arena_tree(&t, t.root));
+    assert_eq!(t.len(), 50);
+    assert_eq!(t.capacity(), 50);
+    for i in 100..150 { t.insert(Range::new(i,i+5), i); }
+    assert!(is_arena_tree(&t, t.root));
+    assert_eq!(t.slots.len(), 100);
+    assert_eq!(t.range(10..=12).map(|(_,v)| *v).collect::<Vec<u64>>(), vec![5,7,9,11]);
+    assert_eq!(t.iter().count(), 100);
+}
+
+#[test]
+fn test_fork(){
+    let mut t = ArenaIntervalTree::with_alloc(SharedChunks::new());
+    for i in 0..10 * CHUNK as u64 { t.insert(Range::new(i*10, i*10+5), i); }
+    let mut fork = t.fork();
+    fork.insert(Range::new(3, 4), 0);
+    assert!(is_arena_tree(&fork, fork.root) && is_arena_tree(&t, t.root));
+    //only the chunks on the path of the insert and the appended slot are copied
+    let shared = t.slots.chunks.iter().zip(fork.slots.chunks.iter()).filter(|&(a, b)| Arc::ptr_eq(a, b)).count();
+    assert!(shared > 0 && 10 - shared <= t.height());
+    assert_eq!((t.len(), fork.len()), (10 * CHUNK, 10 * CHUNK + 1));
+    assert_eq!(t.range(0..=4).count(), 1);
+    assert_eq!(fork.range(0..=4).count(), 2);
+}
@@ -0,0 +1,92 @@
+use std::io;
+use std::io::{Read, Write};
+
+/// Magic bytes at the start of every saved tree.
+pub const MAGIC: &'static [u8; 4] = b"ITRE";
+/// Version of the binary format written by `IntervalTree::save_to`.
+pub const VERSION: u32 = 1;
+
+/// Types that can be stored as payload in the binary format used by `IntervalTree::save_to` and
+/// `IntervalTree::load_from`. Integers are encoded little endian, variable sized types are
+/// prefixed by their length as u64.
+pub trait BinaryData: Sized {
+    fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    fn read_binary<R: Read>(reader: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! impl_binary_data_int {
+    ($($t:ty),*) => {$(
+        impl BinaryData for $t {
+            fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(&self.to_le_bytes())
+            }
+
+            fn read_binary<R: Read>(reader: &mut R) -> io::Result<$t> {
+                let mut buf = [0u8; ::std::mem::size_of::<$t>()];
+                reader.read_exact(&mut buf)?;
+                Ok(<$t>::from_le_bytes(buf))
+            }
+        }
+    )*}
+}
+
+impl_binary_data_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl BinaryData for () {
+    fn write_binary<W: Write>(&self, _writer: &mut W) -> io::Result<()> { Ok(()) }
+    fn read_binary<R: Read>(_reader: &mut R) -> io::Result<()> { Ok(()) }
+}
+
+impl BinaryData for bool {
+    fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (*self as u8).write_binary(writer)
+    }
+
+    fn read_binary<R: Read>(reader: &mut R) -> io::Result<bool> {
+        match u8::read_binary(reader)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("invalid bool"))
+        }
+    }
+}
+
+impl<T: BinaryData> BinaryData for Vec<T> {
+    fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (self.len() as u64).write_binary(writer)?;
+        for elem in self.iter() {
+            elem.write_binary(writer)?;
+        }
+        Ok(())
+    }
+
+    fn read_binary<R: Read>(reader: &mut R) -> io::Result<Vec<T>> {
+        let len = u64::read_binary(reader)?;
+        let mut res = Vec::new();
+        for _ in 0..len {
+            res.push(T::read_binary(reader)?);
+        }
+        Ok(res)
+    }
+}
+
+impl BinaryData for String {
+    fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (self.len() as u64).write_binary(writer)?;
+        writer.write_all(self.as_bytes())
+    }
+
+    fn read_binary<R: Read>(reader: &mut R) -> io::Result<String> {
+        let len = u64::read_binary(reader)?;
+        let mut bytes = Vec::new();
+        reader.take(len).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) != len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "string truncated"))
+        }
+        String::from_utf8(bytes).map_err(|_| invalid_data("string is not valid utf8"))
+    }
+}
+
+pub fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
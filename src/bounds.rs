@@ -0,0 +1,78 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use std::collections::Bound;
+use std::ops;
+use std::ops::RangeBounds;
+
+/// Types that describe a set of consecutive u64 and can therefore be used wherever the tree
+/// expects a key. Exclusive and unbounded endpoints are normalized to the inclusive `Range` used
+/// internally, e.g. `5..10` becomes `Range::new(5,9)` and `..` becomes `Range::new(0, u64::MAX)`.
+/// A `(min, max)` tuple is read as the inclusive range [min, max]. A `Range` or tuple with
+/// min > max is treated as empty.
+pub trait IntoRange {
+    /// returns the inclusive range described by self, or None if it contains no value at all
+    /// (e.g. `5..5`).
+    fn into_range(self) -> Option<Range>;
+}
+
+/// normalizes arbitrary bounds into an inclusive Range, returning None for empty bounds
+pub fn normalize<R: RangeBounds<u64>>(bounds: &R) -> Option<Range> {
+    let min = match bounds.start_bound() {
+        Bound::Included(&min) => min,
+        Bound::Excluded(&min) => match min.checked_add(1) { Some(min) => min, None => return None },
+        Bound::Unbounded => 0
+    };
+    let max = match bounds.end_bound() {
+        Bound::Included(&max) => max,
+        Bound::Excluded(&max) => match max.checked_sub(1) { Some(max) => max, None => return None },
+        Bound::Unbounded => 0xffff_ffff_ffff_ffff
+    };
+    if min > max { return None }
+    Some(Range::new(min, max))
+}
+
+impl IntoRange for Range {
+    fn into_range(self) -> Option<Range> {
+        if self.min > self.max { return None }
+        Some(self)
+    }
+}
+
+impl<'a> IntoRange for &'a Range {
+    fn into_range(self) -> Option<Range> { (*self).into_range() }
+}
+
+impl IntoRange for (u64, u64) {
+    fn into_range(self) -> Option<Range> {
+        if self.0 > self.1 { return None }
+        Some(Range::new(self.0, self.1))
+    }
+}
+
+macro_rules! impl_into_range_for_bounds {
+    ($($t:ty),*) => {$(
+        impl IntoRange for $t {
+            fn into_range(self) -> Option<Range> { normalize(&self) }
+        }
+    )*}
+}
+
+impl_into_range_for_bounds!(ops::Range<u64>, ops::RangeInclusive<u64>, ops::RangeFrom<u64>,
+                            ops::RangeTo<u64>, ops::RangeToInclusive<u64>, ops::RangeFull,
+                            (Bound<u64>, Bound<u64>));
+
+#[test]
+fn test_normalize(){
+    assert_eq!((5..10).into_range(), Some(Range::new(5,9)));
+    assert_eq!((5..=10).into_range(), Some(Range::new(5,10)));
+    assert_eq!((5..5).into_range(), None);
+    assert_eq!((..0).into_range(), None);
+    assert_eq!((..).into_range(), Some(Range::new(0, 0xffff_ffff_ffff_ffff)));
+    assert_eq!((Bound::Excluded(0xffff_ffff_ffff_ffff), Bound::Unbounded).into_range(), None);
+    assert_eq!((Bound::Excluded(3), Bound::Included(4)).into_range(), Some(Range::new(4,4)));
+    assert_eq!(Range{min: 5, max: 4}.into_range(), None);
+    assert_eq!(Range::new(4,4).into_range(), Some(Range::new(4,4)));
+    assert_eq!((3u64,7u64).into_range(), Some(Range::new(3,7)));
+    assert_eq!((7u64,3u64).into_range(), None);
+}
@@ -0,0 +1,54 @@
+extern crate memrange;
+extern crate chrono;
+
+use self::memrange::Range;
+use self::chrono::{DateTime, TimeZone, Utc};
+use bounds::IntoRange;
+use time_keys::{time_bounds, checked_system_time_key, key_system_time};
+use std::cmp::Ordering;
+use std::ops;
+use std::time::SystemTime;
+
+fn checked_datetime_key<Tz: TimeZone>(t: &DateTime<Tz>) -> Result<u64, Ordering> {
+    checked_system_time_key(&SystemTime::from(t.clone()))
+}
+
+/// This function will return the nanoseconds between the unix epoch and t, or None if t lies
+/// before the epoch or too far after it. Ranges of `DateTime`s in any time zone can be used as
+/// keys directly, like ranges of `SystemTime`, see `time_keys`. Only available with the `chrono`
+/// feature.
+/// # Examples
+/// ```
+/// extern crate chrono;
+/// extern crate interval_tree;
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use chrono::{DateTime, Utc};
+/// use interval_tree::time_keys::{datetime_key, key_datetime};
+///
+/// let start = DateTime::<Utc>::from(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+/// let end = DateTime::<Utc>::from(UNIX_EPOCH + Duration::from_secs(1_700_003_600));
+/// let mut t=interval_tree::IntervalTree::<&str>::new();
+/// t.insert(start.clone()..end, "shift");
+/// let (key, _) = t.range(start.clone()..=start.clone()).next().unwrap();
+/// assert_eq!(key.min, datetime_key(&start).unwrap());
+/// assert_eq!(key_datetime(key.min), start);
+/// ```
+pub fn datetime_key<Tz: TimeZone>(t: &DateTime<Tz>) -> Option<u64> {
+    checked_datetime_key(t).ok()
+}
+
+/// This function will return the point in time key nanoseconds after the unix epoch. Only
+/// available with the `chrono` feature.
+pub fn key_datetime(key: u64) -> DateTime<Utc> {
+    DateTime::<Utc>::from(key_system_time(key))
+}
+
+macro_rules! impl_into_range_for_datetime_bounds {
+    ($($t:ident),*) => {$(
+        impl<Tz: TimeZone> IntoRange for ops::$t<DateTime<Tz>> {
+            fn into_range(self) -> Option<Range> { time_bounds(&self, checked_datetime_key::<Tz>) }
+        }
+    )*}
+}
+
+impl_into_range_for_datetime_bounds!(Range, RangeInclusive, RangeFrom, RangeTo, RangeToInclusive);
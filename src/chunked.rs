@@ -0,0 +1,331 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use bounds::IntoRange;
+use std::cmp;
+use std::mem;
+use std::slice;
+
+/// maximal number of entries of a leaf and of successors of an inner chunk
+const CAPACITY: usize = 32;
+
+/// A node of a `ChunkedIntervalMap`. Leafs hold the key,value pairs sorted by key, inner chunks
+/// hold the summaries of their successors, which all lie at the same depth.
+#[derive(Debug)]
+enum Chunk<D> {
+    Leaf(Vec<(Range, D)>),
+    Inner(Vec<Child<D>>),
+}
+
+/// A successor of an inner chunk together with the smallest key and the maximal end of all keys
+/// stored below it.
+#[derive(Debug)]
+struct Child<D> {
+    first: Range,
+    max: u64,
+    chunk: Box<Chunk<D>>,
+}
+
+impl<D> Chunk<D> {
+    fn len(&self) -> usize {
+        match *self {
+            Chunk::Leaf(ref entries) => entries.len(),
+            Chunk::Inner(ref children) => children.len()
+        }
+    }
+
+    fn summary(&self) -> (Range, u64) {
+        match *self {
+            Chunk::Leaf(ref entries) => (entries[0].0, entries.iter().map(|&(ref k, _)| k.max).max().unwrap_or(0)),
+            Chunk::Inner(ref children) => (children[0].first, children.iter().map(|c| c.max).max().unwrap_or(0))
+        }
+    }
+
+    /// moves the upper half of the chunk into a new chunk, which is returned
+    fn split(&mut self) -> Chunk<D> {
+        let half = self.len() / 2;
+        match *self {
+            Chunk::Leaf(ref mut entries) => Chunk::Leaf(entries.split_off(half)),
+            Chunk::Inner(ref mut children) => Chunk::Inner(children.split_off(half))
+        }
+    }
+
+    /// appends all entries (or successors) of right, which has to be of the same kind
+    fn append(&mut self, right: Chunk<D>) {
+        match (self, right) {
+            (&mut Chunk::Leaf(ref mut entries), Chunk::Leaf(mut more)) => entries.append(&mut more),
+            (&mut Chunk::Inner(ref mut children), Chunk::Inner(mut more)) => children.append(&mut more),
+            _ => unreachable!("chunks of different depth")
+        }
+    }
+}
+
+impl<D> Child<D> {
+    fn new(chunk: Chunk<D>) -> Child<D> {
+        let (first, max) = chunk.summary();
+        Child{first: first, max: max, chunk: Box::new(chunk)}
+    }
+
+    fn update(&mut self) {
+        let (first, max) = self.chunk.summary();
+        self.first = first;
+        self.max = max;
+    }
+}
+
+/// returns the index of the successor whose keys include key
+fn position<D>(children: &[Child<D>], key: &Range) -> usize {
+    match children.binary_search_by(|c| c.first.cmp(key)) {
+        Ok(i) => i,
+        Err(i) => i.saturating_sub(1)
+    }
+}
+
+/// inserts the pair below chunk, returns the data previously stored under key and the upper half
+/// of chunk if it had to be split
+fn insert<D>(chunk: &mut Chunk<D>, key: Range, data: D) -> (Option<D>, Option<Chunk<D>>) {
+    let old = match *chunk {
+        Chunk::Leaf(ref mut entries) => match entries.binary_search_by(|e| e.0.cmp(&key)) {
+            Ok(i) => Some(mem::replace(&mut entries[i].1, data)),
+            Err(i) => { entries.insert(i, (key, data)); None }
+        },
+        Chunk::Inner(ref mut children) => {
+            let i = position(children, &key);
+            let (old, split) = insert(&mut children[i].chunk, key, data);
+            children[i].update();
+            if let Some(split) = split { children.insert(i+1, Child::new(split)) }
+            old
+        }
+    };
+    let split = if chunk.len() > CAPACITY { Some(chunk.split()) } else { None };
+    (old, split)
+}
+
+/// removes key from below chunk and returns its pair. Successors that become less than half
+/// full are merged with a neighbour.
+fn remove<D>(chunk: &mut Chunk<D>, key: &Range) -> Option<(Range, D)> {
+    match *chunk {
+        Chunk::Leaf(ref mut entries) => match entries.binary_search_by(|e| e.0.cmp(key)) {
+            Ok(i) => Some(entries.remove(i)),
+            Err(_) => None
+        },
+        Chunk::Inner(ref mut children) => {
+            let i = position(children, key);
+            let removed = remove(&mut children[i].chunk, key);
+            if removed.is_none() { return None }
+            if children[i].chunk.len() >= CAPACITY / 2 || children.len() == 1 {
+                children[i].update();
+                return removed
+            }
+            let left = if i + 1 < children.len() { i } else { i - 1 };
+            let right = children.remove(left + 1);
+            children[left].chunk.append(*right.chunk);
+            if children[left].chunk.len() > CAPACITY {
+                let split = children[left].chunk.split();
+                children.insert(left + 1, Child::new(split));
+            }
+            let end = cmp::min(left + 2, children.len());
+            for child in children[left..end].iter_mut() { child.update() }
+            removed
+        }
+    }
+}
+
+/// An interval map that packs up to 32 key,value pairs into every leaf, B-tree style, instead of
+/// using one node per pair. Every successor of an inner chunk carries the maximal end of the keys
+/// below it, so overlap queries skip subtrees just like in `IntervalTree`, but walk far fewer
+/// pointers and scan contiguous memory. This pays off for trees with millions of small keys,
+/// while `IntervalTree` offers the much richer interface.
+#[derive(Debug)]
+pub struct ChunkedIntervalMap<D> {
+    root: Chunk<D>,
+    len: usize,
+}
+
+/// An iterator over the key,value pairs of a `ChunkedIntervalMap` whose keys intersect a range, in
+/// ascending order of the keys.
+pub struct ChunkedIter<'a, D: 'a> {
+    range: Range,
+    stack: Vec<slice::Iter<'a, Child<D>>>,
+    leaf: slice::Iter<'a, (Range, D)>,
+}
+
+impl<'a, D: 'a> Iterator for ChunkedIter<'a, D> {
+
+    type Item = (&'a Range, &'a D);
+
+    fn next(&mut self) -> Option<(&'a Range, &'a D)> {
+        loop {
+            while let Some(&(ref key, ref data)) = self.leaf.next() {
+                if key.min > self.range.max { self.stack.clear(); return None }
+                if key.max >= self.range.min { return Some((key, data)) }
+            }
+            let child = match self.stack.last_mut() {
+                Some(children) => children.next(),
+                None => return None
+            };
+            match child {
+                None => { self.stack.pop(); },
+                Some(child) if child.first.min > self.range.max => { self.stack.clear(); return None },
+                Some(child) if child.max < self.range.min => {},
+                Some(child) => match *child.chunk {
+                    Chunk::Leaf(ref entries) => self.leaf = entries.iter(),
+                    Chunk::Inner(ref children) => self.stack.push(children.iter())
+                }
+            }
+        }
+    }
+}
+
+impl<D> ChunkedIntervalMap<D> {
+
+/// This function will construct a new empty ChunkedIntervalMap.
+/// # Examples
+/// ```
+/// let m=interval_tree::ChunkedIntervalMap::<i32>::new();
+/// assert!(m.empty());
+/// ```
+    pub fn new() -> ChunkedIntervalMap<D> {
+        ChunkedIntervalMap{root: Chunk::Leaf(Vec::new()), len: 0}
+    }
+
+/// This function will return the number of key,value pairs in the map.
+    pub fn len(&self) -> usize { self.len }
+
+/// This function will return true if the map is empty, false otherwise.
+    pub fn empty(&self) -> bool { self.len == 0 }
+
+/// This function will return the number of chunks on the path from the root to any leaf, 1 for
+/// a map that fits into a single leaf.
+    pub fn depth(&self) -> usize {
+        let mut depth = 1;
+        let mut chunk = &self.root;
+        while let Chunk::Inner(ref children) = *chunk {
+            depth += 1;
+            chunk = &children[0].chunk;
+        }
+        depth
+    }
+
+/// This function will insert the key,value pair into the map and return the data previously
+/// stored under key, if any. Empty keys are not inserted.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut m=interval_tree::ChunkedIntervalMap::new();
+/// assert_eq!(m.insert(Range::new(2,8),25), None);
+/// assert_eq!(m.insert(Range::new(2,8),30), Some(25));
+/// assert_eq!(m.get(2..=8), Some(&30));
+/// ```
+    pub fn insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let (old, split) = insert(&mut self.root, key, data);
+        if let Some(split) = split {
+            let left = mem::replace(&mut self.root, Chunk::Leaf(Vec::new()));
+            self.root = Chunk::Inner(vec![Child::new(left), Child::new(split)]);
+        }
+        if old.is_none() { self.len += 1 }
+        old
+    }
+
+/// This function will remove the key,value pair from the map and return its data, or None if the
+/// key is not part of the map.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut m=interval_tree::ChunkedIntervalMap::new();
+/// for i in 0..100 { m.insert(Range::new(i,i), i); }
+/// for i in 0..100 { assert_eq!(m.remove(Range::new(i,i)), Some(i)) }
+/// assert!(m.empty());
+/// assert_eq!(m.depth(), 1);
+/// ```
+    pub fn remove<K: IntoRange>(&mut self, key: K) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let removed = remove(&mut self.root, &key);
+        let collapse = match self.root { Chunk::Inner(ref mut children) if children.len() == 1 => children.pop(), _ => None };
+        if let Some(child) = collapse { self.root = *child.chunk }
+        removed.map(|(_, data)| { self.len -= 1; data })
+    }
+
+/// This function will return the data stored under exactly key.
+    pub fn get<K: IntoRange>(&self, key: K) -> Option<&D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let mut chunk = &self.root;
+        loop {
+            match *chunk {
+                Chunk::Inner(ref children) => chunk = &children[position(children, &key)].chunk,
+                Chunk::Leaf(ref entries) => return entries.binary_search_by(|e| e.0.cmp(&key)).ok().map(|i| &entries[i].1)
+            }
+        }
+    }
+
+/// This function will return an iterator over all key,value pairs in ascending order of the keys.
+    pub fn iter(&self) -> ChunkedIter<D> {
+        self.range(..)
+    }
+
+/// This function will return an iterator over all key,value pairs whose keys intersect the
+/// bounds, in ascending order of the keys.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut m=interval_tree::ChunkedIntervalMap::new();
+/// for i in 0..1000 { m.insert(Range::new(i*10,i*10+15), i); }
+/// assert_eq!(m.range(95..=105).map(|(_,v)| *v).collect::<Vec<u64>>(), vec![8,9,10]);
+/// ```
+    pub fn range<K: IntoRange>(&self, bounds: K) -> ChunkedIter<D> {
+        let range = match bounds.into_range() {
+            Some(range) => range,
+            None => return ChunkedIter{range: Range::new(0,0), stack: Vec::new(), leaf: [].iter()}
+        };
+        match self.root {
+            Chunk::Leaf(ref entries) => ChunkedIter{range: range, stack: Vec::new(), leaf: entries.iter()},
+            Chunk::Inner(ref children) => ChunkedIter{range: range, stack: vec![children.iter()], leaf: [].iter()}
+        }
+    }
+}
+
+#[cfg(test)]
+fn is_chunked_tree<D>(chunk: &Chunk<D>, is_root: bool) -> Option<usize> {
+    if chunk.len() > CAPACITY || (!is_root && chunk.len() < CAPACITY / 2) { return None }
+    match *chunk {
+        Chunk::Leaf(ref entries) => {
+            if entries.windows(2).any(|w| w[0].0 >= w[1].0) { return None }
+            Some(1)
+        },
+        Chunk::Inner(ref children) => {
+            let mut depth = None;
+            for child in children {
+                if child.chunk.summary() != (child.first, child.max) { return None }
+                let d = is_chunked_tree(&child.chunk, false);
+                if d.is_none() || (depth.is_some() && depth != d) { return None }
+                depth = d;
+            }
+            depth.map(|d| d + 1)
+        }
+    }
+}
+
+#[test]
+fn test_chunked(){
+    let mut m = ChunkedIntervalMap::new();
+    for i in 0..2000 { assert_eq!(m.insert(Range::new(i,i+5), i), None) }
+    assert_eq!(is_chunked_tree(&m.root, true), Some(m.depth()));
+    assert_eq!(m.depth(), 3);
+    for i in 0..2000 { if i % 3 != 0 { assert_eq!(m.remove(Range::new(i,i+5)), Some(i)) } }
+    assert_eq!(is_chunked_tree(&m.root, true), Some(m.depth()));
+    assert_eq!(m.len(), 667);
+    assert_eq!(m.range(10..=12).map(|(_,v)| *v).collect::<Vec<u64>>(), vec![6,9,12]);
+    assert_eq!(m.iter().count(), 667);
+    assert_eq!(m.get(Range::new(9,14)), Some(&9));
+    assert_eq!(m.get(Range::new(10,15)), None);
+}
@@ -0,0 +1,164 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use bounds::IntoRange;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A part of a `ConcurrentIntervalTree` holding all keys starting at or after start (and before
+/// the start of the next shard). max_end only grows, it is an upper bound on the ends of the keys.
+#[derive(Debug)]
+struct Shard<D> {
+    start: u64,
+    max_end: AtomicU64,
+    tree: RwLock<IntervalTree<D>>,
+}
+
+/// An interval map that can be used from many threads at once. The key space is split into
+/// shards by the start of the keys, each guarded by its own `RwLock`, so readers never block each
+/// other and a writer only blocks the shard of its key. Overlap queries lock, one after another,
+/// only the shards that can hold an overlapping key. As data cannot be borrowed out of a lock,
+/// reading functions return clones or hand references to a closure.
+///
+/// Writes to different shards proceed in parallel, so the shard boundaries should spread the keys
+/// of the workload evenly, see `with_boundaries`.
+#[derive(Debug)]
+pub struct ConcurrentIntervalTree<D> {
+    shards: Vec<Shard<D>>,
+}
+
+impl<D> ConcurrentIntervalTree<D> {
+
+/// This function will construct a new empty ConcurrentIntervalTree that splits the whole u64 key
+/// space into count shards of equal size.
+/// # Examples
+/// ```
+/// let t=interval_tree::ConcurrentIntervalTree::<i32>::new(32);
+/// assert_eq!(t.len(), 0);
+/// ```
+    pub fn new(count: usize) -> ConcurrentIntervalTree<D> {
+        let count = if count == 0 { 1 } else { count as u64 };
+        let width = (u64::max_value() / count).saturating_add(1);
+        ConcurrentIntervalTree::with_boundaries((1..count).map(|i| i * width).collect())
+    }
+
+/// This function will construct a new empty ConcurrentIntervalTree whose shards start at 0 and at
+/// every given boundary, e.g. the quantiles of the expected key starts.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let t=interval_tree::ConcurrentIntervalTree::with_boundaries(vec![100, 200]);
+/// t.insert(Range::new(50,150), "a");
+/// t.insert(Range::new(250,260), "b");
+/// assert_eq!(t.find_overlaps(Range::new(120,255)), vec![(Range::new(50,150),"a"), (Range::new(250,260),"b")]);
+/// ```
+    pub fn with_boundaries(mut boundaries: Vec<u64>) -> ConcurrentIntervalTree<D> {
+        boundaries.push(0);
+        boundaries.sort();
+        boundaries.dedup();
+        let shards = boundaries.into_iter().map(|start| Shard{start: start, max_end: AtomicU64::new(0), tree: RwLock::new(IntervalTree::new())});
+        ConcurrentIntervalTree{shards: shards.collect()}
+    }
+
+/// returns the shard that holds keys starting at p
+    fn shard(&self, p: u64) -> &Shard<D> {
+        let i = match self.shards.binary_search_by(|shard| shard.start.cmp(&p)) {
+            Ok(i) => i,
+            Err(i) => i - 1
+        };
+        &self.shards[i]
+    }
+
+/// This function will insert the key,value pair into the tree, overwriting the old data if the
+/// key is already part of the tree. Empty keys are not inserted.
+    pub fn insert<K: IntoRange>(&self, key: K, data: D) {
+        let key = match key.into_range() { Some(key) => key, None => return };
+        let shard = self.shard(key.min);
+        let mut tree = shard.tree.write().expect("shard lock poisoned");
+        tree.insert(key, data);
+        shard.max_end.fetch_max(key.max, Ordering::Release);
+    }
+
+/// This function will remove the key,value pair from the tree and return its data, or None if
+/// the key is not part of the tree.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let t=interval_tree::ConcurrentIntervalTree::new(4);
+/// t.insert(Range::new(2,8), 25);
+/// assert_eq!(t.remove(Range::new(2,8)), Some(25));
+/// assert_eq!(t.get(Range::new(2,8)), None);
+/// ```
+    pub fn remove<K: IntoRange>(&self, key: K) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let mut tree = self.shard(key.min).tree.write().expect("shard lock poisoned");
+        tree.remove_entry(key).map(|(_, data)| data)
+    }
+
+/// This function will return a clone of the data stored under exactly key.
+    pub fn get<K: IntoRange>(&self, key: K) -> Option<D> where D: Clone {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let tree = self.shard(key.min).tree.read().expect("shard lock poisoned");
+        tree.get(key).cloned()
+    }
+
+/// This function will call f with every key,value pair whose key intersects the bounds, in
+/// ascending order of the keys. The shard being visited is read locked while f runs, so f must
+/// not write to this tree.
+    pub fn for_each_overlap<K: IntoRange, F: FnMut(&Range, &D)>(&self, bounds: K, mut f: F) {
+        let query = match bounds.into_range() { Some(query) => query, None => return };
+        for shard in self.shards.iter().take_while(|shard| shard.start <= query.max) {
+            if shard.max_end.load(Ordering::Acquire) < query.min { continue }
+            let tree = shard.tree.read().expect("shard lock poisoned");
+            for (key, data) in tree.range(query) { f(key, data) }
+        }
+    }
+
+/// This function will return clones of all key,value pairs whose key intersects the bounds, in
+/// ascending order of the keys. Every shard is read consistently, but writes to other shards may
+/// happen while the query runs.
+    pub fn find_overlaps<K: IntoRange>(&self, bounds: K) -> Vec<(Range, D)> where D: Clone {
+        let mut found = Vec::new();
+        self.for_each_overlap(bounds, |key, data| found.push((*key, data.clone())));
+        found
+    }
+
+/// This function will return the number of key,value pairs in the tree.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.tree.read().expect("shard lock poisoned").len()).sum()
+    }
+
+/// This function will take the contents of all shards and return them as a single IntervalTree.
+    pub fn into_tree(self) -> IntervalTree<D> {
+        let mut result = IntervalTree::new();
+        for shard in self.shards {
+            let mut tree = shard.tree.into_inner().expect("shard lock poisoned");
+            result.append(&mut tree);
+        }
+        result
+    }
+}
+
+#[test]
+fn test_concurrent(){
+    let t = ConcurrentIntervalTree::new(7);
+    assert_eq!(t.shards.len(), 7);
+    assert_eq!(t.shard(0).start, 0);
+    assert_eq!(t.shard(u64::max_value()).start, t.shards[6].start);
+    t.insert(Range::new(0, u64::max_value()), 0);
+    for (i, shard) in t.shards.iter().enumerate() {
+        t.insert(Range::new(shard.start, shard.start), i+1);
+    }
+    assert_eq!(t.len(), 8);
+    assert_eq!(t.find_overlaps(Range::new(t.shards[3].start, t.shards[3].start)).len(), 2);
+    assert_eq!(t.find_overlaps(..).iter().map(|&(_, v)| v).collect::<Vec<usize>>(), vec![1,0,2,3,4,5,6,7]);
+    let tree = t.into_tree();
+    assert_eq!(tree.len(), 8);
+}
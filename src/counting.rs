@@ -0,0 +1,106 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use map::IntervalMap;
+use aggregate::Sum;
+use bounds::IntoRange;
+
+/// An interval map that counts the keys intersecting a window in O(log n), however deeply the
+/// keys are nested. Next to the tree, the ends of all keys are kept in a second tree that sums up
+/// how many keys end at each point. A key misses the window [min, max] iff it starts after max or
+/// ends before min, so the count is the rank of max among the starts minus the number of ends
+/// before min. Keeping the ends costs a second O(log n) update on every insert and remove.
+///
+/// All other queries are answered by the underlying tree, see `tree`.
+#[derive(Debug)]
+pub struct CountingIntervalTree<D> {
+    tree: IntervalTree<D>,
+    ends: IntervalTree<u64, Sum>,
+}
+
+impl<D> CountingIntervalTree<D> {
+
+/// This function will construct a new empty CountingIntervalTree.
+/// # Examples
+/// ```
+/// let t=interval_tree::CountingIntervalTree::<i32>::new();
+/// assert!(t.tree().empty());
+/// ```
+    pub fn new() -> CountingIntervalTree<D> {
+        CountingIntervalTree{tree: IntervalTree::new(), ends: IntervalTree::with_aggregate(Sum)}
+    }
+
+/// This function will return the tree holding all pairs.
+    pub fn tree(&self) -> &IntervalTree<D> {
+        &self.tree
+    }
+
+/// This function will insert the key,value pair and return the old data if the key was already
+/// part of the tree. Empty keys are not inserted.
+    pub fn insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let replaced = IntervalMap::insert(&mut self.tree, key, data);
+        if replaced.is_none() {
+            self.ends.insert_with(key.max..=key.max, 1, |old, new| old + new);
+        }
+        replaced
+    }
+
+/// This function will remove the key,value pair from the tree and return its data.
+    pub fn remove<K: IntoRange>(&mut self, key: K) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let (_, data) = match self.tree.remove_entry(key) { Some(pair) => pair, None => return None };
+        if let Some((end, count)) = self.ends.remove_entry(key.max..=key.max) {
+            if count > 1 { self.ends.insert(end, count - 1) }
+        }
+        Some(data)
+    }
+
+/// This function will return the number of stored keys that intersect the bounds in O(log n).
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::CountingIntervalTree::new();
+/// for i in 0..100 { t.insert(Range::new(500-i*5, 500+i*5), i); }
+/// assert_eq!(t.count_in_range(Range::new(0,10)), 2);
+/// assert_eq!(t.count_in_range(Range::new(500,500)), 100);
+/// assert_eq!(t.count_in_range(1000..), 0);
+/// ```
+    pub fn count_in_range<K: IntoRange>(&self, bounds: K) -> usize {
+        let range = match bounds.into_range() { Some(range) => range, None => return 0 };
+        let starting_before = match range.max.checked_add(1) {
+            Some(after) => self.tree.rank(after..=after),
+            None => self.tree.len()
+        };
+        let ending_before = if range.min == 0 { 0 } else { self.ends.aggregate(0..range.min) };
+        starting_before - ending_before as usize
+    }
+}
+
+#[test]
+fn test_counting(){
+    let mut t = CountingIntervalTree::new();
+    let mut keys = Vec::new();
+    for i in 0..400u64 {
+        let key = Range::new(i*37 % 500, i*37 % 500 + 200 - (i*13 % 200));
+        if t.insert(key, i).is_none() { keys.push(key) }
+        if i % 5 == 0 {
+            let key = keys.remove((i as usize * 7) % keys.len());
+            assert!(t.remove(key).is_some());
+            assert_eq!(t.remove(key), None);
+        }
+    }
+    t.insert(Range::new(0, u64::max_value()), 0);
+    keys.push(Range::new(0, u64::max_value()));
+    assert_eq!(t.tree().len(), keys.len());
+    for q in 0..80u64 {
+        let window = Range::new(q*10, q*10 + q % 7);
+        assert_eq!(t.count_in_range(window), keys.iter().filter(|k| k.intersect(&window)).count());
+    }
+    assert_eq!(t.count_in_range(..), keys.len());
+    assert_eq!(t.count_in_range(u64::max_value()..=u64::max_value()), 1);
+}
@@ -0,0 +1,278 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use bounds::IntoRange;
+use aggregate::Aggregate;
+use node::{Node, update_height, min_pair, max_pair};
+use std::cmp::Ordering;
+
+/// A cursor over the pairs of an `IntervalTree` in ascending order of the keys, which can read and
+/// change the data of the pair it points to, look at and move to the neighbouring pairs, and
+/// insert or remove pairs while the tree stays borrowed, see `IntervalTree::lower_bound_mut`.
+///
+/// Besides the pairs there is one more position, the ghost position after the biggest key; moving
+/// past either end of the tree leads there, and moving on from there wraps around to the other
+/// end. As the nodes do not link to their parents, the cursor takes the nodes on the path from the
+/// root to its pair out of the tree and reattaches them when it moves up again, so walking over k
+/// neighbouring pairs takes O(k + log n) steps. Cached values, including aggregates changed via
+/// `value_mut`, are updated on reattaching. Only `insert` and `remove_current` descend from the
+/// root again. The tree is complete again once the cursor is dropped; if the cursor is leaked,
+/// e.g. with `mem::forget`, the tree is left empty.
+pub struct CursorMut<'a, D: 'a, A: 'a + Aggregate<D>> {
+    tree: &'a mut IntervalTree<D, A>,
+    //the ancestors of current with the link to the subtree holding current taken out, root first;
+    //Less means current lies in the right subtree
+    path: Vec<(Box<Node<D, A>>, Ordering)>,
+    //the node the cursor points to, None at the ghost position, where the tree is complete
+    current: Option<Box<Node<D, A>>>,
+}
+
+impl<'a, D: 'a, A: 'a + Aggregate<D>> CursorMut<'a, D, A> {
+
+    //points a cursor to the smallest key that is not smaller than probe
+    fn new(tree: &'a mut IntervalTree<D, A>, probe: &Range) -> CursorMut<'a, D, A> {
+        let mut cursor = CursorMut{tree: tree, path: Vec::new(), current: None};
+        cursor.seek(probe);
+        cursor
+    }
+
+    //puts all nodes back into the tree and moves to the ghost position
+    fn close(&mut self) {
+        let mut subtree = match self.current.take() { Some(node) => node, None => return };
+        update_height(&mut subtree);
+        while let Some((parent, dir)) = self.path.pop() { subtree = attach(parent, dir, subtree) }
+        self.tree.root = Some(subtree);
+    }
+
+    fn seek(&mut self, probe: &Range) {
+        self.close();
+        let mut node = match self.tree.root.take() { Some(node) => node, None => return };
+        loop {
+            let dir = node.key.cmp(probe);
+            let succ = match dir { Ordering::Equal => None, Ordering::Less => node.right.take(), Ordering::Greater => node.left.take() };
+            match succ {
+                Some(succ) => { self.path.push((node, dir)); node = succ }
+                None => {
+                    self.current = Some(node);
+                    if dir == Ordering::Less { self.ascend(Ordering::Greater) }
+                    return
+                }
+            }
+        }
+    }
+
+    //moves from current to its successor in direction dir (Less: right), returns false if there is none
+    fn down(&mut self, dir: Ordering) -> bool {
+        let mut node = self.current.take().expect("cursor at a pair");
+        let succ = match dir { Ordering::Less => node.right.take(), _ => node.left.take() };
+        match succ {
+            Some(succ) => { self.path.push((node, dir)); self.current = Some(succ); true }
+            None => { self.current = Some(node); false }
+        }
+    }
+
+    //moves up until current was reached from direction dir of its parent (Greater: current was in
+    //the left subtree), or to the ghost position if there is no such ancestor
+    fn ascend(&mut self, dir: Ordering) {
+        let mut node = self.current.take().expect("cursor at a pair");
+        //the data of current may have been changed via value_mut
+        update_height(&mut node);
+        while let Some((parent, from)) = self.path.pop() {
+            node = attach(parent, from, node);
+            if from == dir { self.current = Some(node); return }
+        }
+        self.tree.root = Some(node);
+    }
+
+    //moves to the neighbour in direction dir (Less: next), wrapping around at the ghost position
+    fn step(&mut self, dir: Ordering) {
+        if self.current.is_none() {
+            self.current = self.tree.root.take();
+            if self.current.is_none() { return }
+        } else if !self.down(dir) {
+            return self.ascend(dir.reverse())
+        }
+        while self.down(dir.reverse()) {}
+    }
+
+/// This function will return the key the cursor points to, or None at the ghost position.
+    pub fn key(&self) -> Option<&Range> {
+        self.current.as_ref().map(|node| &node.key)
+    }
+
+/// This function will return the data of the pair the cursor points to.
+    pub fn value(&self) -> Option<&D> {
+        self.current.as_ref().map(|node| &node.data)
+    }
+
+/// This function will return a mutable reference to the data of the pair the cursor points to.
+    pub fn value_mut(&mut self) -> Option<&mut D> {
+        self.current.as_mut().map(|node| &mut node.data)
+    }
+
+/// This function will return the pair after the cursor without moving it.
+    pub fn peek_next(&self) -> Option<(&Range, &D)> {
+        let node = match self.current {
+            Some(ref node) => node,
+            None => return self.tree.root.as_ref().map(min_pair)
+        };
+        if let Some(ref succ) = node.right { return Some(min_pair(succ)) }
+        self.path.iter().rev().find(|&&(_, dir)| dir == Ordering::Greater).map(|&(ref parent, _)| (&parent.key, &parent.data))
+    }
+
+/// This function will return the pair before the cursor without moving it.
+    pub fn peek_prev(&self) -> Option<(&Range, &D)> {
+        let node = match self.current {
+            Some(ref node) => node,
+            None => return self.tree.root.as_ref().map(max_pair)
+        };
+        if let Some(ref succ) = node.left { return Some(max_pair(succ)) }
+        self.path.iter().rev().find(|&&(_, dir)| dir == Ordering::Less).map(|&(ref parent, _)| (&parent.key, &parent.data))
+    }
+
+/// This function will move the cursor to the next pair, or to the ghost position after the
+/// biggest key, or from the ghost position to the smallest key.
+    pub fn move_next(&mut self) {
+        self.step(Ordering::Less)
+    }
+
+/// This function will move the cursor to the previous pair, or from the smallest key to the ghost
+/// position, or from the ghost position to the biggest key.
+    pub fn move_prev(&mut self) {
+        self.step(Ordering::Greater)
+    }
+
+/// This function will insert the pair into the tree like `IntervalTree::insert` and move the
+/// cursor to it. If the key is rejected by the universe of the tree, the cursor stays at its pair.
+    pub fn insert<K: IntoRange>(&mut self, key: K, data: D) {
+        let old = self.key().cloned();
+        self.close();
+        match self.tree.clamp_insert(key, data) {
+            Some(key) => self.seek(&key),
+            None => if let Some(old) = old { self.seek(&old) }
+        }
+    }
+
+/// This function will remove the pair the cursor points to and return it. The cursor moves on to
+/// the next pair. At the ghost position nothing is removed.
+    pub fn remove_current(&mut self) -> Option<(Range, D)> {
+        let key = match self.key() { Some(key) => *key, None => return None };
+        self.close();
+        let removed = self.tree.remove_entry(key);
+        self.seek(&key);
+        removed
+    }
+}
+
+impl<'a, D: 'a, A: 'a + Aggregate<D>> Drop for CursorMut<'a, D, A> {
+    fn drop(&mut self) {
+        self.close()
+    }
+}
+
+//reattaches subtree as the successor of parent in direction dir and updates the cached values of parent
+fn attach<D, A: Aggregate<D>>(mut parent: Box<Node<D, A>>, dir: Ordering, subtree: Box<Node<D, A>>) -> Box<Node<D, A>> {
+    match dir {
+        Ordering::Less => parent.right = Some(subtree),
+        _ => parent.left = Some(subtree)
+    }
+    update_height(&mut parent);
+    parent
+}
+
+impl<D, A: Aggregate<D>> IntervalTree<D, A> {
+
+/// This function will return a cursor pointing to the smallest key that starts at or after point,
+/// or to the ghost position if there is none, see `CursorMut`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,4),1);
+/// t.insert(Range::new(5,9),2);
+/// t.insert(Range::new(20,30),3);
+/// //merge the pair at 5 into its adjacent predecessor
+/// {
+///     let mut cursor = t.lower_bound_mut(5);
+///     let (key, data) = cursor.remove_current().unwrap();
+///     assert_eq!(cursor.key(), Some(&Range::new(20,30)));
+///     cursor.move_prev();
+///     let (prev, prev_data) = cursor.remove_current().unwrap();
+///     cursor.insert(Range::new(prev.min, key.max), prev_data + data);
+///     *cursor.value_mut().unwrap() *= 10;
+///     assert_eq!(cursor.peek_next().map(|(_,v)| *v), Some(3));
+/// }
+/// assert_eq!(t.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(), vec![(Range::new(0,9),30), (Range::new(20,30),3)]);
+/// ```
+    pub fn lower_bound_mut(&mut self, point: u64) -> CursorMut<D, A> {
+        //no key starting at point is smaller than this inverted probe
+        CursorMut::new(self, &Range{min: point, max: 0})
+    }
+}
+
+#[test]
+fn test_cursor(){
+    let mut t = IntervalTree::<u64>::new();
+    for i in 0..100 { t.insert(Range::new(i*10, i*10+5), i) }
+    {
+        let mut cursor = t.lower_bound_mut(995);
+        assert_eq!(cursor.key(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.key(), Some(&Range::new(990, 995)));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.key(), Some(&Range::new(0, 5)));
+        assert_eq!(cursor.peek_prev(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.key(), None);
+        assert_eq!(cursor.remove_current(), None);
+    }
+    {
+        let mut cursor = t.lower_bound_mut(0);
+        while cursor.key().is_some() {
+            if *cursor.value().unwrap() % 2 == 1 { cursor.remove_current(); } else { *cursor.value_mut().unwrap() += 1000; cursor.move_next() }
+        }
+    }
+    assert_eq!(t.len(), 50);
+    assert!(t.values().all(|v| *v >= 1000 && *v % 2 == 0));
+}
+
+#[test]
+fn test_cursor_walk(){
+    use aggregate::Sum;
+    let mut t = IntervalTree::<u64, Sum>::with_aggregate(Sum);
+    for i in 0..300 { t.insert(Range::new(i*7 % 1000, i*7 % 1000 + 3), i) }
+    let keys = t.keys().cloned().collect::<Vec<Range>>();
+    {
+        let mut cursor = t.lower_bound_mut(0);
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(cursor.key(), Some(key));
+            assert_eq!(cursor.peek_prev().map(|(k, _)| *k), if i == 0 { None } else { Some(keys[i - 1]) });
+            assert_eq!(cursor.peek_next().map(|(k, _)| *k), keys.get(i + 1).cloned());
+            *cursor.value_mut().unwrap() += 1;
+            cursor.move_next();
+        }
+        assert_eq!(cursor.key(), None);
+        assert_eq!(cursor.peek_prev().map(|(k, _)| *k), keys.last().cloned());
+        for key in keys.iter().rev() {
+            cursor.move_prev();
+            assert_eq!(cursor.key(), Some(key));
+        }
+        cursor.move_prev();
+        assert_eq!(cursor.key(), None);
+    }
+    assert!(t.check_invariants().is_ok());
+    assert_eq!(t.aggregate(..), (0..300).sum::<u64>() + 300);
+    assert_eq!(t.lower_bound_mut(500).key(), keys.iter().find(|k| k.min >= 500));
+    let mut empty = IntervalTree::<u64>::new();
+    let mut cursor = empty.lower_bound_mut(0);
+    cursor.move_next();
+    cursor.move_prev();
+    assert_eq!((cursor.key(), cursor.peek_next(), cursor.peek_prev()), (None, None, None));
+    cursor.insert(Range::new(1, 2), 3);
+    assert_eq!(cursor.value(), Some(&3));
+}
@@ -0,0 +1,114 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use bounds::IntoRange;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, BTreeMap};
+
+/// An interval map whose pairs can carry a deadline, e.g. for leases on address ranges. Expired
+/// pairs are removed with `expire_before`, which only looks at the pairs that actually expire: the
+/// deadlines are kept in a heap next to the tree. Overwriting or removing a pair leaves its old
+/// heap entry behind, which is skipped once it comes up. Deadlines can be of any ordered type,
+/// such as ticks or `std::time::Instant`.
+///
+/// All queries are answered by the underlying tree, see `tree`.
+#[derive(Debug)]
+pub struct ExpiringIntervalTree<D, T: Ord + Copy = u64> {
+    tree: IntervalTree<D>,
+    deadlines: BTreeMap<Range, T>,
+    heap: BinaryHeap<Reverse<(T, Range)>>,
+}
+
+impl<D, T: Ord + Copy> ExpiringIntervalTree<D, T> {
+
+/// This function will construct a new empty ExpiringIntervalTree.
+/// # Examples
+/// ```
+/// let t=interval_tree::ExpiringIntervalTree::<i32>::new();
+/// assert!(t.tree().empty());
+/// ```
+    pub fn new() -> ExpiringIntervalTree<D, T> {
+        ExpiringIntervalTree{tree: IntervalTree::new(), deadlines: BTreeMap::new(), heap: BinaryHeap::new()}
+    }
+
+/// This function will return the tree holding all pairs that have not been expired yet.
+    pub fn tree(&self) -> &IntervalTree<D> {
+        &self.tree
+    }
+
+/// This function will insert the key,value pair without a deadline, overwriting the old data and
+/// deadline if the key is already part of the tree.
+    pub fn insert<K: IntoRange>(&mut self, key: K, data: D) {
+        let key = match key.into_range() { Some(key) => key, None => return };
+        self.deadlines.remove(&key);
+        self.tree.insert(key, data);
+    }
+
+/// This function will insert the key,value pair, which expires at deadline, overwriting the old
+/// data and deadline if the key is already part of the tree.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::ExpiringIntervalTree::new();
+/// t.insert_with_deadline(Range::new(0,4095), "lease a", 100);
+/// t.insert_with_deadline(Range::new(4096,8191), "lease b", 200);
+/// t.insert(Range::new(8192,12287), "static");
+/// assert_eq!(t.expire_before(150), vec![(Range::new(0,4095), "lease a")]);
+/// assert_eq!(t.deadline(Range::new(4096,8191)), Some(200));
+/// assert_eq!(t.tree().len(), 2);
+/// ```
+    pub fn insert_with_deadline<K: IntoRange>(&mut self, key: K, data: D, deadline: T) {
+        let key = match key.into_range() { Some(key) => key, None => return };
+        self.deadlines.insert(key, deadline);
+        self.heap.push(Reverse((deadline, key)));
+        self.tree.insert(key, data);
+    }
+
+/// This function will return the deadline of key, or None if the key does not expire or is not
+/// part of the tree.
+    pub fn deadline<K: IntoRange>(&self, key: K) -> Option<T> {
+        key.into_range().and_then(|key| self.deadlines.get(&key).cloned())
+    }
+
+/// This function will remove the key,value pair from the tree and return its data.
+    pub fn remove<K: IntoRange>(&mut self, key: K) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        self.deadlines.remove(&key);
+        self.tree.remove_entry(key).map(|(_, data)| data)
+    }
+
+/// This function will remove all pairs whose deadline is before t and return them in ascending
+/// order of their deadlines. Pairs without deadline never expire. This takes O(log n) per removed
+/// pair, independent of the number of pairs that stay.
+    pub fn expire_before(&mut self, t: T) -> Vec<(Range, D)> {
+        let mut expired = Vec::new();
+        while self.heap.peek().map_or(false, |&Reverse((deadline, _))| deadline < t) {
+            let Reverse((deadline, key)) = self.heap.pop().expect("peeked");
+            if self.deadlines.get(&key) != Some(&deadline) { continue }
+            self.deadlines.remove(&key);
+            if let Some(pair) = self.tree.remove_entry(key) { expired.push(pair) }
+        }
+        expired
+    }
+}
+
+#[test]
+fn test_expiry(){
+    let mut t = ExpiringIntervalTree::new();
+    for i in 0..100u64 { t.insert_with_deadline(Range::new(i*10, i*10+9), i, 100 - i) }
+    t.insert_with_deadline(Range::new(0, 9), 1000, 500);
+    t.insert(Range::new(10, 19), 1001);
+    assert_eq!(t.remove(Range::new(20, 29)), Some(2));
+    let expired = t.expire_before(50);
+    assert_eq!(expired.len(), 49);
+    assert_eq!(expired.first(), Some(&(Range::new(990, 999), 99)));
+    assert_eq!(expired.last(), Some(&(Range::new(510, 519), 51)));
+    assert_eq!(t.expire_before(100).len(), 48);
+    assert_eq!(t.tree().iter().map(|(_, v)| *v).collect::<Vec<u64>>(), vec![1000, 1001]);
+    assert_eq!(t.expire_before(501), vec![(Range::new(0, 9), 1000)]);
+    assert!(t.heap.is_empty());
+}
@@ -0,0 +1,96 @@
+//! Conversions between f64 values and the u64 keys of the trees, so intervals over real numbers
+//! can be stored without quantizing them. The conversion is monotone and maps the floats other
+//! than NaN, with -0.0 treated as 0.0, onto a run of keys: consecutive keys are neighbouring floats,
+//! so an exclusive bound `a..b` ends at the float right before b and keys are adjacent exactly if
+//! no float lies between them. Infinities are ordinary endpoints, NaN cannot be converted.
+//!
+//! Std ranges of f64 can be used directly wherever a key is expected; a range with a NaN bound is
+//! empty. Queries return keys, which `key_f64` turns back into floats.
+//! # Examples
+//! ```
+//! extern crate interval_tree;
+//! use std::f64;
+//! use interval_tree::float_keys::{f64_key, key_f64};
+//!
+//! let mut t=interval_tree::IntervalTree::<&str>::new();
+//! t.insert(-1.5..=0.25, "a");
+//! t.insert(0.25..1.0, "b");
+//! t.insert(1.0..=f64::INFINITY, "c");
+//! assert_eq!(t.range(0.25..=0.25).map(|(_,v)| *v).collect::<Vec<_>>(), vec!["a", "b"]);
+//! let (b, _) = t.range(0.5..=0.5).next().unwrap();
+//! assert_eq!(key_f64(b.min), 0.25);
+//! assert_eq!(b.max + 1, f64_key(1.0).unwrap());
+//! assert_eq!(t.range(-0.0..=-0.0).count(), 1);
+//! assert_eq!(t.range(f64::NAN..1.0).count(), 0);
+//! ```
+
+extern crate memrange;
+
+use self::memrange::Range;
+use bounds::{IntoRange, normalize};
+use std::collections::Bound;
+use std::ops;
+use std::ops::RangeBounds;
+
+const SIGN: u64 = 0x8000_0000_0000_0000;
+
+/// This function will return the key of x, or None if x is NaN. -0.0 has the same key as 0.0.
+pub fn f64_key(x: f64) -> Option<u64> {
+    if x.is_nan() { return None }
+    let bits = if x == 0.0 { 0 } else { x.to_bits() };
+    //negative floats are ordered by descending bits, the + 1 closes the gap left by -0.0
+    Some(if bits & SIGN == 0 { bits | SIGN } else { !bits + 1 })
+}
+
+/// This function will return the float of key. The keys below negative infinity and above
+/// positive infinity yield NaN.
+pub fn key_f64(key: u64) -> f64 {
+    if key == 0 { return f64::NAN }
+    f64::from_bits(if key & SIGN != 0 { key & !SIGN } else { !(key - 1) })
+}
+
+fn float_bound(bound: Bound<&f64>) -> Option<Bound<u64>> {
+    match bound {
+        Bound::Included(&x) => f64_key(x).map(Bound::Included),
+        Bound::Excluded(&x) => f64_key(x).map(Bound::Excluded),
+        Bound::Unbounded => Some(Bound::Unbounded)
+    }
+}
+
+fn float_bounds<R: RangeBounds<f64>>(bounds: &R) -> Option<Range> {
+    match (float_bound(bounds.start_bound()), float_bound(bounds.end_bound())) {
+        (Some(min), Some(max)) => normalize(&(min, max)),
+        _ => None
+    }
+}
+
+macro_rules! impl_into_range_for_float_bounds {
+    ($($t:ty),*) => {$(
+        impl IntoRange for $t {
+            fn into_range(self) -> Option<Range> { float_bounds(&self) }
+        }
+    )*}
+}
+
+impl_into_range_for_float_bounds!(ops::Range<f64>, ops::RangeInclusive<f64>, ops::RangeFrom<f64>,
+                                  ops::RangeTo<f64>, ops::RangeToInclusive<f64>);
+
+#[test]
+fn test_float_keys(){
+    use std::f64;
+    let values = [f64::NEG_INFINITY, f64::MIN, -1.0, -f64::MIN_POSITIVE, -1e-310, 0.0, 1e-310, f64::MIN_POSITIVE, 1.0, f64::MAX, f64::INFINITY];
+    for pair in values.windows(2) {
+        assert!(f64_key(pair[0]).unwrap() < f64_key(pair[1]).unwrap());
+    }
+    for &x in values.iter() { assert_eq!(key_f64(f64_key(x).unwrap()), x) }
+    assert_eq!(f64_key(-0.0), f64_key(0.0));
+    assert_eq!(f64_key(f64::NAN), None);
+    assert_eq!(key_f64(f64_key(1.0).unwrap() + 1), 1.0 + f64::EPSILON);
+    assert_eq!(key_f64(f64_key(0.0).unwrap() - 1), -5e-324);
+    assert!(key_f64(f64_key(f64::NEG_INFINITY).unwrap() - 1).is_nan());
+    assert!(key_f64(f64_key(f64::INFINITY).unwrap() + 1).is_nan());
+    assert!(key_f64(0).is_nan() && key_f64(0xffff_ffff_ffff_ffff).is_nan());
+    assert_eq!((1.0..1.0).into_range(), None);
+    assert_eq!((..).into_range(), Some(Range::new(0, 0xffff_ffff_ffff_ffff)));
+    assert_eq!((f64::NEG_INFINITY..).into_range().map(|r| key_f64(r.min)), Some(f64::NEG_INFINITY));
+}
@@ -0,0 +1,309 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use binary::{BinaryData, invalid_data};
+use bounds::IntoRange;
+use aggregate::Aggregate;
+use std::borrow::Cow;
+use std::io;
+use std::marker::PhantomData;
+
+/// Magic bytes at the start of every frozen tree.
+pub const FROZEN_MAGIC: &'static [u8; 4] = b"ITRF";
+/// Version of the layout written by `IntervalTree::freeze`.
+pub const FROZEN_VERSION: u32 = 1;
+
+const HEADER: usize = 16;
+const RECORD: usize = 24;
+
+/// A read-only interval tree in a single contiguous buffer that is queried in place, so it can be
+/// written to a file once and then memory-mapped (or read) and used by many processes without
+/// rebuilding a tree, see `IntervalTree::freeze` and `FrozenIntervalTree::from_bytes`.
+///
+/// All integers are little endian and no field needs alignment. The layout is:
+///
+/// * the magic bytes `ITRF`, the version as u32 and the number of keys n as u64
+/// * n records of three u64: start and end of the key and the biggest end of its implicit
+///   subtree. The records are sorted by key; the subtree of the records [lo, hi) is rooted at
+///   (lo + hi) / 2, like in a binary search.
+/// * n + 1 u64 offsets into the payload, the data of key i spans the offsets i to i + 1
+/// * the payload: the data of all keys, encoded with `BinaryData`
+///
+/// A query walks the implicit tree like `IntervalTree::range`, in O(log n + k), and decodes only
+/// the data of the k keys it returns.
+#[derive(Debug, Clone)]
+pub struct FrozenIntervalTree<'a, D> {
+    bytes: Cow<'a, [u8]>,
+    len: usize,
+    marker: PhantomData<fn() -> D>,
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[at..at + 8]);
+    u64::from_le_bytes(buf)
+}
+
+/// returns the biggest end of keys and stores the biggest end of every implicit subtree at the
+/// position of its root in maxes
+fn subtree_maxes(keys: &[Range], maxes: &mut [u64]) -> u64 {
+    if keys.is_empty() { return 0 }
+    let mid = keys.len() / 2;
+    let (left, right) = (subtree_maxes(&keys[..mid], &mut maxes[..mid]), subtree_maxes(&keys[mid + 1..], &mut maxes[mid + 1..]));
+    maxes[mid] = *[left, right, keys[mid].max].iter().max().expect("not empty");
+    maxes[mid]
+}
+
+impl<'a, D: BinaryData> FrozenIntervalTree<'a, D> {
+
+/// This function will use bytes, which have been written by `IntervalTree::freeze`, as a frozen
+/// tree without copying them. The layout is checked completely, which takes O(n) but allocates
+/// nothing; the data itself is only decoded by the queries. Malformed input (wrong header, unknown
+/// version, wrong size, unsorted or invalid keys, wrong subtree ends or offsets) results in an
+/// error.
+/// # Examples
+/// ```
+/// extern crate interval_tree;
+/// use interval_tree::FrozenIntervalTree;
+///
+/// let garbage = [1u8, 2, 3, 4];
+/// assert!(FrozenIntervalTree::<u32>::from_bytes(&garbage).is_err());
+/// ```
+    pub fn from_bytes(bytes: &'a [u8]) -> io::Result<FrozenIntervalTree<'a, D>> {
+        if bytes.len() < HEADER || &bytes[..4] != FROZEN_MAGIC { return Err(invalid_data("not a frozen interval tree")) }
+        let mut version = &bytes[4..8];
+        if u32::read_binary(&mut version)? != FROZEN_VERSION { return Err(invalid_data("unsupported version")) }
+        let len = read_u64(bytes, 8);
+        let payload = len.checked_mul((RECORD + 8) as u64).and_then(|size| size.checked_add((HEADER + 8) as u64));
+        let payload = match payload { Some(payload) if payload <= bytes.len() as u64 => payload as usize, _ => return Err(invalid_data("truncated")) };
+        let frozen = FrozenIntervalTree{bytes: Cow::Borrowed(bytes), len: len as usize, marker: PhantomData};
+        if frozen.offset(frozen.len) != (bytes.len() - payload) as u64 { return Err(invalid_data("wrong payload size")) }
+        for i in 0..frozen.len {
+            let key = frozen.key(i);
+            if key.min > key.max { return Err(invalid_data("invalid range")) }
+            if i > 0 && frozen.key(i - 1) >= key { return Err(invalid_data("keys not sorted")) }
+            if frozen.offset(i) > frozen.offset(i + 1) { return Err(invalid_data("offsets not sorted")) }
+        }
+        if !frozen.proper_maxes(0, frozen.len) { return Err(invalid_data("wrong subtree ends")) }
+        Ok(frozen)
+    }
+
+    fn proper_maxes(&self, lo: usize, hi: usize) -> bool {
+        if lo >= hi { return true }
+        let mid = lo + (hi - lo) / 2;
+        let max = [self.subtree_max(lo, mid), self.subtree_max(mid + 1, hi), self.key(mid).max].iter().cloned().max();
+        max == Some(self.subtree_max(lo, hi)) && self.proper_maxes(lo, mid) && self.proper_maxes(mid + 1, hi)
+    }
+
+    fn key(&self, i: usize) -> Range {
+        let at = HEADER + i * RECORD;
+        Range{min: read_u64(&self.bytes, at), max: read_u64(&self.bytes, at + 8)}
+    }
+
+    /// returns the biggest end in the implicit subtree of the records [lo, hi), 0 if it is empty
+    fn subtree_max(&self, lo: usize, hi: usize) -> u64 {
+        if lo >= hi { return 0 }
+        read_u64(&self.bytes, HEADER + (lo + (hi - lo) / 2) * RECORD + 16)
+    }
+
+    fn offset(&self, i: usize) -> u64 {
+        read_u64(&self.bytes, HEADER + self.len * RECORD + i * 8)
+    }
+
+    fn encoded(&self, i: usize) -> &[u8] {
+        let payload = HEADER + self.len * RECORD + (self.len + 1) * 8;
+        let (start, end) = (payload + self.offset(i) as usize, payload + self.offset(i + 1) as usize);
+        &self.bytes[start..end]
+    }
+
+    fn data(&self, i: usize) -> io::Result<D> {
+        D::read_binary(&mut self.encoded(i))
+    }
+
+/// This function will return the frozen tree as bytes, e.g. to write them to a file.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+/// This function will return the number of key,value pairs in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+/// This function will return an iterator over all key,value pairs whose keys intersect the
+/// bounds, in ascending order of the keys. Each value is decoded when it is yielded, which fails
+/// if the payload was written for a different type of data.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::FrozenIntervalTree;
+///
+/// let mut t=interval_tree::IntervalTree::<String>::new();
+/// t.insert(Range::new(100,199), "exon 1".to_string());
+/// t.insert(Range::new(300,399), "exon 2".to_string());
+/// t.insert(Range::new(150,350), "gene".to_string());
+/// let bytes = t.freeze().as_bytes().to_vec();
+/// //e.g. in another process after mapping the file
+/// let frozen = FrozenIntervalTree::<String>::from_bytes(&bytes).unwrap();
+/// let hits = frozen.find_overlaps(Range::new(320,400)).map(|pair| pair.unwrap().1).collect::<Vec<_>>();
+/// assert_eq!(hits, vec!["gene", "exon 2"]);
+/// ```
+    pub fn find_overlaps<'b, K: IntoRange>(&'b self, bounds: K) -> FrozenIter<'b, 'a, D> {
+        FrozenIter{indices: self.overlap_indices(bounds)}
+    }
+
+/// This function will return an iterator over the positions of the keys that intersect the
+/// bounds, in ascending order. Keys are stored sorted, so the position of a key is its rank, and
+/// `key_at`, `data_at` and `encoded_at` read the pair at a position. Nothing is decoded or
+/// copied, so the positions can be collected, sorted or handed over, e.g. through an FFI, and the
+/// pairs be looked up only where needed.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<u32>::new();
+/// for i in 0..100 { t.insert(Range::new(i*10, i*10+14), i as u32) }
+/// let frozen = t.freeze();
+/// let hits = frozen.overlap_indices(Range::new(200,215)).collect::<Vec<usize>>();
+/// assert_eq!(hits, vec![19, 20, 21]);
+/// assert_eq!(frozen.key_at(20), Some(Range::new(200,214)));
+/// assert_eq!(frozen.data_at(21).unwrap().unwrap(), 21);
+/// assert_eq!(frozen.encoded_at(21), Some(&21u32.to_le_bytes()[..]));
+/// ```
+    pub fn overlap_indices<'b, K: IntoRange>(&'b self, bounds: K) -> FrozenIndices<'b, 'a, D> {
+        let mut stack = Vec::new();
+        let range = bounds.into_range();
+        if range.is_some() { stack.push(Step::Visit(0, self.len)) }
+        FrozenIndices{tree: self, range: range.unwrap_or(Range{min: 0, max: 0}), stack: stack}
+    }
+
+/// This function will return the key at position i, or None if i is not smaller than `len`.
+    pub fn key_at(&self, i: usize) -> Option<Range> {
+        if i < self.len { Some(self.key(i)) } else { None }
+    }
+
+/// This function will decode and return the data at position i, or None if i is not smaller than
+/// `len`.
+    pub fn data_at(&self, i: usize) -> Option<io::Result<D>> {
+        if i < self.len { Some(self.data(i)) } else { None }
+    }
+
+/// This function will return the data at position i as encoded by `BinaryData`, a slice of the
+/// buffer of the tree, or None if i is not smaller than `len`.
+    pub fn encoded_at(&self, i: usize) -> Option<&[u8]> {
+        if i < self.len { Some(self.encoded(i)) } else { None }
+    }
+}
+
+enum Step {
+    Visit(usize, usize),
+    Report(usize),
+}
+
+/// An iterator over the positions of the keys of a `FrozenIntervalTree` that intersect a range,
+/// see `FrozenIntervalTree::overlap_indices`.
+pub struct FrozenIndices<'b, 'a: 'b, D: 'b> {
+    tree: &'b FrozenIntervalTree<'a, D>,
+    range: Range,
+    stack: Vec<Step>,
+}
+
+impl<'b, 'a: 'b, D: BinaryData> Iterator for FrozenIndices<'b, 'a, D> {
+
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while let Some(step) = self.stack.pop() {
+            match step {
+                Step::Report(i) => {
+                    if self.tree.key(i).intersect(&self.range) { return Some(i) }
+                },
+                Step::Visit(lo, hi) => {
+                    if lo >= hi || self.tree.subtree_max(lo, hi) < self.range.min { continue }
+                    let mid = lo + (hi - lo) / 2;
+                    if self.tree.key(mid).min <= self.range.max {
+                        self.stack.push(Step::Visit(mid + 1, hi));
+                        self.stack.push(Step::Report(mid));
+                    }
+                    self.stack.push(Step::Visit(lo, mid));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the pairs of a `FrozenIntervalTree` whose keys intersect a range, see
+/// `FrozenIntervalTree::find_overlaps`.
+pub struct FrozenIter<'b, 'a: 'b, D: 'b> {
+    indices: FrozenIndices<'b, 'a, D>,
+}
+
+impl<'b, 'a: 'b, D: BinaryData> Iterator for FrozenIter<'b, 'a, D> {
+
+    type Item = io::Result<(Range, D)>;
+
+    fn next(&mut self) -> Option<io::Result<(Range, D)>> {
+        let tree = self.indices.tree;
+        self.indices.next().map(|i| tree.data(i).map(|data| (tree.key(i), data)))
+    }
+}
+
+impl<D: BinaryData, A: Aggregate<D>> IntervalTree<D, A> {
+
+/// This function will write the tree into the contiguous layout of a `FrozenIntervalTree`, which
+/// can be queried right away or saved via `FrozenIntervalTree::as_bytes` and later used in place
+/// with `FrozenIntervalTree::from_bytes`.
+    pub fn freeze(&self) -> FrozenIntervalTree<'static, D> {
+        let keys = self.keys().cloned().collect::<Vec<_>>();
+        let mut maxes = vec![0; keys.len()];
+        subtree_maxes(&keys, &mut maxes);
+        let mut payload = Vec::new();
+        let mut offsets = Vec::with_capacity(keys.len() + 1);
+        offsets.push(0);
+        for data in self.values() {
+            data.write_binary(&mut payload).expect("writing to a vec does not fail");
+            offsets.push(payload.len() as u64);
+        }
+        let mut bytes = Vec::with_capacity(HEADER + keys.len() * RECORD + offsets.len() * 8 + payload.len());
+        bytes.extend_from_slice(FROZEN_MAGIC);
+        bytes.extend_from_slice(&FROZEN_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(keys.len() as u64).to_le_bytes());
+        for (key, max) in keys.iter().zip(maxes.iter()) {
+            for x in &[key.min, key.max, *max] { bytes.extend_from_slice(&x.to_le_bytes()) }
+        }
+        for offset in offsets.iter() { bytes.extend_from_slice(&offset.to_le_bytes()) }
+        bytes.extend_from_slice(&payload);
+        FrozenIntervalTree{bytes: Cow::Owned(bytes), len: keys.len(), marker: PhantomData}
+    }
+}
+
+#[test]
+fn test_frozen(){
+    let mut t = IntervalTree::<u64>::new();
+    for i in 0..1000u64 { t.insert(Range::new(i * 37 % 5000, i * 37 % 5000 + i % 300), i) }
+    let frozen = t.freeze();
+    let bytes = frozen.as_bytes().to_vec();
+    let frozen = FrozenIntervalTree::<u64>::from_bytes(&bytes).unwrap();
+    assert_eq!(frozen.len(), t.len());
+    for start in (0..5500).step_by(97) {
+        let query = Range::new(start, start + start % 50);
+        let expected = t.range(query).map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+        assert_eq!(frozen.find_overlaps(query).collect::<io::Result<Vec<_>>>().unwrap(), expected);
+        let indices = frozen.overlap_indices(query).collect::<Vec<_>>();
+        assert_eq!(indices.iter().map(|&i| frozen.key_at(i).unwrap()).collect::<Vec<_>>(), expected.iter().map(|p| p.0).collect::<Vec<_>>());
+        assert!(indices.iter().all(|&i| frozen.key_at(i).map(|k| t.rank(k)) == Some(i)));
+    }
+    assert_eq!((frozen.key_at(t.len()), frozen.encoded_at(t.len()).is_none()), (None, true));
+    assert_eq!(frozen.find_overlaps(..).count(), t.len());
+    assert_eq!(IntervalTree::<u64>::new().freeze().find_overlaps(..).count(), 0);
+    let mut broken = bytes.clone();
+    broken[HEADER + 16] ^= 1;
+    assert!(FrozenIntervalTree::<u64>::from_bytes(&broken).is_err());
+    assert!(FrozenIntervalTree::<u64>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+}
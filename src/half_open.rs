@@ -0,0 +1,103 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use bounds::IntoRange;
+use std::ops;
+
+/// A half-open range `[start, end)` of u64. It can be used as key wherever the tree expects one
+/// and is converted to the inclusive `Range` used internally, so overlap queries follow half-open
+/// semantics: `[0, 10)` and `[10, 20)` do not overlap. Ranges with `end <= start` are empty; they
+/// are never inserted and never overlap anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RangeExclusive {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl RangeExclusive {
+
+/// This function will construct the half-open range [start, end).
+/// # Examples
+/// ```
+/// let r=interval_tree::RangeExclusive::new(0x1000, 0x2000);
+/// assert_eq!(r.len(), 0x1000);
+/// ```
+    pub fn new(start: u64, end: u64) -> RangeExclusive {
+        RangeExclusive{start: start, end: end}
+    }
+
+/// This function will convert an inclusive Range into the equivalent half-open range. Ranges
+/// ending at u64::MAX have no half-open representation and return None.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use interval_tree::RangeExclusive;
+///
+/// assert_eq!(RangeExclusive::from_inclusive(&memrange::Range::new(3,9)), Some(RangeExclusive::new(3,10)));
+/// assert_eq!(RangeExclusive::from_inclusive(&memrange::Range::new(3,0xffff_ffff_ffff_ffff)), None);
+/// ```
+    pub fn from_inclusive(range: &Range) -> Option<RangeExclusive> {
+        range.max.checked_add(1).map(|end| RangeExclusive::new(range.min, end))
+    }
+
+/// This function will return true if the range contains no value.
+    pub fn is_empty(&self) -> bool { self.end <= self.start }
+
+/// This function will return the number of values in the range.
+    pub fn len(&self) -> u64 { self.end.saturating_sub(self.start) }
+
+/// This function will return true if both ranges share at least one value.
+/// # Examples
+/// ```
+/// use interval_tree::RangeExclusive;
+///
+/// assert!(RangeExclusive::new(0,10).overlaps(&RangeExclusive::new(9,20)));
+/// assert!(!RangeExclusive::new(0,10).overlaps(&RangeExclusive::new(10,20)));
+/// assert!(!RangeExclusive::new(5,5).overlaps(&RangeExclusive::new(0,10)));
+/// ```
+    pub fn overlaps(&self, other: &RangeExclusive) -> bool {
+        !self.is_empty() && !other.is_empty() && self.start < other.end && other.start < self.end
+    }
+}
+
+impl IntoRange for RangeExclusive {
+    fn into_range(self) -> Option<Range> {
+        if self.is_empty() { return None }
+        Some(Range::new(self.start, self.end - 1))
+    }
+}
+
+impl From<ops::Range<u64>> for RangeExclusive {
+    fn from(range: ops::Range<u64>) -> RangeExclusive { RangeExclusive::new(range.start, range.end) }
+}
+
+impl From<RangeExclusive> for ops::Range<u64> {
+    fn from(range: RangeExclusive) -> ops::Range<u64> { range.start..range.end }
+}
+
+impl<'a> IntoRange for &'a RangeExclusive {
+    fn into_range(self) -> Option<Range> { (*self).into_range() }
+}
+
+#[test]
+fn test_half_open(){
+    assert_eq!(RangeExclusive::new(0,0).into_range(), None);
+    assert_eq!(RangeExclusive::new(7,3).into_range(), None);
+    assert_eq!(RangeExclusive::new(0,1).into_range(), Some(Range::new(0,0)));
+    assert_eq!(RangeExclusive::new(1,0xffff_ffff_ffff_ffff).into_range(), Some(Range::new(1,0xffff_ffff_ffff_fffe)));
+    for &(start, end) in [(0u64,1u64), (5,10), (0,0xffff_ffff_ffff_ffff)].iter() {
+        let r = RangeExclusive::new(start, end);
+        assert_eq!(RangeExclusive::from_inclusive(&r.into_range().unwrap()), Some(r));
+    }
+    assert_eq!(RangeExclusive::from(3..8), RangeExclusive::new(3,8));
+    assert_eq!(ops::Range::from(RangeExclusive::new(3,8)), 3..8);
+    let mut t = ::tree::IntervalTree::<i32>::new();
+    t.insert(RangeExclusive::new(0,10), 1);
+    t.insert(RangeExclusive::new(10,20), 2);
+    t.insert(RangeExclusive::new(30,30), 3);
+    assert_eq!(t.first_overlap(RangeExclusive::new(5,10)).map(|(_,v)| *v), Some(1));
+    assert_eq!(t.last_overlap(RangeExclusive::new(5,11)).map(|(_,v)| *v), Some(2));
+    assert_eq!(t.first_overlap(RangeExclusive::new(20,40)), None);
+    assert_eq!(t.get(RangeExclusive::new(0,10)), Some(&1));
+}
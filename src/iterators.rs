@@ -2,67 +2,97 @@ extern crate memrange;
 
 use ::tree;
 use self::memrange::Range;
-use ::node::Node;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::iter::Peekable;
+use ::aggregate::{Aggregate, NoAggregate};
+use ::node::{Node, first_overlap, first_overlap_after, search_mut};
 
 enum VisitingState {
-    VisitLeft,
+    VisitFirst,
     VisitCenter, 
-    VisitRight
+    VisitSecond
 }
 
-pub struct RangePairIter<'a,D:'a> {
+/// The order in which an iterator yields the keys of a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// smallest key first
+    Ascending,
+    /// biggest key first
+    Descending,
+}
+
+pub struct RangePairIter<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
     //tree: &'a tree::IntervalTree<D>,
     range: Range,
-    stack: Vec<(&'a Node<D>, VisitingState)>
+    direction: Direction,
+    stack: Vec<(&'a Node<D, A>, VisitingState)>
 }
 
 
-impl<'a, D:'a> RangePairIter<'a, D> {
+impl<'a, D:'a, A:'a + Aggregate<D>> RangePairIter<'a, D, A> {
 
-    pub fn new(tree: &'a tree::IntervalTree<D>, lower: u64, upper: u64) -> RangePairIter<'a,D>{
+    pub fn new(tree: &'a tree::IntervalTree<D, A>, lower: u64, upper: u64) -> RangePairIter<'a, D, A>{
+        RangePairIter::new_directed(tree, lower, upper, Direction::Ascending)
+    }
+
+    pub fn new_directed(tree: &'a tree::IntervalTree<D, A>, lower: u64, upper: u64, direction: Direction) -> RangePairIter<'a, D, A>{
         let mut stack = Vec::with_capacity(tree.height());
-        if let Some(ref root) = tree.root {
-            stack.push( (&**root,VisitingState::VisitLeft) );
+        let range = tree.clip_query(lower, upper);
+        if let (&Some(ref root), Some(_)) = (&tree.root, range) {
+            stack.push( (&**root,VisitingState::VisitFirst) );
         }
         //RangePairIter{tree: tree, range: Range::new(lower,upper), stack: stack}
-        RangePairIter{ range: Range::new(lower,upper), stack: stack}
+        RangePairIter{ range: range.unwrap_or(Range::new(lower,upper)), direction: direction, stack: stack}
     }
 
-    pub fn visit_left(&mut self, node: &'a Node<D>) {
+    pub fn empty() -> RangePairIter<'a, D, A>{
+        RangePairIter{ range: Range::new(0,0), direction: Direction::Ascending, stack: Vec::new()}
+    }
+
+    pub fn visit_left(&mut self, node: &'a Node<D, A>) {
         //println!("left {:?}", node.key);
-        match node.left {
-            Some(ref lsucc) => {
-                self.stack.push( (node, VisitingState::VisitCenter) );
-                if node.left_subtree_relevant(&self.range) { 
-                    self.stack.push( (&**lsucc, VisitingState::VisitLeft) ) 
-                }
-            },
-            None => self.stack.push( (node, VisitingState::VisitCenter) )
+        if !node.left_subtree_relevant(&self.range) { return }
+        if let Some(ref lsucc) = node.left {
+            self.stack.push( (&**lsucc, VisitingState::VisitFirst) )
         }
     }
 
-    pub fn visit_right(&mut self, node: &'a Node<D>) {
+    pub fn visit_right(&mut self, node: &'a Node<D, A>) {
         //println!("right {:?}", node.key);
         if !node.right_subtree_relevant(&self.range) { return }
-        match node.right {
-            Some(ref rsucc) => {
-                self.stack.push((&**rsucc, VisitingState::VisitLeft));
-            },
-            None => return
+        if let Some(ref rsucc) = node.right {
+            self.stack.push((&**rsucc, VisitingState::VisitFirst));
+        }
+    }
+
+    fn visit_first(&mut self, node: &'a Node<D, A>) {
+        self.stack.push( (node, VisitingState::VisitCenter) );
+        match self.direction {
+            Direction::Ascending => self.visit_left(node),
+            Direction::Descending => self.visit_right(node)
         }
     }
 
-    pub fn visit_center(&mut self, node: &'a Node<D>) -> Option<&'a Node<D>>{
+    fn visit_second(&mut self, node: &'a Node<D, A>) {
+        match self.direction {
+            Direction::Ascending => self.visit_right(node),
+            Direction::Descending => self.visit_left(node)
+        }
+    }
+
+    pub fn visit_center(&mut self, node: &'a Node<D, A>) -> Option<&'a Node<D, A>>{
         //println!("center {:?}", node.key);
-        self.stack.push((node, VisitingState::VisitRight));
+        self.stack.push((node, VisitingState::VisitSecond));
         if node.key.intersect(&self.range){ return Some(node) } else { return self.get_next_node() }
     }
 
-    pub fn get_next_node(&mut self) -> Option<&'a Node<D>>{
+    pub fn get_next_node(&mut self) -> Option<&'a Node<D, A>>{
         if let Some((node, state)) = self.stack.pop() {
             match state {
-                VisitingState::VisitLeft => {self.visit_left(node); return self.get_next_node()},
-                VisitingState::VisitRight => {self.visit_right(node); return self.get_next_node()}
+                VisitingState::VisitFirst => {self.visit_first(node); return self.get_next_node()},
+                VisitingState::VisitSecond => {self.visit_second(node); return self.get_next_node()}
                 VisitingState::VisitCenter => return self.visit_center(node),
             }
         } else {
@@ -71,7 +101,7 @@ impl<'a, D:'a> RangePairIter<'a, D> {
     }
 }
 
-impl<'a, D:'a> Iterator for RangePairIter<'a, D> {
+impl<'a, D:'a, A:'a + Aggregate<D>> Iterator for RangePairIter<'a, D, A> {
 
     type Item = (&'a Range,&'a D);
 
@@ -80,6 +110,356 @@ impl<'a, D:'a> Iterator for RangePairIter<'a, D> {
     }
 }
 
+/// An iterator over the keys of a tree in ascending order, see `IntervalTree::keys`.
+pub struct Keys<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
+    inner: RangePairIter<'a, D, A>
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Keys<'a, D, A> {
+
+    pub fn new(inner: RangePairIter<'a, D, A>) -> Keys<'a, D, A>{
+        Keys{ inner: inner }
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Iterator for Keys<'a, D, A> {
+
+    type Item = &'a Range;
+
+    fn next(&mut self) -> Option<&'a Range> {
+        self.inner.next().map(|(k,_)| k)
+    }
+}
+
+/// An iterator over the data of a tree in ascending order of the keys, see `IntervalTree::values`.
+pub struct Values<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
+    inner: RangePairIter<'a, D, A>
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Values<'a, D, A> {
+
+    pub fn new(inner: RangePairIter<'a, D, A>) -> Values<'a, D, A>{
+        Values{ inner: inner }
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Iterator for Values<'a, D, A> {
+
+    type Item = &'a D;
+
+    fn next(&mut self) -> Option<&'a D> {
+        self.inner.next().map(|(_,v)| v)
+    }
+}
+
+/// An iterator over mutable references to the data of a tree in ascending order of the keys, see
+/// `IntervalTree::values_mut`.
+pub struct ValuesMut<'a, D:'a> {
+    stack: Vec<(&'a mut D, Option<&'a mut Box<Node<D>>>)>
+}
+
+impl<'a, D:'a> ValuesMut<'a, D> {
+
+    pub fn new(root: Option<&'a mut Box<Node<D>>>) -> ValuesMut<'a, D>{
+        let mut iter = ValuesMut{ stack: Vec::new() };
+        iter.push_left_spine(root);
+        iter
+    }
+
+    fn push_left_spine(&mut self, mut node: Option<&'a mut Box<Node<D>>>) {
+        while let Some(succ) = node {
+            let Node{ref mut data, ref mut left, ref mut right, ..} = **succ;
+            self.stack.push((data, right.as_mut()));
+            node = left.as_mut();
+        }
+    }
+}
+
+impl<'a, D:'a> Iterator for ValuesMut<'a, D> {
+
+    type Item = &'a mut D;
+
+    fn next(&mut self) -> Option<&'a mut D> {
+        match self.stack.pop() {
+            Some((data, right)) => { self.push_left_spine(right); Some(data) },
+            None => None
+        }
+    }
+}
+
+/// An iterator over the maximal parts of a window that are not covered by any key of a tree, in
+/// ascending order, see `IntervalTree::gaps`.
+pub struct GapIter<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
+    inner: RangePairIter<'a, D, A>,
+    next: Option<u64>,
+    max: u64
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> GapIter<'a, D, A> {
+
+    pub fn new(tree: &'a tree::IntervalTree<D, A>, lower: u64, upper: u64) -> GapIter<'a, D, A>{
+        GapIter{ inner: RangePairIter::new(tree, lower, upper), next: Some(lower), max: upper }
+    }
+
+    pub fn empty() -> GapIter<'a, D, A>{
+        GapIter{ inner: RangePairIter::empty(), next: None, max: 0 }
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Iterator for GapIter<'a, D, A> {
+
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Range> {
+        while let Some(start) = self.next {
+            match self.inner.next() {
+                Some((key, _)) if key.min > start => {
+                    self.next = key.max.checked_add(1).and_then(|n| if n <= self.max { Some(n) } else { None });
+                    return Some(Range::new(start, key.min-1))
+                },
+                Some((key, _)) => if key.max >= start {
+                    self.next = key.max.checked_add(1).and_then(|n| if n <= self.max { Some(n) } else { None });
+                },
+                None => {
+                    self.next = None;
+                    return Some(Range::new(start, self.max))
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Which keys a `ContainmentIter` yields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Containment {
+    /// keys that lie completely inside the window
+    Inside,
+    /// keys that completely cover the window
+    Around,
+}
+
+/// An iterator over the keys of a tree that lie completely inside a window or that completely
+/// cover it, in ascending order, see `IntervalTree::find_contained` and
+/// `IntervalTree::find_enclosing`. Subtrees that can not hold such a key are skipped based on
+/// the cached extremes of their starts and ends.
+pub struct ContainmentIter<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
+    window: Range,
+    containment: Containment,
+    stack: Vec<&'a Node<D, A>>,
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> ContainmentIter<'a, D, A> {
+
+    pub fn contained(tree: &'a tree::IntervalTree<D, A>, window: Range) -> ContainmentIter<'a, D, A> {
+        ContainmentIter::new(tree, window, Containment::Inside)
+    }
+
+    pub fn enclosing(tree: &'a tree::IntervalTree<D, A>, window: Range) -> ContainmentIter<'a, D, A> {
+        ContainmentIter::new(tree, window, Containment::Around)
+    }
+
+    pub fn empty() -> ContainmentIter<'a, D, A> {
+        ContainmentIter{window: Range::new(0, 0), containment: Containment::Inside, stack: Vec::new()}
+    }
+
+    fn new(tree: &'a tree::IntervalTree<D, A>, window: Range, containment: Containment) -> ContainmentIter<'a, D, A> {
+        let mut iter = ContainmentIter{window: window, containment: containment, stack: Vec::with_capacity(tree.height())};
+        iter.push_left(&tree.root);
+        iter
+    }
+
+    /// returns true if the subtree below node may hold a key to yield
+    fn relevant(&self, node: &Node<D, A>) -> bool {
+        let w = &self.window;
+        match self.containment {
+            Containment::Inside => node.subtree_max_start() >= w.min && node.subtree_min_start() <= w.max && node.subtree_min_end() <= w.max,
+            Containment::Around => node.subtree_min_start() <= w.min && node.subtree_max_end() >= w.max
+        }
+    }
+
+    fn push_left(&mut self, mut link: &'a Option<Box<Node<D, A>>>) {
+        while let Some(ref node) = *link {
+            if !self.relevant(node) { return }
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Iterator for ContainmentIter<'a, D, A> {
+
+    type Item = (&'a Range, &'a D);
+
+    fn next(&mut self) -> Option<(&'a Range, &'a D)> {
+        while let Some(node) = self.stack.pop() {
+            self.push_left(&node.right);
+            let (key, w) = (&node.key, &self.window);
+            let hit = match self.containment {
+                Containment::Inside => w.min <= key.min && key.max <= w.max,
+                Containment::Around => key.min <= w.min && w.max <= key.max
+            };
+            if hit { return Some((&node.key, &node.data)) }
+        }
+        None
+    }
+}
+
+/// distance between a key and the point p, 0 if the key contains p
+pub fn distance(key: &Range, p: u64) -> u64 {
+    if key.min > p { key.min - p } else { p.saturating_sub(key.max) }
+}
+
+//an entry of the search front of `NearestIter`: either the key of node alone (exact distance) or
+//the whole subtree of node (lower bound of the distance)
+struct Candidate<'a, D:'a, A:'a + Aggregate<D>> {
+    distance: u64,
+    start: u64,
+    whole_subtree: bool,
+    node: &'a Node<D, A>
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Candidate<'a, D, A> {
+
+    fn order(&self) -> (u64, u64, bool) {
+        (self.distance, self.start, !self.whole_subtree)
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> PartialEq for Candidate<'a, D, A> {
+    fn eq(&self, other: &Self) -> bool { self.order() == other.order() }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Eq for Candidate<'a, D, A> {}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> PartialOrd for Candidate<'a, D, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Ord for Candidate<'a, D, A> {
+    //reversed, so that the BinaryHeap pops the closest candidate first
+    fn cmp(&self, other: &Self) -> Ordering { other.order().cmp(&self.order()) }
+}
+
+/// An iterator over the pairs of a tree in increasing distance of their keys to a point, yielding
+/// each key together with its distance, see `IntervalTree::nearest`.
+pub struct NearestIter<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
+    point: u64,
+    front: BinaryHeap<Candidate<'a, D, A>>
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> NearestIter<'a, D, A> {
+
+    pub fn new(tree: &'a tree::IntervalTree<D, A>, point: u64) -> NearestIter<'a, D, A>{
+        let mut iter = NearestIter{ point: point, front: BinaryHeap::new() };
+        if let Some(ref root) = tree.root { iter.push_subtree(root) }
+        iter
+    }
+
+    fn push_subtree(&mut self, node: &'a Node<D, A>) {
+        self.front.push(Candidate{ distance: node.subtree_distance(self.point), start: node.subtree_min_start(),
+                                   whole_subtree: true, node: node });
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Iterator for NearestIter<'a, D, A> {
+
+    type Item = (&'a Range, &'a D, u64);
+
+    fn next(&mut self) -> Option<(&'a Range, &'a D, u64)> {
+        while let Some(candidate) = self.front.pop() {
+            let node = candidate.node;
+            if !candidate.whole_subtree { return Some((&node.key, &node.data, candidate.distance)) }
+            self.front.push(Candidate{ distance: distance(&node.key, self.point), start: node.key.min,
+                                       whole_subtree: false, node: node });
+            if let Some(ref succ) = node.left { self.push_subtree(succ) }
+            if let Some(ref succ) = node.right { self.push_subtree(succ) }
+        }
+        None
+    }
+}
+
+/// An iterator that moves all (key,value) pairs out of a tree in ascending order of their keys.
+pub struct IntoIter<D, A: Aggregate<D> = NoAggregate> {
+    stack: Vec<Box<Node<D, A>>>
+}
+
+impl<D, A: Aggregate<D>> IntoIter<D, A> {
+
+    pub fn new(root: Option<Box<Node<D, A>>>) -> IntoIter<D, A>{
+        let mut iter = IntoIter{ stack: Vec::new() };
+        iter.push_left_spine(root);
+        iter
+    }
+
+    fn push_left_spine(&mut self, mut node: Option<Box<Node<D, A>>>) {
+        while let Some(mut succ) = node {
+            node = succ.left.take();
+            self.stack.push(succ);
+        }
+    }
+}
+
+impl<D, A: Aggregate<D>> Iterator for IntoIter<D, A> {
+
+    type Item = (Range, D);
+
+    fn next(&mut self) -> Option<(Range, D)> {
+        let mut node = match self.stack.pop() { Some(node) => node, None => return None };
+        let right = node.right.take();
+        self.push_left_spine(right);
+        let node = *node;
+        Some((node.key, node.data))
+    }
+}
+
+/// An iterator that removes and yields all (key,value) pairs intersecting a range for which a
+/// predicate returns true, see `IntervalTree::extract_if`.
+pub struct ExtractIf<'a, D: 'a, F> {
+    tree: &'a mut tree::IntervalTree<D>,
+    range: Option<Range>,
+    last: Option<Range>,
+    pred: F
+}
+
+impl<'a, D: 'a, F: FnMut(&Range, &mut D) -> bool> ExtractIf<'a, D, F> {
+
+    pub fn new(tree: &'a mut tree::IntervalTree<D>, range: Option<Range>, pred: F) -> ExtractIf<'a, D, F>{
+        let range = range.and_then(|range| tree.clip_query(range.min, range.max));
+        ExtractIf{ tree: tree, range: range, last: None, pred: pred }
+    }
+
+    fn next_candidate(&self) -> Option<Range> {
+        let range = match self.range { Some(ref range) => range, None => return None };
+        let root = match self.tree.root { Some(ref root) => root, None => return None };
+        let next = match self.last {
+            Some(ref last) => first_overlap_after(range, last, root),
+            None => first_overlap(range, root)
+        };
+        next.map(|(k,_)| *k)
+    }
+}
+
+impl<'a, D: 'a, F: FnMut(&Range, &mut D) -> bool> Iterator for ExtractIf<'a, D, F> {
+
+    type Item = (Range, D);
+
+    fn next(&mut self) -> Option<(Range, D)> {
+        while let Some(key) = self.next_candidate() {
+            self.last = Some(key);
+            let extract = {
+                let root = self.tree.root.as_mut().expect("candidate found in empty tree");
+                let data = search_mut(&key, root).expect("candidate is part of the tree");
+                (self.pred)(&key, data)
+            };
+            if extract {
+                return self.tree.remove_entry(key)
+            }
+        }
+        None
+    }
+}
+
 #[test]
 fn test_iterators(){
     let mut tree = tree::IntervalTree::<i32>::new();
@@ -112,3 +492,346 @@ fn test_iterators(){
     assert_eq!(iter.next().expect("should have a few values").0, &Range::new(17,17));
     assert!(iter.next().is_none());
 }
+
+#[test]
+fn test_descending_iterators(){
+    let mut tree = tree::IntervalTree::<i32>::new();
+    for i in 0..100 {
+        tree.insert(Range::new(i*3, i*3+5), i as i32);
+    }
+    let asc = RangePairIter::new(&tree, 20, 150).map(|(k,_)| *k).collect::<Vec<Range>>();
+    let mut desc = RangePairIter::new_directed(&tree, 20, 150, Direction::Descending).map(|(k,_)| *k).collect::<Vec<Range>>();
+    desc.reverse();
+    assert_eq!(asc, desc);
+    assert_eq!(asc.first(), Some(&Range::new(15,20)));
+    assert_eq!(asc.last(), Some(&Range::new(150,155)));
+}
+
+/// The pair whose partners are currently being emitted by a `JoinIter`, together with the index of
+/// its next partner in the active list of the other tree.
+enum JoinCursor<'a, D: 'a, E: 'a> {
+    Left((&'a Range, &'a D), usize),
+    Right((&'a Range, &'a E), usize),
+}
+
+/// An iterator over all pairs of overlapping keys of two trees, see `IntervalTree::join`. Both
+/// trees are swept once in ascending order of the keys, and every key is remembered while it can
+/// still overlap a later key of the other tree.
+pub struct JoinIter<'a, D:'a, E:'a, A:'a + Aggregate<D> = NoAggregate, B:'a + Aggregate<E> = NoAggregate> {
+    left: Peekable<RangePairIter<'a, D, A>>,
+    right: Peekable<RangePairIter<'a, E, B>>,
+    left_active: Vec<(&'a Range, &'a D)>,
+    right_active: Vec<(&'a Range, &'a E)>,
+    cursor: Option<JoinCursor<'a, D, E>>,
+}
+
+impl<'a, D:'a, E:'a, A:'a + Aggregate<D>, B:'a + Aggregate<E>> JoinIter<'a, D, E, A, B> {
+
+    pub fn new(left: &'a tree::IntervalTree<D, A>, right: &'a tree::IntervalTree<E, B>) -> JoinIter<'a, D, E, A, B>{
+        JoinIter{ left: left.iter().peekable(), right: right.iter().peekable(), left_active: Vec::new(), right_active: Vec::new(), cursor: None }
+    }
+
+    fn left_is_next(&mut self) -> Option<bool> {
+        match (self.left.peek(), self.right.peek()) {
+            (Some(&(l, _)), Some(&(r, _))) => Some(l.min <= r.min),
+            (Some(_), None) => if self.right_active.is_empty() { None } else { Some(true) },
+            (None, Some(_)) => if self.left_active.is_empty() { None } else { Some(false) },
+            (None, None) => None
+        }
+    }
+}
+
+impl<'a, D:'a, E:'a, A:'a + Aggregate<D>, B:'a + Aggregate<E>> Iterator for JoinIter<'a, D, E, A, B> {
+
+    type Item = ((&'a Range, &'a D), (&'a Range, &'a E));
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.cursor.take() {
+                Some(JoinCursor::Left(pair, i)) => if i < self.right_active.len() {
+                    self.cursor = Some(JoinCursor::Left(pair, i+1));
+                    return Some((pair, self.right_active[i]))
+                },
+                Some(JoinCursor::Right(pair, i)) => if i < self.left_active.len() {
+                    self.cursor = Some(JoinCursor::Right(pair, i+1));
+                    return Some((self.left_active[i], pair))
+                },
+                None => {
+                    //all remembered keys of the other tree start before the new key, so after
+                    //dropping the ones that end before it, the rest overlaps it
+                    if self.left_is_next()? {
+                        let pair = self.left.next().expect("peeked");
+                        self.right_active.retain(|&(key, _)| key.max >= pair.0.min);
+                        self.left_active.push(pair);
+                        self.cursor = Some(JoinCursor::Left(pair, 0));
+                    } else {
+                        let pair = self.right.next().expect("peeked");
+                        self.left_active.retain(|&(key, _)| key.max >= pair.0.min);
+                        self.right_active.push(pair);
+                        self.cursor = Some(JoinCursor::Right(pair, 0));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A difference between two trees, as reported by `IntervalTree::diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<'a, D: 'a> {
+    /// the key is only part of the tree diff was called on
+    OnlyInSelf(&'a Range, &'a D),
+    /// the key is only part of the other tree
+    OnlyInOther(&'a Range, &'a D),
+    /// the key is part of both trees, with the data of self and the data of other
+    Differs(&'a Range, &'a D, &'a D),
+}
+
+/// An iterator over the differences between two trees in ascending order of the keys, see
+/// `IntervalTree::diff`.
+pub struct DiffIter<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
+    left: Peekable<RangePairIter<'a, D, A>>,
+    right: Peekable<RangePairIter<'a, D, A>>,
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> DiffIter<'a, D, A> {
+
+    pub fn new(left: &'a tree::IntervalTree<D, A>, right: &'a tree::IntervalTree<D, A>) -> DiffIter<'a, D, A>{
+        DiffIter{ left: left.iter().peekable(), right: right.iter().peekable() }
+    }
+}
+
+impl<'a, D:'a + PartialEq, A:'a + Aggregate<D>> Iterator for DiffIter<'a, D, A> {
+
+    type Item = Change<'a, D>;
+
+    fn next(&mut self) -> Option<Change<'a, D>> {
+        loop {
+            let order = match (self.left.peek(), self.right.peek()) {
+                (Some(&(l, _)), Some(&(r, _))) => l.cmp(r),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => return None
+            };
+            match order {
+                Ordering::Less => return self.left.next().map(|(key, data)| Change::OnlyInSelf(key, data)),
+                Ordering::Greater => return self.right.next().map(|(key, data)| Change::OnlyInOther(key, data)),
+                Ordering::Equal => {
+                    let (key, data) = self.left.next().expect("peeked");
+                    let (_, other) = self.right.next().expect("peeked");
+                    if data != other { return Some(Change::Differs(key, data, other)) }
+                }
+            }
+        }
+    }
+}
+
+/// A boundary of a key of a tree, as yielded by `IntervalTree::events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a, D: 'a> {
+    /// the key begins to cover points at key.min
+    Start(&'a Range, &'a D),
+    /// the key covers its last point at key.max
+    End(&'a Range, &'a D),
+}
+
+impl<'a, D: 'a> Event<'a, D> {
+
+    /// returns the coordinate of the event, key.min for a start and key.max for an end
+    pub fn position(&self) -> u64 {
+        match *self {
+            Event::Start(key, _) => key.min,
+            Event::End(key, _) => key.max,
+        }
+    }
+}
+
+//a key of `EventIter` that has started but not ended yet
+struct PendingEnd<'a, D: 'a> {
+    key: &'a Range,
+    data: &'a D
+}
+
+impl<'a, D: 'a> PartialEq for PendingEnd<'a, D> {
+    fn eq(&self, other: &Self) -> bool { self.key == other.key }
+}
+
+impl<'a, D: 'a> Eq for PendingEnd<'a, D> {}
+
+impl<'a, D: 'a> PartialOrd for PendingEnd<'a, D> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl<'a, D: 'a> Ord for PendingEnd<'a, D> {
+    //reversed, so that the BinaryHeap pops the earliest end first
+    fn cmp(&self, other: &Self) -> Ordering { (other.key.max, other.key).cmp(&(self.key.max, self.key)) }
+}
+
+/// An iterator over the start and end events of the keys of a tree in ascending order of their
+/// positions, see `IntervalTree::events`. Keys are taken from the tree in ascending order, keys
+/// which have started wait in a heap for their end.
+pub struct EventIter<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
+    starts: Peekable<RangePairIter<'a, D, A>>,
+    ends: BinaryHeap<PendingEnd<'a, D>>
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> EventIter<'a, D, A> {
+
+    pub fn new(inner: RangePairIter<'a, D, A>) -> EventIter<'a, D, A>{
+        EventIter{ starts: inner.peekable(), ends: BinaryHeap::new() }
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Iterator for EventIter<'a, D, A> {
+
+    type Item = Event<'a, D>;
+
+    fn next(&mut self) -> Option<Event<'a, D>> {
+        let start_first = match (self.starts.peek(), self.ends.peek()) {
+            (Some(&(key, _)), Some(end)) => key.min <= end.key.max,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => return None
+        };
+        if start_first {
+            let (key, data) = self.starts.next().expect("peeked");
+            self.ends.push(PendingEnd{ key: key, data: data });
+            Some(Event::Start(key, data))
+        } else {
+            self.ends.pop().map(|end| Event::End(end.key, end.data))
+        }
+    }
+}
+
+/// An iterator over the maximal ranges covered by overlapping or adjacent keys of a tree, together
+/// with the data of their keys folded into a value, see `IntervalTree::iter_coalesced_with`.
+pub struct CoalescedWith<'a, D:'a, R, F, A:'a + Aggregate<D> = NoAggregate> {
+    inner: Peekable<RangePairIter<'a, D, A>>,
+    init: R,
+    fold: F
+}
+
+impl<'a, D:'a, R: Clone, F: FnMut(R, &Range, &D) -> R, A:'a + Aggregate<D>> CoalescedWith<'a, D, R, F, A> {
+
+    pub fn new(inner: RangePairIter<'a, D, A>, init: R, fold: F) -> CoalescedWith<'a, D, R, F, A>{
+        CoalescedWith{ inner: inner.peekable(), init: init, fold: fold }
+    }
+}
+
+impl<'a, D:'a, R: Clone, F: FnMut(R, &Range, &D) -> R, A:'a + Aggregate<D>> Iterator for CoalescedWith<'a, D, R, F, A> {
+
+    type Item = (Range, R);
+
+    fn next(&mut self) -> Option<(Range, R)> {
+        let (key, data) = self.inner.next()?;
+        let mut hull = *key;
+        let mut acc = (self.fold)(self.init.clone(), key, data);
+        while let Some(&(key, data)) = self.inner.peek() {
+            if hull.max.checked_add(1).map_or(false, |next| key.min > next) { break }
+            hull = hull.get_union(key);
+            acc = (self.fold)(acc, key, data);
+            self.inner.next();
+        }
+        Some((hull, acc))
+    }
+}
+
+/// An iterator over the maximal ranges covered by overlapping or adjacent keys of a tree, see
+/// `IntervalTree::iter_coalesced`.
+pub struct Coalesced<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
+    inner: CoalescedWith<'a, D, (), fn((), &Range, &D), A>
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Coalesced<'a, D, A> {
+
+    pub fn new(inner: RangePairIter<'a, D, A>) -> Coalesced<'a, D, A>{
+        fn ignore<D>(_: (), _: &Range, _: &D) {}
+        Coalesced{ inner: CoalescedWith::new(inner, (), ignore::<D>) }
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Iterator for Coalesced<'a, D, A> {
+
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Range> {
+        self.inner.next().map(|(range, _)| range)
+    }
+}
+
+//an entry of the search front of `EndOrderIter`: either the key of node alone or the whole subtree
+//of node, whose smallest end and start bound the keys in it from below
+struct EndCandidate<'a, D:'a, A:'a + Aggregate<D>> {
+    end: u64,
+    start: u64,
+    whole_subtree: bool,
+    node: &'a Node<D, A>
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> EndCandidate<'a, D, A> {
+
+    fn order(&self) -> (u64, u64, bool) {
+        (self.end, self.start, !self.whole_subtree)
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> PartialEq for EndCandidate<'a, D, A> {
+    fn eq(&self, other: &Self) -> bool { self.order() == other.order() }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Eq for EndCandidate<'a, D, A> {}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> PartialOrd for EndCandidate<'a, D, A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Ord for EndCandidate<'a, D, A> {
+    //reversed, so that the BinaryHeap pops the earliest end first
+    fn cmp(&self, other: &Self) -> Ordering { other.order().cmp(&self.order()) }
+}
+
+/// An iterator over the pairs of a tree whose keys end inside a range, in ascending order of the
+/// ends of the keys (and of their starts for equal ends), see `IntervalTree::iter_by_end`.
+pub struct EndOrderIter<'a, D:'a, A:'a + Aggregate<D> = NoAggregate> {
+    range: Range,
+    front: BinaryHeap<EndCandidate<'a, D, A>>
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> EndOrderIter<'a, D, A> {
+
+    pub fn new(tree: &'a tree::IntervalTree<D, A>, lower: u64, upper: u64) -> EndOrderIter<'a, D, A>{
+        let mut iter = EndOrderIter{ range: Range::new(lower, upper), front: BinaryHeap::new() };
+        if let Some(ref root) = tree.root { iter.push_subtree(root) }
+        iter
+    }
+
+    pub fn empty() -> EndOrderIter<'a, D, A>{
+        EndOrderIter{ range: Range::new(0, 0), front: BinaryHeap::new() }
+    }
+
+    fn push_subtree(&mut self, node: &'a Node<D, A>) {
+        if node.subtree_max_end() < self.range.min { return }
+        self.front.push(EndCandidate{ end: node.subtree_min_end(), start: node.subtree_min_start(),
+                                      whole_subtree: true, node: node });
+    }
+}
+
+impl<'a, D:'a, A:'a + Aggregate<D>> Iterator for EndOrderIter<'a, D, A> {
+
+    type Item = (&'a Range, &'a D);
+
+    fn next(&mut self) -> Option<(&'a Range, &'a D)> {
+        while let Some(candidate) = self.front.pop() {
+            if candidate.end > self.range.max { break }
+            let node = candidate.node;
+            if !candidate.whole_subtree { return Some((&node.key, &node.data)) }
+            if node.key.max >= self.range.min {
+                self.front.push(EndCandidate{ end: node.key.max, start: node.key.min, whole_subtree: false, node: node });
+            }
+            if let Some(ref succ) = node.left { self.push_subtree(succ) }
+            if let Some(ref succ) = node.right { self.push_subtree(succ) }
+        }
+        self.front.clear();
+        None
+    }
+}
@@ -0,0 +1,168 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use aggregate::Aggregate;
+use bounds::IntoRange;
+use node::top_overlap;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+/// The aggregate of a `LayeredIntervalTree`: the biggest priority of a subtree, None if it is
+/// empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TopPriority;
+
+impl<P: Ord + Copy + fmt::Debug, D> Aggregate<(P, D)> for TopPriority {
+    type Value = Option<P>;
+    fn empty() -> Option<P> { None }
+    fn lift(_: &Range, data: &(P, D)) -> Option<P> { Some(data.0) }
+    fn combine(left: &Option<P>, right: &Option<P>) -> Option<P> { if right > left { *right } else { *left } }
+}
+
+/// An interval map whose pairs are stacked in layers, e.g. overlay mounts or mappings shadowing
+/// each other: every pair carries a priority, and `top_at` and `top_overlaps` only report the pair
+/// with the highest priority at each location. Pairs with the same priority are ordered by their
+/// keys, the smallest key is on top. Like every map of this crate, a key holds one pair; inserting
+/// the same key again replaces its data and priority.
+///
+/// The biggest priority of every subtree is cached, so lookups skip the subtrees whose pairs are
+/// all shadowed. All other queries are answered by the underlying tree, see `tree`.
+#[derive(Debug)]
+pub struct LayeredIntervalTree<D, P: Ord + Copy + fmt::Debug = u32> {
+    tree: IntervalTree<(P, D), TopPriority>,
+}
+
+impl<D, P: Ord + Copy + fmt::Debug> LayeredIntervalTree<D, P> {
+
+/// This function will construct a new empty LayeredIntervalTree.
+/// # Examples
+/// ```
+/// let t=interval_tree::LayeredIntervalTree::<i32>::new();
+/// assert!(t.tree().empty());
+/// ```
+    pub fn new() -> LayeredIntervalTree<D, P> {
+        LayeredIntervalTree{tree: IntervalTree::with_aggregate(TopPriority)}
+    }
+
+/// This function will return the tree holding all pairs together with their priorities.
+    pub fn tree(&self) -> &IntervalTree<(P, D), TopPriority> {
+        &self.tree
+    }
+
+/// This function will insert the key,value pair with the given priority, overwriting the old data
+/// and priority if the key is already part of the tree.
+    pub fn insert<K: IntoRange>(&mut self, key: K, priority: P, data: D) {
+        self.tree.insert(key, (priority, data));
+    }
+
+/// This function will remove the key,value pair from the tree and return its priority and data.
+    pub fn remove<K: IntoRange>(&mut self, key: K) -> Option<(P, D)> {
+        self.tree.remove_entry(key).map(|(_, entry)| entry)
+    }
+
+/// This function will return the priority and data stored under exactly key.
+    pub fn get<K: IntoRange>(&self, key: K) -> Option<(P, &D)> {
+        self.tree.get(key).map(|&(priority, ref data)| (priority, data))
+    }
+
+/// This function will return the pair with the highest priority whose key contains point, or
+/// None if no key does.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::LayeredIntervalTree::new();
+/// t.insert(Range::new(0,0xffff), 0, "root");
+/// t.insert(Range::new(0x1000,0x1fff), 1, "overlay");
+/// assert_eq!(t.top_at(0x1800), Some((&Range::new(0x1000,0x1fff), 1, &"overlay")));
+/// assert_eq!(t.top_at(0x2000).map(|(_,_,v)| *v), Some("root"));
+/// assert_eq!(t.top_at(0x10000), None);
+/// ```
+    pub fn top_at(&self, point: u64) -> Option<(&Range, P, &D)> {
+        let mut best = None;
+        top_overlap(&Range::new(point, point), &self.tree.root, &mut best);
+        best.map(|(_, node)| (&node.key, node.data.0, &node.data.1))
+    }
+
+/// This function will split the bounds into the maximal sub ranges on which the same pair is on
+/// top and return them in ascending order, each together with that pair. Sub ranges not covered by
+/// any key are left out. This takes O(k log k) steps for the k keys intersecting the bounds.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::LayeredIntervalTree::new();
+/// t.insert(Range::new(0,99), 0, "base");
+/// t.insert(Range::new(20,39), 2, "top");
+/// t.insert(Range::new(30,59), 1, "middle");
+/// let layers = t.top_overlaps(10..=200).into_iter().map(|(r,_,_,v)| (r, *v)).collect::<Vec<_>>();
+/// assert_eq!(layers, vec![(Range::new(10,19), "base"), (Range::new(20,39), "top"),
+///                         (Range::new(40,59), "middle"), (Range::new(60,99), "base")]);
+/// ```
+    pub fn top_overlaps<K: IntoRange>(&self, bounds: K) -> Vec<(Range, &Range, P, &D)> {
+        let bounds = match bounds.into_range() { Some(bounds) => bounds, None => return vec![] };
+        let entries = self.tree.range(bounds).collect::<Vec<_>>();
+        //the keys covering pos, highest priority and then smallest key on top; keys ending before pos are dropped once they come up
+        let mut covering = BinaryHeap::new();
+        let mut layers: Vec<(Range, usize)> = vec![];
+        let (mut pos, mut next) = (bounds.min, 0);
+        loop {
+            while next < entries.len() && entries[next].0.min <= pos {
+                covering.push(((entries[next].1).0, Reverse(next)));
+                next += 1;
+            }
+            while covering.peek().map_or(false, |&(_, Reverse(i))| entries[i].0.max < pos) { covering.pop(); }
+            let top = match covering.peek() {
+                Some(&(_, Reverse(i))) => i,
+                None if next < entries.len() => { pos = entries[next].0.min; continue }
+                None => break
+            };
+            let mut end = entries[top].0.max.min(bounds.max);
+            if next < entries.len() { end = end.min(entries[next].0.min - 1) }
+            match layers.last_mut() {
+                Some(&mut (ref mut layer, i)) if i == top && layer.max + 1 == pos => layer.max = end,
+                _ => layers.push((Range::new(pos, end), top))
+            }
+            if end == bounds.max { break }
+            pos = end + 1;
+        }
+        layers.into_iter().map(|(layer, i)| (layer, entries[i].0, (entries[i].1).0, &(entries[i].1).1)).collect()
+    }
+}
+
+#[test]
+fn test_layered(){
+    let mut t = LayeredIntervalTree::<usize, u8>::new();
+    let mut keys = vec![];
+    for i in 0..300usize {
+        let min = (i as u64 * 37) % 500;
+        let key = Range::new(min, min + (i as u64 * 13) % 40);
+        t.insert(key, (i % 7) as u8, i);
+        keys.push(key);
+    }
+    assert_eq!(t.remove(keys[0]), Some((0, 0)));
+    assert_eq!(t.get(keys[1]), Some((1, &1)));
+    let brute = |p: u64| t.tree().iter().filter(|&(k, _)| k.min <= p && p <= k.max)
+                                  .fold(None, |best: Option<(&Range, &(u8, usize))>, (k, v)| match best {
+                                      Some((_, b)) if b.0 >= v.0 => best,
+                                      _ => Some((k, v))
+                                  });
+    for p in 0..560 {
+        assert_eq!(t.top_at(p).map(|(k, _, v)| (*k, *v)), brute(p).map(|(k, v)| (*k, v.1)));
+    }
+    let layers = t.top_overlaps(100..=520);
+    let mut covered = 0;
+    for &(layer, key, priority, data) in layers.iter() {
+        for p in layer.min..layer.max + 1 { assert_eq!(t.top_at(p), Some((key, priority, data))) }
+        covered += layer.max - layer.min + 1;
+    }
+    assert_eq!(covered, t.tree().coverage(100..=520));
+    assert!(layers.windows(2).all(|w| w[0].0.max < w[1].0.min && (w[0].0.max + 1 < w[1].0.min || w[0].1 != w[1].1)));
+    assert_eq!(t.top_overlaps(2000..).len(), 0);
+}
@@ -0,0 +1,418 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use bounds::IntoRange;
+use std::cmp;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Add;
+
+/// An update of the stored data that a `LazyIntervalTree` can attach to a whole subtree instead of
+/// applying it to every pair. All updates pending on a subtree are composed into a single one, so
+/// a subtree holds one update no matter how many were made, and reading a pair applies it once.
+///
+/// `first.compose(&then)` has to change any data exactly like applying first and then then, and
+/// compose has to be associative.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::{LazyIntervalTree, LazyUpdate};
+///
+/// /// sets and clears permission bits, clearing after setting
+/// #[derive(Clone)]
+/// struct Bits { set: u32, clear: u32 }
+///
+/// impl LazyUpdate<u32> for Bits {
+///     fn apply(&self, bits: &mut u32) { *bits = (*bits | self.set) & !self.clear }
+///     fn compose(&self, then: &Bits) -> Bits {
+///         Bits{set: (self.set & !then.clear) | then.set, clear: (self.clear & !then.set) | then.clear}
+///     }
+/// }
+///
+/// let mut t=LazyIntervalTree::new();
+/// for i in 0..1000 { t.insert(Range::new(i*10, i*10+9), 0b011u32); }
+/// t.update_range(0..5000, Bits{set: 0b100, clear: 0b001});
+/// t.update_range(4995..=5005, Bits{set: 0b001, clear: 0});
+/// assert_eq!(t.get(Range::new(4990,4999)), Some(&0b111));
+/// assert_eq!(t.get(Range::new(5000,5009)), Some(&0b011));
+/// assert_eq!(t.get(Range::new(0,9)), Some(&0b110));
+/// ```
+pub trait LazyUpdate<D>: Clone {
+    /// changes data by this update
+    fn apply(&self, data: &mut D);
+    /// returns the update that has the same effect as applying self and then then
+    fn compose(&self, then: &Self) -> Self;
+}
+
+/// Adds a constant to the stored values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Increment<T>(pub T);
+
+impl<T: Copy + Add<Output=T>> LazyUpdate<T> for Increment<T> {
+    fn apply(&self, data: &mut T) { *data = *data + self.0 }
+    fn compose(&self, then: &Increment<T>) -> Increment<T> { Increment(self.0 + then.0) }
+}
+
+/// Replaces the stored values by a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Overwrite<T>(pub T);
+
+impl<T: Clone> LazyUpdate<T> for Overwrite<T> {
+    fn apply(&self, data: &mut T) { *data = self.0.clone() }
+    fn compose(&self, then: &Overwrite<T>) -> Overwrite<T> { then.clone() }
+}
+
+/// A node of a `LazyIntervalTree`. If tag is set, it has been applied to none of the pairs of the
+/// subtree yet, including the pair of this node.
+struct LazyNode<D, U> {
+    key: Range,
+    data: D,
+    tag: Option<U>,
+    height: u32,
+    max: u64,
+    min_start: u64,
+    max_start: u64,
+    min_end: u64,
+    left: Link<D, U>,
+    right: Link<D, U>,
+}
+
+type Link<D, U> = Option<Box<LazyNode<D, U>>>;
+
+fn height<D, U>(link: &Link<D, U>) -> u32 {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+/// schedules tag for all pairs of the subtree link, after the updates already pending there
+fn schedule<D, U: LazyUpdate<D>>(link: &mut Link<D, U>, tag: &U) {
+    if let Some(ref mut node) = *link {
+        node.tag = Some(match node.tag.take() { Some(old) => old.compose(tag), None => tag.clone() });
+    }
+}
+
+/// applies the pending update of node to its own pair and hands it down to both successors
+fn push<D, U: LazyUpdate<D>>(node: &mut LazyNode<D, U>) {
+    if let Some(tag) = node.tag.take() {
+        tag.apply(&mut node.data);
+        schedule(&mut node.left, &tag);
+        schedule(&mut node.right, &tag);
+    }
+}
+
+fn node<D, U>(key: Range, data: D, left: Link<D, U>, right: Link<D, U>) -> Box<LazyNode<D, U>> {
+    let height = cmp::max(height(&left), height(&right)) + 1;
+    let max = [left.as_ref().map_or(0, |n| n.max), right.as_ref().map_or(0, |n| n.max), key.max].iter().cloned().max().expect("not empty");
+    let min_end = [left.as_ref().map_or(u64::max_value(), |n| n.min_end), right.as_ref().map_or(u64::max_value(), |n| n.min_end), key.max].iter().cloned().min().expect("not empty");
+    let min_start = left.as_ref().map_or(key.min, |n| n.min_start);
+    let max_start = right.as_ref().map_or(key.min, |n| n.max_start);
+    Box::new(LazyNode{key: key, data: data, tag: None, height: height, max: max, min_start: min_start,
+                      max_start: max_start, min_end: min_end, left: left, right: right})
+}
+
+/// Builds a new node from key, data and both successors, applying the rotations necessary to keep
+/// the AVL balance. Nodes that move are pushed first, so no pending update changes its subtree.
+fn balanced<D, U: LazyUpdate<D>>(key: Range, data: D, left: Link<D, U>, right: Link<D, U>) -> Box<LazyNode<D, U>> {
+    let (hl, hr) = (height(&left), height(&right));
+    if hl > hr + 1 {
+        let mut l = left.expect("Avl broken");
+        push(&mut l);
+        let l = *l;
+        if height(&l.left) >= height(&l.right) {
+            return node(l.key, l.data, l.left, Some(node(key, data, l.right, right)))
+        }
+        let mut lr = l.right.expect("Avl broken");
+        push(&mut lr);
+        let lr = *lr;
+        return node(lr.key, lr.data, Some(node(l.key, l.data, l.left, lr.left)), Some(node(key, data, lr.right, right)))
+    }
+    if hr > hl + 1 {
+        let mut r = right.expect("Avl broken");
+        push(&mut r);
+        let r = *r;
+        if height(&r.right) >= height(&r.left) {
+            return node(r.key, r.data, Some(node(key, data, left, r.left)), r.right)
+        }
+        let mut rl = r.left.expect("Avl broken");
+        push(&mut rl);
+        let rl = *rl;
+        return node(rl.key, rl.data, Some(node(key, data, left, rl.left)), Some(node(r.key, r.data, rl.right, r.right)))
+    }
+    node(key, data, left, right)
+}
+
+/// returns the new subtree with key inserted and the data it replaced
+fn insert<D, U: LazyUpdate<D>>(link: Link<D, U>, key: Range, data: D) -> (Box<LazyNode<D, U>>, Option<D>) {
+    let mut n = match link { Some(n) => n, None => return (node(key, data, None, None), None) };
+    push(&mut n);
+    let n = *n;
+    match n.key.cmp(&key) {
+        Ordering::Equal => (node(key, data, n.left, n.right), Some(n.data)),
+        Ordering::Less => {
+            let (right, replaced) = insert(n.right, key, data);
+            (balanced(n.key, n.data, n.left, Some(right)), replaced)
+        },
+        Ordering::Greater => {
+            let (left, replaced) = insert(n.left, key, data);
+            (balanced(n.key, n.data, Some(left), n.right), replaced)
+        }
+    }
+}
+
+/// returns the subtree without its minimal node and the pair of that node
+fn remove_min<D, U: LazyUpdate<D>>(mut n: Box<LazyNode<D, U>>) -> (Link<D, U>, (Range, D)) {
+    push(&mut n);
+    let n = *n;
+    match n.left {
+        None => (n.right, (n.key, n.data)),
+        Some(left) => {
+            let (left, min) = remove_min(left);
+            (Some(balanced(n.key, n.data, left, n.right)), min)
+        }
+    }
+}
+
+/// returns the subtree without key and the removed data, or None if key is not part of it
+fn remove<D, U: LazyUpdate<D>>(link: Link<D, U>, key: &Range) -> (Link<D, U>, Option<D>) {
+    let mut n = match link { Some(n) => n, None => return (None, None) };
+    push(&mut n);
+    let n = *n;
+    match n.key.cmp(key) {
+        Ordering::Less => {
+            let (right, data) = remove(n.right, key);
+            (Some(balanced(n.key, n.data, n.left, right)), data)
+        },
+        Ordering::Greater => {
+            let (left, data) = remove(n.left, key);
+            (Some(balanced(n.key, n.data, left, n.right)), data)
+        },
+        Ordering::Equal => {
+            let subtree = match (n.left, n.right) {
+                (None, right) => right,
+                (left, None) => left,
+                (left, Some(right)) => {
+                    let (right, (min, data)) = remove_min(right);
+                    Some(balanced(min, data, left, right))
+                }
+            };
+            (subtree, Some(n.data))
+        }
+    }
+}
+
+/// schedules tag for all pairs below link whose keys intersect range. Subtrees whose keys all
+/// intersect range only get the tag attached.
+fn update<D, U: LazyUpdate<D>>(link: &mut Link<D, U>, range: &Range, tag: &U) {
+    let whole = match *link {
+        None => return,
+        Some(ref n) if n.max < range.min || n.min_start > range.max => return,
+        Some(ref n) => n.max_start <= range.max && n.min_end >= range.min
+    };
+    if whole { return schedule(link, tag) }
+    let n = link.as_mut().expect("checked above");
+    push(n);
+    if n.key.intersect(range) { tag.apply(&mut n.data) }
+    update(&mut n.left, range, tag);
+    update(&mut n.right, range, tag);
+}
+
+/// pushes the pending updates of all pairs below link whose keys intersect range into their data
+fn flush<D, U: LazyUpdate<D>>(link: &mut Link<D, U>, range: &Range) {
+    let n = match *link { Some(ref mut n) => n, None => return };
+    if n.max < range.min || n.min_start > range.max { return }
+    push(n);
+    flush(&mut n.left, range);
+    flush(&mut n.right, range);
+}
+
+/// appends all pairs below link whose keys intersect range in ascending order, all of them must be
+/// flushed
+fn collect<'a, D, U>(link: &'a Link<D, U>, range: &Range, pairs: &mut Vec<(&'a Range, &'a D)>) {
+    let n = match *link { Some(ref n) => n, None => return };
+    if n.max < range.min || n.min_start > range.max { return }
+    collect(&n.left, range, pairs);
+    if n.key.intersect(range) { pairs.push((&n.key, &n.data)) }
+    collect(&n.right, range, pairs);
+}
+
+fn into_pairs<D, U>(link: Link<D, U>, pairs: &mut Vec<(Range, D)>) {
+    if let Some(n) = link {
+        let n = *n;
+        into_pairs(n.left, pairs);
+        pairs.push((n.key, n.data));
+        into_pairs(n.right, pairs);
+    }
+}
+
+/// An interval map that applies an update to all pairs whose keys intersect a window without
+/// visiting each of them, like the lazy propagation of a segment tree. Subtrees whose keys all
+/// intersect the window only remember the update; it reaches the data when a later operation
+/// passes through the subtree. An update spanning k pairs thus costs O(log n) for windows over
+/// keys that do not contain each other, instead of O(k). The updates are of the type U (see
+/// `LazyUpdate`), so the updates pending on a subtree can be composed into one and memory as well
+/// as the cost of reading do not grow with the number of updates made.
+///
+/// Reading has to apply pending updates first, so all reading functions take `&mut self`.
+pub struct LazyIntervalTree<D, U: LazyUpdate<D>> {
+    root: Link<D, U>,
+    len: usize,
+}
+
+impl<D: fmt::Debug, U: LazyUpdate<D>> fmt::Debug for LazyIntervalTree<D, U> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LazyIntervalTree").field("len", &self.len).finish()
+    }
+}
+
+impl<D, U: LazyUpdate<D>> LazyIntervalTree<D, U> {
+
+/// This function will construct a new empty LazyIntervalTree.
+/// # Examples
+/// ```
+/// use interval_tree::{LazyIntervalTree, Increment};
+///
+/// let t=LazyIntervalTree::<u32, Increment<u32>>::new();
+/// assert_eq!(t.len(), 0);
+/// ```
+    pub fn new() -> LazyIntervalTree<D, U> {
+        LazyIntervalTree{root: None, len: 0}
+    }
+
+/// This function will return the number of key,value pairs in the tree.
+    pub fn len(&self) -> usize { self.len }
+
+/// This function will insert the key,value pair into the tree and return the old data if the key
+/// was already part of the tree. Empty keys are not inserted.
+    pub fn insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let (root, replaced) = insert(self.root.take(), key, data);
+        self.root = Some(root);
+        if replaced.is_none() { self.len += 1 }
+        replaced
+    }
+
+/// This function will remove the key,value pair from the tree and return its data with all
+/// updates applied.
+    pub fn remove<K: IntoRange>(&mut self, key: K) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let (root, removed) = remove(self.root.take(), &key);
+        self.root = root;
+        if removed.is_some() { self.len -= 1 }
+        removed
+    }
+
+/// This function will apply update to the data of every pair whose key intersects the bounds.
+/// The update reaches a pair only when the pair is read, removed or moved by a rebalancing,
+/// composed with the later updates of the pair.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::{LazyIntervalTree, Increment};
+///
+/// let mut t=LazyIntervalTree::new();
+/// for i in 0..1000 { t.insert(Range::new(i*10, i*10+9), 0u32); }
+/// t.update_range(0..5000, Increment(1));
+/// t.update_range(4995..=5005, Increment(2));
+/// assert_eq!(t.get(Range::new(4990,4999)), Some(&3));
+/// assert_eq!(t.get(Range::new(5000,5009)), Some(&2));
+/// assert_eq!(t.range(..).iter().filter(|&&(_, v)| *v != 0).count(), 501);
+/// ```
+    pub fn update_range<K: IntoRange>(&mut self, bounds: K, update: U) {
+        let range = match bounds.into_range() { Some(range) => range, None => return };
+        self::update(&mut self.root, &range, &update);
+    }
+
+/// This function will return the data stored under exactly key with all updates applied.
+    pub fn get<K: IntoRange>(&mut self, key: K) -> Option<&D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let mut link = &mut self.root;
+        loop {
+            let n = match *link { Some(ref mut n) => n, None => return None };
+            push(n);
+            match n.key.cmp(&key) {
+                Ordering::Equal => return Some(&n.data),
+                Ordering::Less => link = &mut n.right,
+                Ordering::Greater => link = &mut n.left,
+            }
+        }
+    }
+
+/// This function will return all key,value pairs whose keys intersect the bounds in ascending
+/// order, with all updates applied.
+    pub fn range<K: IntoRange>(&mut self, bounds: K) -> Vec<(&Range, &D)> {
+        let mut pairs = Vec::new();
+        if let Some(range) = bounds.into_range() {
+            flush(&mut self.root, &range);
+            collect(&self.root, &range, &mut pairs);
+        }
+        pairs
+    }
+
+/// This function will apply all pending updates and return the pairs as an `IntervalTree`.
+    pub fn into_tree(mut self) -> IntervalTree<D> {
+        flush(&mut self.root, &Range::new(0, u64::max_value()));
+        let mut pairs = Vec::with_capacity(self.len);
+        into_pairs(self.root.take(), &mut pairs);
+        IntervalTree::from_sorted_iter(pairs)
+    }
+}
+
+/// x -> x * mul + add, in wrapping arithmetic
+#[cfg(test)]
+#[derive(Clone)]
+struct Affine { mul: u64, add: u64 }
+
+#[cfg(test)]
+impl LazyUpdate<u64> for Affine {
+    fn apply(&self, data: &mut u64) { *data = data.wrapping_mul(self.mul).wrapping_add(self.add) }
+    fn compose(&self, then: &Affine) -> Affine {
+        Affine{mul: self.mul.wrapping_mul(then.mul), add: self.add.wrapping_mul(then.mul).wrapping_add(then.add)}
+    }
+}
+
+#[test]
+fn test_lazy(){
+    let mut t = LazyIntervalTree::<u64, Affine>::new();
+    let mut model = Vec::new();
+    for i in 0..500u64 {
+        let key = Range::new(i*13 % 1000, i*13 % 1000 + i % 40);
+        if t.insert(key, i).is_none() { model.push((key, i)) } else { model.iter_mut().find(|p| p.0 == key).unwrap().1 = i }
+    }
+    for step in 0..200u64 {
+        let window = Range::new(step*7 % 1000, step*7 % 1000 + step % 50);
+        t.update_range(window, Affine{mul: 3, add: step});
+        for pair in model.iter_mut().filter(|p| p.0.intersect(&window)) { pair.1 = pair.1.wrapping_mul(3).wrapping_add(step) }
+        if step % 10 == 0 {
+            let key = model.remove((step as usize * 31) % model.len()).0;
+            let data = t.remove(key);
+            assert!(data.is_some());
+        }
+        if step % 7 == 0 { t.insert(Range::new(step, step + 100), step); model.retain(|p| p.0 != Range::new(step, step + 100)); model.push((Range::new(step, step + 100), step)) }
+    }
+    model.sort();
+    assert_eq!(t.len(), model.len());
+    let window = Range::new(300, 600);
+    let expected = model.iter().filter(|p| p.0.intersect(&window)).map(|&(k, v)| (k, v)).collect::<Vec<_>>();
+    assert_eq!(t.range(window).into_iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), expected);
+    assert_eq!(t.get(model[3].0), Some(&model[3].1));
+    assert_eq!(t.into_tree().into_iter().collect::<Vec<_>>(), model);
+}
+
+#[test]
+fn test_lazy_many_updates(){
+    let mut t = LazyIntervalTree::new();
+    for i in 0..1000u64 { t.insert(Range::new(i*10, i*10+9), i); }
+    for _ in 0..1_000_000 { t.update_range(.., Increment(1u64)); }
+    assert_eq!(t.get(Range::new(500, 509)), Some(&1_000_050));
+    t.update_range(0..=9, Increment(7));
+    assert_eq!(t.range(0..20).into_iter().map(|(_, v)| *v).collect::<Vec<_>>(), vec![1_000_007, 1_000_001]);
+    let mut flags = LazyIntervalTree::new();
+    for i in 0..100u64 { flags.insert(Range::new(i, i), false); }
+    flags.update_range(.., Overwrite(true));
+    flags.update_range(10..20, Overwrite(false));
+    assert_eq!(flags.range(..).into_iter().filter(|&(_, v)| *v).count(), 90);
+}
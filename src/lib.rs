@@ -1,8 +1,70 @@
 #![crate_type = "lib"]
-#![feature(test)]
 
 mod node;
+pub mod aggregate;
 pub mod tree;
+pub mod time_keys;
+pub mod float_keys;
 mod iterators;
-pub use tree::IntervalTree;
-pub use iterators::RangePairIter;
+mod binary;
+mod bounds;
+mod set_ops;
+mod set;
+mod allocator;
+mod multimap;
+mod half_open;
+mod range_ext;
+mod arena;
+mod chunked;
+mod persistent;
+mod concurrent;
+mod cursor;
+mod expiry;
+mod counting;
+mod rect;
+mod lazy;
+mod frozen;
+mod versioned;
+mod map;
+mod layered;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "quickcheck")]
+mod arbitrary;
+#[cfg(feature = "test_util")]
+mod test_util;
+#[cfg(feature = "chrono")]
+mod chrono_keys;
+#[cfg(feature = "time")]
+mod offset_datetime_keys;
+pub use tree::{IntervalTree, UniversePolicy, BalancePolicy, Error, OverlapError, InvariantError, ShiftError, KeyOrderError, MapKeysError, MemoryUsage, TreeStats};
+pub use iterators::{RangePairIter, Direction, IntoIter, ExtractIf, GapIter, Keys, Values, ValuesMut, NearestIter, JoinIter, DiffIter, Change, EventIter, Event, Coalesced, CoalescedWith, EndOrderIter, ContainmentIter};
+pub use binary::BinaryData;
+pub use bounds::IntoRange;
+pub use set::{IntervalSet, SetIter};
+pub use allocator::RangeAllocator;
+pub use multimap::{IntervalMultiMap, MultiIter};
+pub use half_open::RangeExclusive;
+pub use range_ext::{RangeExt, RangeError};
+pub use aggregate::{Aggregate, NoAggregate};
+pub use node::{Invariant, VisitControl};
+pub use arena::{ArenaIntervalTree, ArenaIter, ArenaSlot, NodeAlloc, SharedChunks};
+pub use chunked::{ChunkedIntervalMap, ChunkedIter};
+pub use persistent::{PersistentIntervalTree, PersistentIter, Snapshot};
+pub use concurrent::ConcurrentIntervalTree;
+pub use cursor::CursorMut;
+pub use expiry::ExpiringIntervalTree;
+pub use counting::CountingIntervalTree;
+pub use rect::{IntervalTree2D, RectIter};
+pub use lazy::{LazyIntervalTree, LazyUpdate, Increment, Overwrite};
+pub use frozen::{FrozenIntervalTree, FrozenIter, FrozenIndices};
+pub use versioned::VersionedIntervalTree;
+pub use map::IntervalMap;
+pub use layered::{LayeredIntervalTree, TopPriority};
+pub use time_keys::InstantKeys;
+#[cfg(feature = "rayon")]
+pub use parallel::ParRangeIter;
+#[cfg(feature = "quickcheck")]
+pub use arbitrary::ArbitraryRange;
+#[cfg(feature = "test_util")]
+pub use test_util::TestRng;
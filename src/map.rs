@@ -0,0 +1,151 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use arena::{ArenaIntervalTree, ArenaSlot, NodeAlloc};
+use chunked::ChunkedIntervalMap;
+use persistent::PersistentIntervalTree;
+use bounds::IntoRange;
+use aggregate::Aggregate;
+
+/// The operations shared by the interval maps of this crate that own their data and change in
+/// place, so code can be written once and used with whichever variant suits the workload:
+/// `IntervalTree`, `ArenaIntervalTree`, `ChunkedIntervalMap` and `PersistentIntervalTree` (whose
+/// versions are changed via `insert_in_place` and `remove_in_place`).
+///
+/// The inherent functions of the trees take precedence over the ones of this trait, e.g.
+/// `IntervalTree::insert` returns nothing; call `IntervalMap::insert(&mut t, ..)` or write generic
+/// code to get the behaviour described here. Until the iterators can be named in a trait, they are
+/// boxed.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::{IntervalMap, IntervalTree, ChunkedIntervalMap};
+///
+/// fn mapped_bytes<M: IntervalMap<&'static str>>(mut map: M) -> u64 {
+///     map.insert(Range::new(0x1000,0x1fff), "text");
+///     map.insert(Range::new(0x3000,0x3fff), "data");
+///     map.remove(Range::new(0x1000,0x1fff));
+///     map.find_overlaps(Range::new(0,0xffff)).map(|(k,_)| k.max - k.min + 1).sum()
+/// }
+///
+/// assert_eq!(mapped_bytes(IntervalTree::new()), 0x1000);
+/// assert_eq!(mapped_bytes(ChunkedIntervalMap::new()), 0x1000);
+/// ```
+pub trait IntervalMap<D> {
+    /// returns the number of key,value pairs in the map
+    fn len(&self) -> usize;
+    /// inserts the key,value pair and returns the data previously stored under exactly key
+    fn insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<D>;
+    /// removes key and returns its data, or None if key is not part of the map
+    fn remove<K: IntoRange>(&mut self, key: K) -> Option<D>;
+    /// returns the data stored under exactly key
+    fn get<K: IntoRange>(&self, key: K) -> Option<&D>;
+    /// returns all key,value pairs whose keys intersect the bounds in ascending order of the keys
+    fn find_overlaps<'a, K: IntoRange>(&'a self, bounds: K) -> Box<dyn Iterator<Item=(&'a Range, &'a D)> + 'a>;
+    /// returns all key,value pairs in ascending order of the keys
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item=(&'a Range, &'a D)> + 'a> {
+        self.find_overlaps(..)
+    }
+}
+
+impl<D, A: Aggregate<D>> IntervalMap<D> for IntervalTree<D, A> {
+    fn len(&self) -> usize { IntervalTree::len(self) }
+
+    /// Keys are inserted like `IntervalTree::insert`, i.e. subject to the universe of the tree, and
+    /// the data returned is the one stored under the key that results from the universe policy.
+    fn insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<D> {
+        let mut replaced = None;
+        self.insert_with(key, data, |old, new| { replaced = Some(old); new });
+        replaced
+    }
+
+    fn remove<K: IntoRange>(&mut self, key: K) -> Option<D> {
+        self.remove_entry(key).map(|(_, data)| data)
+    }
+
+    fn get<K: IntoRange>(&self, key: K) -> Option<&D> { IntervalTree::get(self, key) }
+
+    fn find_overlaps<'a, K: IntoRange>(&'a self, bounds: K) -> Box<dyn Iterator<Item=(&'a Range, &'a D)> + 'a> {
+        Box::new(self.range(bounds))
+    }
+}
+
+impl<D, S: NodeAlloc<ArenaSlot<D>>> IntervalMap<D> for ArenaIntervalTree<D, S> {
+    fn len(&self) -> usize { ArenaIntervalTree::len(self) }
+
+    fn insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<D> { ArenaIntervalTree::insert(self, key, data) }
+
+    fn remove<K: IntoRange>(&mut self, key: K) -> Option<D> { ArenaIntervalTree::remove(self, key) }
+
+    fn get<K: IntoRange>(&self, key: K) -> Option<&D> { ArenaIntervalTree::get(self, key) }
+
+    fn find_overlaps<'a, K: IntoRange>(&'a self, bounds: K) -> Box<dyn Iterator<Item=(&'a Range, &'a D)> + 'a> {
+        Box::new(self.range(bounds))
+    }
+}
+
+impl<D> IntervalMap<D> for ChunkedIntervalMap<D> {
+    fn len(&self) -> usize { ChunkedIntervalMap::len(self) }
+
+    fn insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<D> { ChunkedIntervalMap::insert(self, key, data) }
+
+    fn remove<K: IntoRange>(&mut self, key: K) -> Option<D> { ChunkedIntervalMap::remove(self, key) }
+
+    fn get<K: IntoRange>(&self, key: K) -> Option<&D> { ChunkedIntervalMap::get(self, key) }
+
+    fn find_overlaps<'a, K: IntoRange>(&'a self, bounds: K) -> Box<dyn Iterator<Item=(&'a Range, &'a D)> + 'a> {
+        Box::new(self.range(bounds))
+    }
+}
+
+impl<D: Clone> IntervalMap<D> for PersistentIntervalTree<D> {
+    fn len(&self) -> usize { PersistentIntervalTree::len(self) }
+
+    fn insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<D> { self.insert_in_place(key, data) }
+
+    fn remove<K: IntoRange>(&mut self, key: K) -> Option<D> { self.remove_in_place(key) }
+
+    fn get<K: IntoRange>(&self, key: K) -> Option<&D> { PersistentIntervalTree::get(self, key) }
+
+    fn find_overlaps<'a, K: IntoRange>(&'a self, bounds: K) -> Box<dyn Iterator<Item=(&'a Range, &'a D)> + 'a> {
+        Box::new(self.range(bounds))
+    }
+}
+
+#[cfg(test)]
+fn check_map<M: IntervalMap<u64>>(mut map: M) {
+    for i in 0..500u64 {
+        assert_eq!(map.insert(Range::new(i * 7 % 1000, i * 7 % 1000 + i % 20), i), None);
+    }
+    assert_eq!(map.insert(Range::new(7, 8), 1000), Some(1));
+    assert_eq!(map.get(Range::new(7, 8)), Some(&1000));
+    for i in 0..100u64 { assert!(map.remove(Range::new(i * 14 % 1000, i * 14 % 1000 + i * 2 % 20)).is_some()) }
+    assert_eq!(map.remove(Range::new(0, 0)), None);
+    assert_eq!(map.len(), 400);
+    assert_eq!(map.iter().count(), 400);
+    let query = Range::new(300, 320);
+    assert_eq!(map.find_overlaps(query).count(), map.iter().filter(|&(k, _)| k.intersect(&query)).count());
+    assert!(map.iter().zip(map.iter().skip(1)).all(|((a, _), (b, _))| a < b));
+}
+
+#[test]
+fn test_interval_map_universe(){
+    use tree::UniversePolicy;
+    let mut t = IntervalTree::<u64>::with_universe(Range::new(10, 20), UniversePolicy::Clamp);
+    assert_eq!(IntervalMap::insert(&mut t, Range::new(5, 12), 1), None);
+    assert_eq!(IntervalMap::insert(&mut t, Range::new(5, 12), 2), Some(1));
+    assert_eq!(IntervalMap::insert(&mut t, Range::new(30, 40), 3), None);
+    assert_eq!(IntervalMap::get(&t, Range::new(10, 12)), Some(&2));
+    assert_eq!(IntervalMap::len(&t), 1);
+}
+
+#[test]
+fn test_interval_map(){
+    check_map(IntervalTree::<u64>::new());
+    check_map(ArenaIntervalTree::<u64>::new());
+    check_map(ChunkedIntervalMap::<u64>::new());
+    check_map(PersistentIntervalTree::<u64>::new());
+}
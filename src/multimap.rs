@@ -0,0 +1,196 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use iterators::RangePairIter;
+use bounds::IntoRange;
+use node::search_mut;
+use std::slice;
+use std::cmp::Ordering;
+use std::fmt;
+
+type Order<D> = Box<dyn Fn(&D, &D) -> Ordering + Send + Sync>;
+
+/// An interval map that keeps every value inserted for a key instead of overwriting it, e.g. for
+/// annotations that share the exact same coordinates. Values of the same key are kept in
+/// insertion order, or in the order given to `with_order`.
+///
+/// The keys themselves are always ordered by start and then by end, as the overlap search prunes
+/// subtrees by the start of their keys. A tiebreak between intervals with identical coordinates,
+/// such as an id, is expressed by the order of their values.
+pub struct IntervalMultiMap<D> {
+    tree: IntervalTree<Vec<D>>,
+    order: Option<Order<D>>
+}
+
+impl<D: fmt::Debug> fmt::Debug for IntervalMultiMap<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IntervalMultiMap").field("tree", &self.tree).field("ordered", &self.order.is_some()).finish()
+    }
+}
+
+/// An iterator over the key,value pairs of an `IntervalMultiMap`, yielding every value of a key
+/// in the order the map keeps them (see `IntervalMultiMap`) before moving on to the next key.
+pub struct MultiIter<'a, D: 'a> {
+    inner: RangePairIter<'a, Vec<D>>,
+    current: Option<(&'a Range, slice::Iter<'a, D>)>
+}
+
+impl<'a, D: 'a> Iterator for MultiIter<'a, D> {
+
+    type Item = (&'a Range, &'a D);
+
+    fn next(&mut self) -> Option<(&'a Range, &'a D)> {
+        loop {
+            if let Some((key, ref mut values)) = self.current {
+                if let Some(value) = values.next() { return Some((key, value)) }
+            }
+            match self.inner.next() {
+                Some((key, values)) => self.current = Some((key, values.iter())),
+                None => return None
+            }
+        }
+    }
+}
+
+impl<D> IntervalMultiMap<D> {
+
+/// This function will construct a new empty IntervalMultiMap.
+/// # Examples
+/// ```
+/// let m=interval_tree::IntervalMultiMap::<i32>::new();
+/// assert!(m.empty());
+/// ```
+    pub fn new() -> IntervalMultiMap<D> {
+        IntervalMultiMap{tree: IntervalTree::new(), order: None}
+    }
+
+/// This function will construct a new empty IntervalMultiMap that keeps the values of every key
+/// sorted by order, so iteration yields pairs in the order (start, end, order). Values that are
+/// equal according to order stay in insertion order. order may capture its environment, e.g. a
+/// table of ranks to sort by, as long as it can be shared between threads, so the map stays
+/// `Send` and `Sync` like its values.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut m=interval_tree::IntervalMultiMap::with_order(|a: &(u32, &str), b: &(u32, &str)| a.0.cmp(&b.0));
+/// m.insert(Range::new(10,20),(7,"cds"));
+/// m.insert(Range::new(10,30),(1,"gene"));
+/// m.insert(Range::new(10,20),(3,"exon"));
+/// assert_eq!(m.iter().map(|(_,v)| v.1).collect::<Vec<_>>(), vec!["exon","cds","gene"]);
+/// ```
+    pub fn with_order<F: Fn(&D, &D) -> Ordering + Send + Sync + 'static>(order: F) -> IntervalMultiMap<D> {
+        IntervalMultiMap{tree: IntervalTree::new(), order: Some(Box::new(order))}
+    }
+
+/// This function will add data to the values of key, keeping all values inserted before.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut m=interval_tree::IntervalMultiMap::new();
+/// m.insert(Range::new(10,20),"exon");
+/// m.insert(Range::new(10,20),"cds");
+/// assert_eq!(m.get(Range::new(10,20)), &["exon","cds"]);
+/// ```
+    pub fn insert<K: IntoRange>(&mut self, key: K, data: D) {
+        let key = match key.into_range() { Some(key) => key, None => return };
+        if let Some(values) = self.tree.root.as_mut().and_then(|root| search_mut(&key, root)) {
+            match self.order {
+                Some(ref order) => {
+                    let at = values.iter().position(|value| order(value, &data) == Ordering::Greater).unwrap_or(values.len());
+                    values.insert(at, data)
+                },
+                None => values.push(data)
+            }
+            return;
+        }
+        self.tree.insert(key, vec![data]);
+    }
+
+/// This function will return all values stored for exactly key, or an empty slice if the key is
+/// not part of the map.
+    pub fn get<K: IntoRange>(&self, key: K) -> &[D] {
+        match self.tree.get(key) {
+            Some(values) => values,
+            None => &[]
+        }
+    }
+
+/// This function will remove the first value of key that is equal to data and return it. The key
+/// is removed from the map together with its last value.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut m=interval_tree::IntervalMultiMap::new();
+/// m.insert(Range::new(10,20),"exon");
+/// m.insert(Range::new(10,20),"cds");
+/// assert_eq!(m.remove_one(Range::new(10,20), &"exon"), Some("exon"));
+/// assert_eq!(m.remove_one(Range::new(10,20), &"exon"), None);
+/// assert_eq!(m.get(Range::new(10,20)), &["cds"]);
+/// ```
+    pub fn remove_one<K: IntoRange>(&mut self, key: K, data: &D) -> Option<D> where D: PartialEq {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let (removed, now_empty) = match self.tree.root.as_mut().and_then(|root| search_mut(&key, root)) {
+            Some(values) => match values.iter().position(|v| v == data) {
+                Some(pos) => (values.remove(pos), values.is_empty()),
+                None => return None
+            },
+            None => return None
+        };
+        if now_empty { self.remove_all(key); }
+        Some(removed)
+    }
+
+/// This function will remove key with all of its values and return them in the order the map
+/// keeps them, i.e. in insertion order unless the map was constructed `with_order`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut m=interval_tree::IntervalMultiMap::new();
+/// m.insert(Range::new(10,20),"exon");
+/// m.insert(Range::new(10,20),"cds");
+/// assert_eq!(m.remove_all(Range::new(10,20)), vec!["exon","cds"]);
+/// assert!(m.empty());
+/// ```
+    pub fn remove_all<K: IntoRange>(&mut self, key: K) -> Vec<D> {
+        self.tree.remove_entry(key).map_or(Vec::new(), |(_, values)| values)
+    }
+
+/// This function will return true if the map is empty, false otherwise.
+    pub fn empty(&self) -> bool { self.tree.empty() }
+
+/// This function will return an iterator over all key,value pairs in ascending order of the keys.
+    pub fn iter(&self) -> MultiIter<D> {
+        MultiIter{inner: self.tree.iter(), current: None}
+    }
+
+/// This function will return an iterator over all key,value pairs whose key intersects the
+/// bounds, yielding every value of a key.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut m=interval_tree::IntervalMultiMap::new();
+/// m.insert(Range::new(10,20),"exon");
+/// m.insert(Range::new(10,20),"cds");
+/// m.insert(Range::new(30,40),"utr");
+/// assert_eq!(m.range(15..=35).map(|(_,v)| *v).collect::<Vec<_>>(), vec!["exon","cds","utr"]);
+/// ```
+    pub fn range<K: IntoRange>(&self, bounds: K) -> MultiIter<D> {
+        MultiIter{inner: self.tree.range(bounds), current: None}
+    }
+}
@@ -2,20 +2,31 @@ extern crate memrange;
 use std::cmp;
 use std::cmp::Ordering;
 use self::memrange::Range;
+use aggregate::{Aggregate, NoAggregate, LenSum};
+use std::io;
+use std::io::Write;
 
 #[derive(Debug)]
-pub struct Node<D> {
+pub struct Node<D, A: Aggregate<D> = NoAggregate> {
     pub key: Range,
     pub data: D,
     height: u32,
     max: u64,
-    pub left: Option<Box<Node<D>>>,
-    pub right:Option<Box<Node<D>>>,
+    min_start: u64,
+    max_gap: u64,
+    size: usize,
+    min_end: u64,
+    max_start: u64,
+    agg: A::Value,
+    pub left: Option<Box<Node<D, A>>>,
+    pub right:Option<Box<Node<D, A>>>,
 }
 
-impl<D> Node<D> {
-    pub fn new(key: Range, data: D) -> Node<D>{
-        Node::<D>{key: key, data: data, height: 1, max: key.max, left: None, right: None}
+impl<D, A: Aggregate<D>> Node<D, A> {
+    pub fn new(key: Range, data: D) -> Node<D, A>{
+        let agg = A::lift(&key, &data);
+        Node::<D, A>{key: key, data: data, height: 1, max: key.max, min_start: key.min, max_gap: 0,
+                  size: 1, min_end: key.max, max_start: key.min, agg: agg, left: None, right: None}
     }
 
     pub fn right_subtree_relevant(&self, range: &Range) -> bool{
@@ -25,19 +36,100 @@ impl<D> Node<D> {
     pub fn left_subtree_relevant(&self, range: &Range) -> bool{
         return self.max >= range.min
     }
+
+    /// returns a lower bound for the distance between p and the keys of this subtree, where a key
+    /// containing p has distance 0
+    pub fn subtree_distance(&self, p: u64) -> u64 {
+        if self.min_start > p { self.min_start - p } else { p.saturating_sub(self.max) }
+    }
+
+    /// returns the smallest start of the keys in this subtree
+    pub fn subtree_min_start(&self) -> u64 {
+        self.min_start
+    }
+
+    /// returns the biggest start of the keys in this subtree
+    pub fn subtree_max_start(&self) -> u64 {
+        self.max_start
+    }
+
+    /// returns the smallest end of the keys in this subtree
+    pub fn subtree_min_end(&self) -> u64 {
+        self.min_end
+    }
+
+    /// returns the biggest end of the keys in this subtree
+    pub fn subtree_max_end(&self) -> u64 {
+        self.max
+    }
+}
+
+impl<D: Clone, A: Aggregate<D>> Clone for Node<D, A> {
+    fn clone(&self) -> Node<D, A> {
+        Node::<D, A>{key: self.key, data: self.data.clone(), height: self.height, max: self.max, min_start: self.min_start,
+                  max_gap: self.max_gap, size: self.size, min_end: self.min_end, max_start: self.max_start,
+                  agg: self.agg.clone(), left: self.left.clone(), right: self.right.clone()}
+    }
 }
 
-pub fn height<D>(node: &Option<Box<Node<D>>>) -> u32  {
+pub fn height<D, A: Aggregate<D>>(node: &Option<Box<Node<D, A>>>) -> u32  {
     return node.as_ref().map_or(0, |succ| succ.height)
 }
 
 
-fn subtree_max<D>(node: &Option<Box<Node<D>>>) -> u64 {
+fn subtree_max<D, A: Aggregate<D>>(node: &Option<Box<Node<D, A>>>) -> u64 {
     return node.as_ref().map_or(0, |succ| succ.max)
 }
 
-/// Perform a single right rotation on this (sub) tree
-fn rotate_right<D>(mut root: Box<Node<D>>) -> Box<Node<D>>{
+fn subtree_size<D, A: Aggregate<D>>(node: &Option<Box<Node<D, A>>>) -> usize {
+    return node.as_ref().map_or(0, |succ| succ.size)
+}
+
+fn subtree_min_end<D, A: Aggregate<D>>(node: &Option<Box<Node<D, A>>>) -> u64 {
+    return node.as_ref().map_or(0xffff_ffff_ffff_ffff, |succ| succ.min_end)
+}
+
+fn subtree_gap<D, A: Aggregate<D>>(node: &Option<Box<Node<D, A>>>) -> u64 {
+    return node.as_ref().map_or(0, |succ| succ.max_gap)
+}
+
+/// returns the number of values strictly between covered_until and next_start
+fn gap_between(covered_until: u64, next_start: u64) -> u64 {
+    if next_start > covered_until { next_start - covered_until - 1 } else { 0 }
+}
+
+/// returns an upper bound for the size of all uncovered runs between the smallest start and the
+/// biggest end of the keys below root. The bound is exact if no two keys intersect.
+fn max_gap<D, A: Aggregate<D>>(root: &Node<D, A>) -> u64 {
+    let mut res = cmp::max(subtree_gap(&root.left), subtree_gap(&root.right));
+    let mut covered_until = root.key.max;
+    if let Some(ref succ) = root.left {
+        res = cmp::max(res, gap_between(succ.max, root.key.min));
+        covered_until = cmp::max(covered_until, succ.max);
+    }
+    if let Some(ref succ) = root.right {
+        res = cmp::max(res, gap_between(covered_until, succ.min_start));
+    }
+    res
+}
+
+//Adds up the depths of all nodes below root, where root has the given depth
+pub fn total_depth<D, A: Aggregate<D>>(root: &Option<Box<Node<D, A>>>, depth: usize) -> usize {
+    let mut total = 0;
+    let mut stack = Vec::new();
+    if let Some(ref node) = *root { stack.push((node, depth)) }
+    while let Some((node, depth)) = stack.pop() {
+        total += depth;
+        if let Some(ref succ) = node.left { stack.push((succ, depth + 1)) }
+        if let Some(ref succ) = node.right { stack.push((succ, depth + 1)) }
+    }
+    total
+}
+
+/// Perform a single right rotation on this (sub) tree, counted in rotations. The functions that
+/// may rotate take the counter of the tree they work on.
+fn rotate_right<D, A: Aggregate<D>>(mut root: Box<Node<D, A>>, rotations: &mut u64) -> Box<Node<D, A>>{
+    *rotations += 1;
     let mut new_root_box = root.left.take().expect("Avl broken");
     root.left = new_root_box.right.take();
     update_height(&mut root);
@@ -47,7 +139,8 @@ fn rotate_right<D>(mut root: Box<Node<D>>) -> Box<Node<D>>{
 }
 
 /// Perform a single left rotation on this (sub) tree
-fn rotate_left<D>(mut root: Box<Node<D>>) -> Box<Node<D>>{
+fn rotate_left<D, A: Aggregate<D>>(mut root: Box<Node<D, A>>, rotations: &mut u64) -> Box<Node<D, A>>{
+    *rotations += 1;
     let mut new_root_box = root.right.take().expect("Avl broken");
     root.right = new_root_box.left.take();
     update_height(&mut root);
@@ -57,105 +150,194 @@ fn rotate_left<D>(mut root: Box<Node<D>>) -> Box<Node<D>>{
 }
 
 /// Performs a rotation that counteracts the fact that the left successor is too high
-fn rotate_left_successor<D>(mut root: Box<Node<D>>) -> Box<Node<D>> {
+fn rotate_left_successor<D, A: Aggregate<D>>(mut root: Box<Node<D, A>>, rotations: &mut u64) -> Box<Node<D, A>> {
     let left = root.left.take().expect("Interval broken");
     if height(&left.left) < height(&left.right) {
-        let rotated = rotate_left(left);
+        let rotated = rotate_left(left, rotations);
         root.left = Some(rotated);
         update_height(&mut root);
     }
     else{
         root.left = Some(left);
     }
-    rotate_right(root)
+    rotate_right(root, rotations)
 }
 
 /// Performs a rotation that counteracts the fact that the right successor is too high
-fn rotate_right_successor<D>(mut root: Box<Node<D>>) -> Box<Node<D>> {
+fn rotate_right_successor<D, A: Aggregate<D>>(mut root: Box<Node<D, A>>, rotations: &mut u64) -> Box<Node<D, A>> {
     let right = root.right.take().expect("Interval broken");
     if height(&right.left) > height(&right.right) {
-        let rotated = rotate_right(right);
+        let rotated = rotate_right(right, rotations);
         root.right = Some(rotated);
         update_height(&mut root);
     }
     else {
         root.right = Some(right)
     }
-    rotate_left(root)
+    rotate_left(root, rotations)
 }
 
-fn diff_of_successors_height<D>(root: &Box<Node<D>>) -> i32 {
+fn diff_of_successors_height<D, A: Aggregate<D>>(root: &Box<Node<D, A>>) -> i32 {
     let l = height(&root.left);
     let r = height(&root.right);
     (l as i32) - (r as i32)
 }
 
 
-/// Apply all necessary rotations on root. 
-fn rotate_if_necessary<D>(root: Box<Node<D>>) -> Box<Node<D>> {
+/// Apply all necessary rotations on root. In a strict AVL tree the difference is at most 2, after
+/// relaxed deletes it may be bigger; the rotation then only moves root towards balance.
+fn rotate_if_necessary<D, A: Aggregate<D>>(root: Box<Node<D, A>>, rotations: &mut u64) -> Box<Node<D, A>> {
     let diff  = diff_of_successors_height(&root);
     if -1 <= diff && diff <= 1 {return root}
-    match diff{
-        2 => rotate_left_successor::<D>(root),
-        -2 => rotate_right_successor::<D>(root),
-        _ => unreachable!()
-    }
+    if diff > 1 { rotate_left_successor(root, rotations) } else { rotate_right_successor(root, rotations) }
 }
 
 /// update the cached height of root. To call this function make sure that the cached values of
 /// both children of root ar up to date.
-fn update_height<D>(root: &mut Node<D>){
+pub fn update_height<D, A: Aggregate<D>>(root: &mut Node<D, A>){
     root.height = cmp::max( height(&root.left), height(&root.right) )+1;
     root.max = cmp::max(subtree_max(&root.left), cmp::max(subtree_max(&root.right), root.key.max));
+    root.min_start = root.left.as_ref().map_or(root.key.min, |succ| succ.min_start);
+    root.max_gap = max_gap(root);
+    root.size = subtree_size(&root.left) + subtree_size(&root.right) + 1;
+    root.min_end = cmp::min(subtree_min_end(&root.left), cmp::min(subtree_min_end(&root.right), root.key.max));
+    root.max_start = root.right.as_ref().map_or(root.key.min, |succ| succ.max_start);
+    let own = A::lift(&root.key, &root.data);
+    let with_left = match root.left { Some(ref succ) => A::combine(&succ.agg, &own), None => own };
+    root.agg = match root.right { Some(ref succ) => A::combine(&with_left, &succ.agg), None => with_left };
 }
 
-/// recursively insert the (key,data) pair into the given optional succesor and return its new
-/// value
-fn insert_in_successor<D>(key: Range, data: D, successor: Option<Box<Node<D>>>)->Option<Box<Node<D>>> {
-            Some(match successor {
-                Some(succ) => insert(key, data, succ),
-                None =>Box::new(Node::new(key, data))
-            })
+/// Detaches the successor of node in direction dir (Less means right, Greater means left) and
+/// returns it, used to walk down the tree while keeping ownership of the path.
+fn take_successor<D, A: Aggregate<D>>(node: &mut Node<D, A>, dir: Ordering) -> Option<Box<Node<D, A>>> {
+    match dir {
+        Ordering::Less => node.right.take(),
+        _ => node.left.take()
+    }
+}
+
+/// Reattaches the subtrees along path (as produced by walking down with `take_successor`) bottom
+/// up, starting with subtree as the new successor of the last node. Heights are updated and
+/// rotations are applied on the way up. Returns the new root.
+fn rebuild_path<D, A: Aggregate<D>>(mut path: Vec<(Box<Node<D, A>>, Ordering)>, mut subtree: Option<Box<Node<D, A>>>, rotations: &mut u64) -> Option<Box<Node<D, A>>> {
+    while let Some((mut parent, dir)) = path.pop() {
+        match dir {
+            Ordering::Less => parent.right = subtree,
+            _ => parent.left = subtree
+        }
+        subtree = Some(updated_node(parent, rotations));
+    }
+    subtree
+}
+
+/// Like `rebuild_path`, but only updates the cached values on the way up and never rotates.
+fn reattach_path<D, A: Aggregate<D>>(mut path: Vec<(Box<Node<D, A>>, Ordering)>, mut subtree: Option<Box<Node<D, A>>>) -> Option<Box<Node<D, A>>> {
+    while let Some((mut parent, dir)) = path.pop() {
+        match dir {
+            Ordering::Less => parent.right = subtree,
+            _ => parent.left = subtree
+        }
+        update_height(&mut parent);
+        subtree = Some(parent);
+    }
+    subtree
 }
 
 /// Inserts the given data under the key in the tree root. It will replace old data stored
 /// under this key if it was allready used in the tree. The resulting tree will be returned (its
-/// root may now differ due to rotations, thus the old root is moved into the function)
-pub fn insert<D>(key: Range, data: D, mut root: Box<Node<D>>) -> Box<Node<D>>{
-    match root.key.cmp(&key) {
-        Ordering::Equal => { root.data  = data; return root },
-        Ordering::Less =>    root.right = insert_in_successor(key, data, root.right.take()),
-        Ordering::Greater => root.left  = insert_in_successor(key,data, root.left.take())
-    }
-    update_height(&mut *root);
-    return rotate_if_necessary(root)
+/// root may now differ due to rotations, thus the old root is moved into the function).
+pub fn insert<D, A: Aggregate<D>>(key: Range, data: D, root: Box<Node<D, A>>, rotations: &mut u64) -> Box<Node<D, A>>{
+    insert_with(key, data, root, |_, new| new, rotations)
+}
+
+/// Like `insert`, but if key is already used in the tree, the data stored under it is replaced by
+/// merge(old, data) in the same descent. The tree is walked with an explicit path instead of
+/// recursion, so the stack usage does not depend on the height of the tree.
+pub fn insert_with<D, A: Aggregate<D>, F: FnOnce(D, D) -> D>(key: Range, data: D, root: Box<Node<D, A>>, merge: F, rotations: &mut u64) -> Box<Node<D, A>>{
+    let mut path = Vec::with_capacity(root.height as usize);
+    let mut current = Some(root);
+    let subtree = loop {
+        match current {
+            None => break Box::new(Node::new(key, data)),
+            Some(mut node) => {
+                let dir = node.key.cmp(&key);
+                if dir == Ordering::Equal {
+                    let old = node.data;
+                    node.data = merge(old, data);
+                    update_height(&mut node);
+                    break node
+                }
+                current = take_successor(&mut node, dir);
+                path.push((node, dir));
+            }
+        }
+    };
+    rebuild_path(path, Some(subtree), rotations).expect("tree can not be empty after insertion")
+}
+
+/// builds a perfectly balanced tree from the next len (key,data) pairs of iter in O(len). The
+/// pairs have to be sorted by key and free of duplicates, otherwise the result is no valid
+/// interval tree. Returns None if len is 0.
+pub fn from_sorted<D, A: Aggregate<D>, I: Iterator<Item=(Range, D)>>(len: usize, iter: &mut I) -> Option<Box<Node<D, A>>> {
+    if len == 0 { return None }
+    let left = from_sorted(len/2, iter);
+    let (key, data) = iter.next().expect("iterator shorter than announced length");
+    let mut root = Box::new(Node::new(key, data));
+    root.left = left;
+    root.right = from_sorted(len - len/2 - 1, iter);
+    update_height(&mut root);
+    return Some(root)
 }
 
 /// returns a read only reference to the data stored under key in the tree given by root
-pub fn search<'a, D>(key: &Range, root: &'a Box<Node<D>>) -> Option<&'a D>{
+pub fn search<'a, D, A: Aggregate<D>>(key: &Range, root: &'a Box<Node<D, A>>) -> Option<&'a D>{
     search_pair(key,root).map(|(_,v)| v )
 }
 
 /// returns a read only reference paie to the data stored under key in the tree given by root
-pub fn search_pair<'a,D>(key: &Range, root: &'a Box<Node<D>>) -> Option<(&'a Range,&'a D)>{
-    match root.key.cmp(key) {
-        Ordering::Equal => Some((&root.key, &root.data)),
-        Ordering::Less => root.right.as_ref().map_or(None, |succ| search_pair(key, succ)),
-        Ordering::Greater => root.left.as_ref().map_or(None, |succ| search_pair(key, succ))
+pub fn search_pair<'a, D, A: Aggregate<D>>(key: &Range, root: &'a Box<Node<D, A>>) -> Option<(&'a Range,&'a D)>{
+    let mut node = root;
+    loop {
+        let succ = match node.key.cmp(key) {
+            Ordering::Equal => return Some((&node.key, &node.data)),
+            Ordering::Less => &node.right,
+            Ordering::Greater => &node.left
+        };
+        match *succ {
+            Some(ref succ) => node = succ,
+            None => return None
+        }
     }
 }
 
 
+/// returns a mutable reference to the data stored under key in the tree given by root
+pub fn search_mut<'a, D, A: Aggregate<D>>(key: &Range, root: &'a mut Box<Node<D, A>>) -> Option<&'a mut D>{
+    let mut node = root;
+    loop {
+        let current = node;
+        let succ = match current.key.cmp(key) {
+            Ordering::Equal => return Some(&mut current.data),
+            Ordering::Less => &mut current.right,
+            Ordering::Greater => &mut current.left
+        };
+        match *succ {
+            Some(ref mut succ) => node = succ,
+            None => return None
+        }
+    }
+}
+
 /// returns true iff key is stored in the tree given by root
 #[cfg(test)]
-pub fn contains<D>(key: &Range, root: &Box<Node<D>> ) -> bool  {
+pub fn contains<D, A: Aggregate<D>>(key: &Range, root: &Box<Node<D, A>> ) -> bool  {
     search(key,root).is_some()
 }
 
 
 ///returns the smallest key and value after the given key.
 #[cfg(test)]
-pub fn min_after<'a,D>(key: &Range, root: &'a Box<Node<D>>) -> Option<(&'a Range,&'a D)> {
+pub fn min_after<'a, D, A: Aggregate<D>>(key: &Range, root: &'a Box<Node<D, A>>) -> Option<(&'a Range,&'a D)> {
     match root.key.cmp(key){
         Ordering::Equal =>  root.right.as_ref().map_or(None, |succ| Some(min_pair(succ))),
         Ordering::Less =>   root.right.as_ref().map_or(None, |succ| min_after(key, succ)),
@@ -168,121 +350,616 @@ pub fn min_after<'a,D>(key: &Range, root: &'a Box<Node<D>>) -> Option<(&'a Range
     }
 }
 
+///returns the number of keys below root.
+pub fn size<D, A: Aggregate<D>>(root: &Option<Box<Node<D, A>>>) -> usize {
+    subtree_size(root)
+}
+
+///returns the key,value pair with the k-th smallest key (starting at 0) below root.
+pub fn select<'a, D, A: Aggregate<D>>(mut k: usize, root: &'a Box<Node<D, A>>) -> Option<(&'a Range,&'a D)> {
+    let mut node = root;
+    loop {
+        let left = subtree_size(&node.left);
+        let succ = match k.cmp(&left) {
+            Ordering::Less => &node.left,
+            Ordering::Equal => return Some((&node.key, &node.data)),
+            Ordering::Greater => { k -= left + 1; &node.right }
+        };
+        match *succ {
+            Some(ref succ) => node = succ,
+            None => return None
+        }
+    }
+}
+
+///returns the total length of all keys below root
+pub fn total_len<D>(root: &Option<Box<Node<D, LenSum>>>) -> u128 {
+    root.as_ref().map_or(0, |succ| succ.agg)
+}
+
+///returns the pair that covers position offset if the keys below root were laid out one after
+///another in ascending order, or None if offset is not smaller than their total length
+pub fn select_by_len<'a, D>(mut offset: u128, root: &'a Box<Node<D, LenSum>>) -> Option<(&'a Range,&'a D)> {
+    let mut node = root;
+    loop {
+        let left = total_len(&node.left);
+        let own = LenSum::lift(&node.key, &node.data);
+        let succ = if offset < left {
+            &node.left
+        } else if offset - left < own {
+            return Some((&node.key, &node.data))
+        } else {
+            offset -= left + own;
+            &node.right
+        };
+        match *succ {
+            Some(ref succ) => node = succ,
+            None => return None
+        }
+    }
+}
+
+///returns the number of keys below root that are smaller than key.
+pub fn rank<D, A: Aggregate<D>>(key: &Range, root: &Option<Box<Node<D, A>>>) -> usize {
+    let mut res = 0;
+    let mut current = root;
+    while let Some(ref node) = *current {
+        if node.key < *key {
+            res += subtree_size(&node.left) + 1;
+            current = &node.right;
+        } else {
+            current = &node.left;
+        }
+    }
+    res
+}
+
+///appends every pair below root to the results of all queries whose ranges intersect its key. The
+///queries that may reach root are buf[lo..hi], sorted by their start; the ones that reach a
+///successor are pushed onto buf for the recursive call and dropped afterwards, so every node is
+///visited once per batch. As the starts ascend, the scan for a subtree stops at the first query
+///that starts after all keys of the subtree end.
+pub fn batch_overlaps<'a, D, A: Aggregate<D>>(root: &'a Box<Node<D, A>>, queries: &[Range], buf: &mut Vec<usize>, lo: usize, hi: usize, out: &mut Vec<Vec<(&'a Range, &'a D)>>) {
+    if let Some(ref succ) = root.left {
+        batch_overlaps_successor(succ, queries, buf, lo, hi, out);
+    }
+    for i in lo..hi {
+        let q = buf[i];
+        if queries[q].min > root.key.max { break }
+        if root.key.intersect(&queries[q]) { out[q].push((&root.key, &root.data)) }
+    }
+    if let Some(ref succ) = root.right {
+        batch_overlaps_successor(succ, queries, buf, lo, hi, out);
+    }
+}
+
+fn batch_overlaps_successor<'a, D, A: Aggregate<D>>(succ: &'a Box<Node<D, A>>, queries: &[Range], buf: &mut Vec<usize>, lo: usize, hi: usize, out: &mut Vec<Vec<(&'a Range, &'a D)>>) {
+    let start = buf.len();
+    for i in lo..hi {
+        let q = buf[i];
+        if queries[q].min > succ.max { break }
+        if queries[q].max >= succ.min_start { buf.push(q) }
+    }
+    if buf.len() > start { batch_overlaps(succ, queries, buf, start, buf.len(), out) }
+    buf.truncate(start);
+}
+
+///returns the number of keys below root that intersect range. Subtrees whose keys all intersect
+///range are counted as a whole via their size, so if no key contains another one only the two
+///paths to the ends of the intersecting run are followed.
+pub fn count_overlaps<D, A: Aggregate<D>>(range: &Range, root: &Option<Box<Node<D, A>>>) -> usize {
+    let node = match *root { Some(ref node) => node, None => return 0 };
+    if node.min_start > range.max || node.max < range.min { return 0 }
+    if node.max_start <= range.max && node.min_end >= range.min { return node.size }
+    let own = if node.key.intersect(range) { 1 } else { 0 };
+    own + count_overlaps(range, &node.left) + count_overlaps(range, &node.right)
+}
+
+///returns the aggregate over all keys below root that intersect range, combined in ascending
+///order of the keys. Whole subtrees are taken from the cache under the same conditions as in
+///`count_overlaps`.
+pub fn aggregate_overlaps<D, A: Aggregate<D>>(range: &Range, root: &Option<Box<Node<D, A>>>) -> A::Value {
+    let node = match *root { Some(ref node) => node, None => return A::empty() };
+    if node.min_start > range.max || node.max < range.min { return A::empty() }
+    if node.max_start <= range.max && node.min_end >= range.min { return node.agg.clone() }
+    let mut res = aggregate_overlaps(range, &node.left);
+    if node.key.intersect(range) { res = A::combine(&res, &A::lift(&node.key, &node.data)) }
+    A::combine(&res, &aggregate_overlaps(range, &node.right))
+}
+
+///finds the node with the biggest lifted aggregate among the keys below root that intersect range
+///and stores it in best if it beats the one found so far; ties go to the smallest key. The
+///aggregate of a subtree has to bound the ones of its keys from above, so subtrees that can not
+///beat best are skipped.
+pub fn top_overlap<'a, D, A: Aggregate<D>>(range: &Range, root: &'a Option<Box<Node<D, A>>>, best: &mut Option<(A::Value, &'a Node<D, A>)>) where A::Value: Ord {
+    let node = match *root { Some(ref node) => node, None => return };
+    if node.min_start > range.max || node.max < range.min { return }
+    if best.as_ref().map_or(false, |&(ref top, _)| node.agg <= *top) { return }
+    top_overlap(range, &node.left, best);
+    if node.key.intersect(range) {
+        let own = A::lift(&node.key, &node.data);
+        if best.as_ref().map_or(true, |&(ref top, _)| own > *top) { *best = Some((own, node)) }
+    }
+    top_overlap(range, &node.right, best);
+}
+
+///returns the node with the biggest end below root, preferring nodes closer to root.
+fn max_end_node<D, A: Aggregate<D>>(root: &Node<D, A>) -> &Node<D, A> {
+    let mut node = root;
+    while node.key.max != node.max {
+        node = match node.left {
+            Some(ref succ) if succ.max == node.max => succ,
+            _ => node.right.as_ref().expect("Interval broken")
+        };
+    }
+    node
+}
+
+///returns the key,value pair with the biggest end among all keys below root that start at or
+///before p. The keys starting at or before p form O(log n) subtrees and nodes along a single path,
+///of which the one with the biggest end is picked and then searched for the key reaching it.
+pub fn max_end_starting_before<'a, D, A: Aggregate<D>>(p: u64, root: &'a Box<Node<D, A>>) -> Option<(&'a Range,&'a D)> {
+    //the best end found so far, the node reaching it and whether it is reached anywhere in the
+    //subtree of that node (the whole subtree starts before p) or by the key of the node itself
+    let mut best: Option<(u64, &'a Node<D, A>, bool)> = None;
+    let mut current = Some(root);
+    while let Some(node) = current {
+        if node.key.min <= p {
+            if best.map_or(true, |(end, _, _)| node.key.max > end) { best = Some((node.key.max, node, false)) }
+            if let Some(ref succ) = node.left {
+                if best.map_or(true, |(end, _, _)| succ.max > end) { best = Some((succ.max, succ, true)) }
+            }
+            current = node.right.as_ref();
+        } else {
+            current = node.left.as_ref();
+        }
+    }
+    best.map(|(_, node, whole_subtree)| {
+        let node = if whole_subtree { max_end_node(node) } else { node };
+        (&node.key, &node.data)
+    })
+}
+
+///returns the key,value pair with the smallest key below root that starts after p.
+pub fn min_starting_after<'a, D, A: Aggregate<D>>(p: u64, root: &'a Box<Node<D, A>>) -> Option<(&'a Range,&'a D)> {
+    let mut res = None;
+    let mut current = Some(root);
+    while let Some(node) = current {
+        if node.key.min > p {
+            res = Some((&node.key, &node.data));
+            current = node.left.as_ref();
+        } else {
+            current = node.right.as_ref();
+        }
+    }
+    res
+}
+
+///returns the key,value pair with the smallest key that intersects range. If the left subtree
+///reaches into range it either contains an intersecting key or no key after it can intersect, so
+///only a single path has to be followed.
+pub fn first_overlap<'a, D, A: Aggregate<D>>(range: &Range, root: &'a Box<Node<D, A>>) -> Option<(&'a Range,&'a D)> {
+    if root.max < range.min { return None }
+    if let Some(ref succ) = root.left {
+        if succ.max >= range.min { return first_overlap(range, succ) }
+    }
+    if root.key.intersect(range) { return Some((&root.key, &root.data)) }
+    if root.key.min > range.max { return None }
+    root.right.as_ref().map_or(None, |succ| first_overlap(range, succ))
+}
+
+///returns the key,value pair with the biggest key that intersects range. Failed descents into the
+///right subtree are rejected by the cached max, so this stays logarithmic.
+pub fn last_overlap<'a, D, A: Aggregate<D>>(range: &Range, root: &'a Box<Node<D, A>>) -> Option<(&'a Range,&'a D)> {
+    if root.max < range.min { return None }
+    if root.key.min <= range.max {
+        if let Some(res) = root.right.as_ref().map_or(None, |succ| last_overlap(range, succ)) {
+            return Some(res)
+        }
+        if root.key.intersect(range) { return Some((&root.key, &root.data)) }
+    }
+    root.left.as_ref().map_or(None, |succ| last_overlap(range, succ))
+}
+
+///returns the key,value pair with the smallest key bigger than after that intersects range.
+pub fn first_overlap_after<'a, D, A: Aggregate<D>>(range: &Range, after: &Range, root: &'a Box<Node<D, A>>) -> Option<(&'a Range,&'a D)> {
+    if root.max < range.min { return None }
+    if root.key <= *after {
+        return root.right.as_ref().map_or(None, |succ| first_overlap_after(range, after, succ))
+    }
+    if let Some(res) = root.left.as_ref().map_or(None, |succ| first_overlap_after(range, after, succ)) {
+        return Some(res)
+    }
+    if root.key.intersect(range) { return Some((&root.key, &root.data)) }
+    if root.key.min > range.max { return None }
+    root.right.as_ref().map_or(None, |succ| first_overlap_after(range, after, succ))
+}
+
+///searches the keys below root in ascending order for the first run of at least size values that
+///are not covered by any key, starting at the value free. free is advanced past all values covered
+///by the visited keys (None if everything up to u64::MAX is covered). Returns the start of the run
+///if it lies before some key below root. Subtrees whose gap bound is smaller than size are skipped.
+pub fn find_gap<D, A: Aggregate<D>>(root: &Box<Node<D, A>>, size: u64, free: &mut Option<u64>) -> Option<u64> {
+    let start = match *free { Some(start) => start, None => return None };
+    if root.max < start { return None }
+    if root.min_start > start && root.min_start - start >= size { return Some(start) }
+    if root.max_gap < size {
+        *free = root.max.checked_add(1);
+        return None
+    }
+    if let Some(ref succ) = root.left {
+        if let Some(res) = find_gap(succ, size, free) { return Some(res) }
+    }
+    let start = match *free { Some(start) => start, None => return None };
+    if root.key.min > start && root.key.min - start >= size { return Some(start) }
+    if root.key.max >= start { *free = root.key.max.checked_add(1) }
+    root.right.as_ref().map_or(None, |succ| find_gap(succ, size, free))
+}
+
 ///returns the minimal key,value pair within this tree
-pub fn min_pair<D>(root: &Box<Node<D>>) -> (&Range,&D) {
+pub fn min_pair<D, A: Aggregate<D>>(root: &Box<Node<D, A>>) -> (&Range,&D) {
     root.left.as_ref().map_or((&root.key,&root.data), min_pair)
 }
 
 ///returns the maximal key,value pair within this tree
-pub fn max_pair<D>(root: &Box<Node<D>>) -> (&Range,&D) {
+pub fn max_pair<D, A: Aggregate<D>>(root: &Box<Node<D, A>>) -> (&Range,&D) {
     root.right.as_ref().map_or((&root.key,&root.data), max_pair)
 }
 
 ///returns the minimal value within this tree
 #[cfg(test)]
-pub fn min<D>(root: &Box<Node<D>>) -> &D {
+pub fn min<D, A: Aggregate<D>>(root: &Box<Node<D, A>>) -> &D {
     root.left.as_ref().map_or(&root.data, min)
 }
 
 ///returns the minimal value within this tree
 #[cfg(test)]
-pub fn max<D>(root: &Box<Node<D>>) -> &D {
+pub fn max<D, A: Aggregate<D>>(root: &Box<Node<D, A>>) -> &D {
     root.right.as_ref().map_or(&root.data, max)
 }
 
-//will update_heights and rotate the node if necessary, returns the rotated node
-fn updated_node<D>(mut root: Box<Node<D>>) -> Box<Node<D>> {
-    update_height(&mut root);
-    rotate_if_necessary(root)
+/// writes root and all nodes below it as graphviz nodes and edges to writer. next_id is the id
+/// used for root, it will be advanced past all ids used by this subtree. Returns the id of root.
+pub fn write_dot<D, A: Aggregate<D>, W: Write>(root: &Box<Node<D, A>>, next_id: &mut usize, writer: &mut W) -> io::Result<usize> {
+    let id = *next_id;
+    *next_id += 1;
+    writeln!(writer, "    n{} [label=\"[{}, {}]\\nmax {}\\nheight {}, balance {}\"];",
+             id, root.key.min, root.key.max, root.max, root.height, diff_of_successors_height(root))?;
+    if let Some(ref succ) = root.left {
+        let succ_id = write_dot(succ, next_id, writer)?;
+        writeln!(writer, "    n{} -> n{} [label=\"L\"];", id, succ_id)?;
+    }
+    if let Some(ref succ) = root.right {
+        let succ_id = write_dot(succ, next_id, writer)?;
+        writeln!(writer, "    n{} -> n{} [label=\"R\"];", id, succ_id)?;
+    }
+    Ok(id)
+}
+
+/// appends a sideways rendering of root to out: the right subtree above, the left subtree below
+/// and every level indented by four more spaces than its parent.
+pub fn format_tree<D, A: Aggregate<D>>(root: &Box<Node<D, A>>, depth: usize, out: &mut String) {
+    if let Some(ref succ) = root.right { format_tree(succ, depth+1, out) }
+    for _ in 0..depth { out.push_str("    ") }
+    out.push_str(&format!("[{}, {}] max={} h={}\n", root.key.min, root.key.max, root.max, root.height));
+    if let Some(ref succ) = root.left { format_tree(succ, depth+1, out) }
 }
 
-//Performs recursive `drop_and_get_min` if a left  since a successor is available
-fn drop_min_from_left<D>(mut root : Box<Node<D>>, left: Box<Node<D>>) -> (Option<Box<Node<D>>>,Box<Node<D>>) {
-    let (new_left, min) =  drop_min(left);
-    root.left = new_left;
-    (Some(updated_node(root)),min)
+//will update_heights and rotate the node if necessary, returns the rotated node
+fn updated_node<D, A: Aggregate<D>>(mut root: Box<Node<D, A>>, rotations: &mut u64) -> Box<Node<D, A>> {
+    update_height(&mut root);
+    rotate_if_necessary(root, rotations)
 }
 
 //Finds the minimal value below root and returns a new (optional) tree where the minimal value has been
 //removed and the (optional) minimal node as tuple (new_tree, min);
-fn drop_min<D>(mut root: Box<Node<D>>) -> (Option<Box<Node<D>>>, Box<Node<D>>) {
-    match root.left.take() {
-        Some(left) => drop_min_from_left(root, left),
-        None => (root.right.take(), root)
+fn drop_min<D, A: Aggregate<D>>(mut root: Box<Node<D, A>>, rotations: &mut u64) -> (Option<Box<Node<D, A>>>, Box<Node<D, A>>) {
+    let mut path = Vec::with_capacity(root.height as usize);
+    while let Some(left) = root.left.take() {
+        path.push((root, Ordering::Greater));
+        root = left;
     }
+    let remaining = root.right.take();
+    (rebuild_path(path, remaining, rotations), root)
 }
 
 //Return a new Interval tree, as the combination of two subtrees with max(l) <= min(r)
-fn combine_two_subtrees<D>(l: Box<Node<D>>, r: Box<Node<D>>) -> Box<Node<D>>{
-    let (remaining_tree, min) = drop_min(r);
+fn combine_two_subtrees<D, A: Aggregate<D>>(l: Box<Node<D, A>>, r: Box<Node<D, A>>, rotations: &mut u64) -> Box<Node<D, A>>{
+    let (remaining_tree, min) = drop_min(r, rotations);
     let mut new_root = min;
     new_root.left = Some(l);
     new_root.right = remaining_tree;
-    updated_node(new_root)
+    updated_node(new_root, rotations)
 }
 
-//Return a new Interval tree, where the root has been removed
-fn delete_root<D>(mut root: Box<Node<D>>) -> Option<Box<Node<D>>> {
+//Descends along the right spine of l until a subtree is low enough to be joined with r below
+//mid, then rebalances on the way back up. Requires height(l) > height(r)+1.
+fn join_right<D, A: Aggregate<D>>(mut l: Box<Node<D, A>>, mut mid: Box<Node<D, A>>, r: Option<Box<Node<D, A>>>, rotations: &mut u64) -> Box<Node<D, A>> {
+    let succ = l.right.take();
+    if height(&succ) <= height(&r)+1 {
+        mid.left = succ;
+        mid.right = r;
+        l.right = Some(updated_node(mid, rotations));
+    } else {
+        l.right = Some(join_right(succ.expect("Avl broken"), mid, r, rotations));
+    }
+    updated_node(l, rotations)
+}
+
+//Mirror image of `join_right`. Requires height(r) > height(l)+1.
+fn join_left<D, A: Aggregate<D>>(l: Option<Box<Node<D, A>>>, mut mid: Box<Node<D, A>>, mut r: Box<Node<D, A>>, rotations: &mut u64) -> Box<Node<D, A>> {
+    let succ = r.left.take();
+    if height(&succ) <= height(&l)+1 {
+        mid.left = l;
+        mid.right = succ;
+        r.left = Some(updated_node(mid, rotations));
+    } else {
+        r.left = Some(join_left(l, mid, succ.expect("Avl broken"), rotations));
+    }
+    updated_node(r, rotations)
+}
+
+//Return a new Interval tree containing l, mid and r, where all keys in l are smaller and all keys
+//in r are bigger than the key of mid. The successors of mid are replaced. This needs
+//O(|height(l)-height(r)|+1) steps.
+pub fn join<D, A: Aggregate<D>>(l: Option<Box<Node<D, A>>>, mut mid: Box<Node<D, A>>, r: Option<Box<Node<D, A>>>, rotations: &mut u64) -> Box<Node<D, A>> {
+    if height(&l) > height(&r)+1 { return join_right(l.expect("Avl broken"), mid, r, rotations) }
+    if height(&r) > height(&l)+1 { return join_left(l, mid, r.expect("Avl broken"), rotations) }
+    mid.left = l;
+    mid.right = r;
+    updated_node(mid, rotations)
+}
+
+//Return a new Interval tree as the combination of two (optional) trees with max(l) < min(r)
+pub fn join_two<D, A: Aggregate<D>>(l: Option<Box<Node<D, A>>>, r: Option<Box<Node<D, A>>>, rotations: &mut u64) -> Option<Box<Node<D, A>>> {
+    match (l, r) {
+        (None, r) => r,
+        (l, None) => l,
+        (l, Some(r)) => {
+            let (remaining, min) = drop_min(r, rotations);
+            Some(join(l, min, remaining, rotations))
+        }
+    }
+}
+
+//Splits the tree into the nodes whose keys start before at and the nodes whose keys start at or
+//after at, returned as (before, after). Needs O(log n) joins along a single path.
+pub fn split<D, A: Aggregate<D>>(root: Option<Box<Node<D, A>>>, at: u64, rotations: &mut u64) -> (Option<Box<Node<D, A>>>, Option<Box<Node<D, A>>>) {
+    let mut root = match root { Some(root) => root, None => return (None, None) };
+    let left = root.left.take();
+    let right = root.right.take();
+    if root.key.min < at {
+        let (before, after) = split(right, at, rotations);
+        (Some(join(left, root, before, rotations)), after)
+    } else {
+        let (before, after) = split(left, at, rotations);
+        (before, Some(join(after, root, right, rotations)))
+    }
+}
+
+//Moves every key of the subtree by delta, which must not move any key out of the u64 key space. The
+//order of the keys and therefore the shape of the tree stay the same, only the cached values are
+//updated.
+pub fn shift<D, A: Aggregate<D>>(root: &mut Option<Box<Node<D, A>>>, delta: i64) {
+    if let Some(ref mut node) = *root {
+        shift(&mut node.left, delta);
+        shift(&mut node.right, delta);
+        let offset = delta.wrapping_abs() as u64;
+        node.key = if delta < 0 { Range::new(node.key.min - offset, node.key.max - offset) }
+                   else { Range::new(node.key.min + offset, node.key.max + offset) };
+        update_height(node);
+    }
+}
+
+//Converts the data of every node with f in ascending order of the keys, keeping the shape and the
+//cached values of the tree
+pub fn map_values<D, E, F: FnMut(D) -> E>(root: Option<Box<Node<D>>>, f: &mut F) -> Option<Box<Node<E>>> {
+    root.map(|node| {
+        let node = *node;
+        let left = map_values(node.left, f);
+        let data = f(node.data);
+        let right = map_values(node.right, f);
+        Box::new(Node::<E>{key: node.key, data: data, height: node.height, max: node.max, min_start: node.min_start,
+                           max_gap: node.max_gap, size: node.size, min_end: node.min_end, max_start: node.max_start,
+                           agg: (), left: left, right: right})
+    })
+}
+
+//Removes all nodes for which f returns false, visiting the nodes in ascending order of their keys
+pub fn retain<D, A: Aggregate<D>, F: FnMut(&Range, &mut D) -> bool>(root: Option<Box<Node<D, A>>>, f: &mut F, rotations: &mut u64) -> Option<Box<Node<D, A>>> {
+    let mut root = match root { Some(root) => root, None => return None };
+    let left = retain(root.left.take(), f, rotations);
+    let keep = f(&root.key, &mut root.data);
+    let right = retain(root.right.take(), f, rotations);
+    if keep { Some(join(left, root, right, rotations)) } else { join_two(left, right, rotations) }
+}
+
+//Return a new Interval tree built from the successors of root, which are detached from root
+fn combine_successors<D, A: Aggregate<D>>(root: &mut Node<D, A>, rotations: &mut u64) -> Option<Box<Node<D, A>>> {
     match ( root.left.take(), root.right.take() ) {
         ( None,     None)    => None,
         ( Some(l),  None)    => Some(l),
         ( None,     Some(r)) => Some(r),
-        ( Some(l),  Some(r)) => Some(combine_two_subtrees(l,r))
+        ( Some(l),  Some(r)) => Some(combine_two_subtrees(l,r, rotations))
     }
 }
 
+//Return a new Interval tree, where the root has been removed
+fn delete_root<D, A: Aggregate<D>>(mut root: Box<Node<D, A>>, rotations: &mut u64) -> Option<Box<Node<D, A>>> {
+    combine_successors(&mut root, rotations)
+}
 
-// will delete `key` from the tree `root`. Returns either `Some` tree or if the resilting tree is
-// empty: None.
-//
-//
-pub fn delete<D>(key: Range, mut root: Box<Node<D>>) -> Option<Box<Node<D>>>{
-    match root.key.cmp(&key){
-        Ordering::Equal =>  return delete_root(root),
-        Ordering::Less => {
-            if let Some(succ) = root.right.take() {
-                root.right = delete(key, succ);
-                return Some(updated_node(root))
-            }
-        },
-        Ordering::Greater => {
-            if let Some(succ) = root.left.take() {
-                root.left =  delete(key, succ);
-                return Some(updated_node(root))
+
+// walks down to `key` and detaches its node. Returns the path above the node, the combined
+// successors of the node that take its place and the removed (key,data) pair, or the whole tree as
+// path if key is not part of it.
+fn detach<D, A: Aggregate<D>>(key: &Range, root: Box<Node<D, A>>, rotations: &mut u64) -> (Vec<(Box<Node<D, A>>, Ordering)>, Option<Box<Node<D, A>>>, Option<(Range, D)>){
+    let mut path = Vec::with_capacity(root.height as usize);
+    let mut current = Some(root);
+    loop {
+        match current {
+            None => return (path, None, None),
+            Some(mut node) => {
+                let dir = node.key.cmp(key);
+                if dir == Ordering::Equal {
+                    let subtree = combine_successors(&mut node, rotations);
+                    let node = *node;
+                    return (path, subtree, Some((node.key, node.data)))
+                }
+                current = take_successor(&mut node, dir);
+                path.push((node, dir));
             }
         }
     }
-    return Some(root);
 }
 
+// will remove `key` from the tree `root`. Returns the remaining tree (or None if it is empty) and
+// the removed (key,data) pair if key was part of the tree. Like `insert` this walks the tree with
+// an explicit path instead of recursion.
+pub fn remove<D, A: Aggregate<D>>(key: &Range, root: Box<Node<D, A>>, rotations: &mut u64) -> (Option<Box<Node<D, A>>>, Option<(Range, D)>){
+    let (path, subtree, removed) = detach(key, root, rotations);
+    (rebuild_path(path, subtree, rotations), removed)
+}
+
+// will remove `key` from the tree `root` like `remove`, but without rotating the nodes above the
+// removed one. The tree stays a valid interval tree, but may lose its AVL balance.
+pub fn remove_relaxed<D, A: Aggregate<D>>(key: &Range, root: Box<Node<D, A>>, rotations: &mut u64) -> (Option<Box<Node<D, A>>>, Option<(Range, D)>){
+    let (path, subtree, removed) = detach(key, root, rotations);
+    (reattach_path(path, subtree), removed)
+}
+
+// will delete `key` from the tree `root`. Returns either `Some` tree or if the resilting tree is
+// empty: None.
+#[cfg(test)]
+pub fn delete<D, A: Aggregate<D>>(key: Range, root: Box<Node<D, A>>, rotations: &mut u64) -> Option<Box<Node<D, A>>>{
+    remove(&key, root, rotations).0
+}
+
+
+/// Tells a traversal how to go on after visiting a node, see `IntervalTree::visit_preorder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitControl {
+    /// visit the remaining nodes as usual
+    Continue,
+    /// skip the nodes below the current one that have not been visited yet
+    Prune,
+    /// end the traversal
+    Stop,
+}
+
+/// visits root before its subtrees, returns false if the traversal was stopped
+pub fn visit_preorder<D, A: Aggregate<D>, F: FnMut(usize, &Range, &D) -> VisitControl>(root: &Option<Box<Node<D, A>>>, depth: usize, f: &mut F) -> bool {
+    let node = match *root { Some(ref node) => node, None => return true };
+    match f(depth, &node.key, &node.data) {
+        VisitControl::Continue => visit_preorder(&node.left, depth + 1, f) && visit_preorder(&node.right, depth + 1, f),
+        VisitControl::Prune => true,
+        VisitControl::Stop => false
+    }
+}
+
+/// visits root between its subtrees, returns false if the traversal was stopped
+pub fn visit_inorder<D, A: Aggregate<D>, F: FnMut(usize, &Range, &D) -> VisitControl>(root: &Option<Box<Node<D, A>>>, depth: usize, f: &mut F) -> bool {
+    let node = match *root { Some(ref node) => node, None => return true };
+    if !visit_inorder(&node.left, depth + 1, f) { return false }
+    match f(depth, &node.key, &node.data) {
+        VisitControl::Continue => visit_inorder(&node.right, depth + 1, f),
+        VisitControl::Prune => true,
+        VisitControl::Stop => false
+    }
+}
+
+/// visits root after its subtrees, returns false if the traversal was stopped
+pub fn visit_postorder<D, A: Aggregate<D>, F: FnMut(usize, &Range, &D) -> VisitControl>(root: &Option<Box<Node<D, A>>>, depth: usize, f: &mut F) -> bool {
+    let node = match *root { Some(ref node) => node, None => return true };
+    if !visit_postorder(&node.left, depth + 1, f) || !visit_postorder(&node.right, depth + 1, f) { return false }
+    f(depth, &node.key, &node.data) != VisitControl::Stop
+}
+
+/// A property of a tree that can be violated, see `check_invariants`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invariant {
+    /// the keys are not in strictly ascending order
+    Ordering,
+    /// the heights of the subtrees of a node differ by more than one
+    Balance,
+    /// the cached height of a node is wrong
+    Height,
+    /// the cached maximal end of the keys of a subtree is wrong
+    MaxEnd,
+    /// one of the other cached values of a node (subtree size, smallest start and end, biggest
+    /// start or biggest gap) is wrong
+    Cache,
+    /// the cached aggregate of a subtree is wrong
+    Aggregate,
+}
+
+//Visits all nodes in ascending order and returns the first violated invariant together with the key
+//of the node where it was found. The balance is only checked if balanced is set.
+pub fn check_invariants<D, A: Aggregate<D>>(root: &Option<Box<Node<D, A>>>, balanced: bool) -> Result<(), (Invariant, Range)> {
+    let mut stack: Vec<&Box<Node<D, A>>> = Vec::new();
+    let mut current = root.as_ref();
+    let mut last: Option<Range> = None;
+    loop {
+        while let Some(node) = current {
+            stack.push(node);
+            current = node.left.as_ref();
+        }
+        let node = match stack.pop() { Some(node) => node, None => return Ok(()) };
+        let fail = |invariant| Err((invariant, node.key));
+        if last.map_or(false, |last| last >= node.key) { return fail(Invariant::Ordering) }
+        if node.height != cmp::max(height(&node.left), height(&node.right)) + 1 { return fail(Invariant::Height) }
+        if balanced && diff_of_successors_height(node).abs() > 1 { return fail(Invariant::Balance) }
+        if node.max != cmp::max(subtree_max(&node.left), cmp::max(subtree_max(&node.right), node.key.max)) { return fail(Invariant::MaxEnd) }
+        let proper_cache = node.min_start == node.left.as_ref().map_or(node.key.min, |succ| succ.min_start) &&
+            node.max_gap == max_gap(node) &&
+            node.size == subtree_size(&node.left) + subtree_size(&node.right) + 1 &&
+            node.min_end == cmp::min(subtree_min_end(&node.left), cmp::min(subtree_min_end(&node.right), node.key.max)) &&
+            node.max_start == node.right.as_ref().map_or(node.key.min, |succ| succ.max_start);
+        if !proper_cache { return fail(Invariant::Cache) }
+        let own = A::lift(&node.key, &node.data);
+        let with_left = match node.left { Some(ref succ) => A::combine(&succ.agg, &own), None => own };
+        let agg = match node.right { Some(ref succ) => A::combine(&with_left, &succ.agg), None => with_left };
+        if node.agg != agg { return fail(Invariant::Aggregate) }
+        last = Some(node.key);
+        current = node.right.as_ref();
+    }
+}
 
 #[cfg(test)]
 fn simple_tree(size: i32) -> Box<Node<i32>> {
-    let mut t = Box::new(Node::<i32>{key: Range::new(1,1), data: 1337, height: 0, max: 1, left:None, right: None});
+    let mut t = Box::new(Node::<i32, NoAggregate>{key: Range::new(1,1), data: 1337, height: 0, max: 1, min_start: 1, max_gap: 0, size: 1, min_end: 1, max_start: 1, agg: (), left:None, right: None});
     for x in 2..size+1 {
-        t = insert(Range::new(x as u64, x as u64 ),1337+x-1,t)
+        t = insert(Range::new(x as u64, x as u64 ),1337+x-1,t, &mut 0)
     }
     t
 }
 #[cfg(test)]
-fn is_sorted_left<D>(node: &Box<Node<D>>) -> bool {
+fn is_sorted_left<D, A: Aggregate<D>>(node: &Box<Node<D, A>>) -> bool {
     node.left.as_ref().map_or(true, |succ| succ.key < node.key)
 }
 #[cfg(test)]
-fn is_sorted_right<D>(node: &Box<Node<D>>) -> bool {
+fn is_sorted_right<D, A: Aggregate<D>>(node: &Box<Node<D, A>>) -> bool {
     node.right.as_ref().map_or(true, |succ| succ.key > node.key)
 }
 #[cfg(test)]
-fn is_interval_node<D>(node: &Box<Node<D>>) -> bool {
+fn is_interval_node<D, A: Aggregate<D>>(node: &Box<Node<D, A>>) -> bool {
     let sorted = is_sorted_left(node) && is_sorted_right(node);
     let balanced = node.height == cmp::max(height(&node.left),height(&node.right))+1;
     let proper_max = node.max == cmp::max(subtree_max(&node.left), cmp::max(subtree_max(&node.right), node.key.max));
-    return sorted && balanced && proper_max;
+    let proper_gap = node.min_start == node.left.as_ref().map_or(node.key.min, |succ| succ.min_start) &&
+        node.max_gap == max_gap(node);
+    let proper_count = node.size == subtree_size(&node.left) + subtree_size(&node.right) + 1 &&
+        node.min_end == cmp::min(subtree_min_end(&node.left), cmp::min(subtree_min_end(&node.right), node.key.max)) &&
+        node.max_start == node.right.as_ref().map_or(node.key.min, |succ| succ.max_start);
+    return sorted && balanced && proper_max && proper_gap && proper_count;
 }
 
 #[cfg(test)]
-pub fn is_interval_tree<D>(root: &Option<Box<Node<D>>>) -> bool {
-    (*root).as_ref().map_or(true, is_interval_node)
+pub fn is_interval_tree<D, A: Aggregate<D>>(root: &Option<Box<Node<D, A>>>) -> bool {
+    (*root).as_ref().map_or(true, |node| {
+        is_interval_node(node) && diff_of_successors_height(node).abs() <= 1 &&
+            is_interval_tree(&node.left) && is_interval_tree(&node.right)
+    })
 }
 
 #[test]
@@ -295,29 +972,34 @@ fn range_impl() {
 
 #[test]
 fn simple_tree_operations() {
-    let mut t = Box::new(Node::<i32>{key: Range::new(3,3), data: 4, max:3, height: 2,
-        left: Some(Box::new(Node::<i32>{key: Range::new(2,2), data: 5, height:1, max: 2, left: None, right: None})), 
+    let mut t = Box::new(Node::<i32, NoAggregate>{key: Range::new(3,3), data: 4, max:3, height: 2, min_start: 2, max_gap: 0, size: 2, min_end: 2, max_start: 3, agg: (),
+        left: Some(Box::new(Node::<i32, NoAggregate>{key: Range::new(2,2), data: 5, height:1, max: 2, min_start: 2, max_gap: 0, size: 1, min_end: 2, max_start: 2, agg: (), left: None, right: None})), 
         right: None});
     assert!(is_interval_node(&t));
-    assert!( contains::<i32>(&Range::new(3,3),&t) );
-    assert!( contains::<i32>(&Range::new(2,2),&t) );
-    assert!( !contains::<i32>(&Range::new(6,6),&t) );
-    assert!( !contains::<i32>(&Range::new(4,4),&t) );
-    t = insert::<i32>(Range::new(4,4),7, t);
-    t = insert::<i32>(Range::new(5,5),7, t);
-    t = insert::<i32>(Range::new(6,6),8, t);
-    assert!(  contains::<i32>(&Range::new(4,4),&t) );
-    assert!(  contains::<i32>(&Range::new(6,6),&t) );
-    assert!( !contains::<i32>(&Range::new(7,7),&t) );
+    assert!( contains(&Range::new(3,3),&t) );
+    assert!( contains(&Range::new(2,2),&t) );
+    assert!( !contains(&Range::new(6,6),&t) );
+    assert!( !contains(&Range::new(4,4),&t) );
+    t = insert(Range::new(4,4),7, t, &mut 0);
+    t = insert(Range::new(5,5),7, t, &mut 0);
+    t = insert(Range::new(6,6),8, t, &mut 0);
+    assert!(  contains(&Range::new(4,4),&t) );
+    assert!(  contains(&Range::new(6,6),&t) );
+    assert!( !contains(&Range::new(7,7),&t) );
 }
 
 #[test]
 fn rotations_on_tree(){ 
-    let mut t = Box::new(Node::<i32>{key: Range::new(1,1), data: 1337, height: 1, max: 1, left: None, right: None});
+    let mut t = Box::new(Node::<i32, NoAggregate>{key: Range::new(1,1), data: 1337, height: 1, max: 1, min_start: 1, max_gap: 0, size: 1, min_end: 1, max_start: 1, agg: (), left: None, right: None});
+    let mut rotations = 0;
     for i in 2..255 {
-        t = insert::<i32>(Range::new(i,i),1337, t);
+        t = insert(Range::new(i,i),1337, t, &mut rotations);
         assert!(is_interval_node(&t));
+        if i == 3 { assert_eq!(rotations, 1) }
     }
+    let before = rotations;
+    t = insert(Range::new(7,7),0, t, &mut rotations);
+    assert_eq!(rotations, before);
     //check that the tree is indeed balanced
     assert!(height(&Some(t)) <= 8);
 }
@@ -325,23 +1007,23 @@ fn rotations_on_tree(){
 #[test]
 fn test_drop_min(){
     let mut t = simple_tree(3);
-    let (maybe_tree,min) = drop_min(t);
+    let (maybe_tree,min) = drop_min(t, &mut 0);
     t = maybe_tree.expect("failure to get tree for first min delete");
     assert!(is_interval_node(&t));
     assert!( min.key == Range::new(1,1));
-    assert!(!contains::<i32>(&Range::new(1,1),&t));
-    assert!( contains::<i32>(&Range::new(2,2),&t));
-    assert!( contains::<i32>(&Range::new(3,3),&t));
+    assert!(!contains(&Range::new(1,1),&t));
+    assert!( contains(&Range::new(2,2),&t));
+    assert!( contains(&Range::new(3,3),&t));
 
-    let (maybe_tree,min) = drop_min(t);
+    let (maybe_tree,min) = drop_min(t, &mut 0);
     t = maybe_tree.expect("failure to get tree for second min delete");
     assert!(is_interval_node(&t));
     assert!( min.key == Range::new(2,2));
-    assert!(!contains::<i32>(&Range::new(1,1),&t));
-    assert!(!contains::<i32>(&Range::new(2,2),&t));
-    assert!( contains::<i32>(&Range::new(3,3),&t));
+    assert!(!contains(&Range::new(1,1),&t));
+    assert!(!contains(&Range::new(2,2),&t));
+    assert!( contains(&Range::new(3,3),&t));
 
-    let (maybe_tree,min) = drop_min(t);
+    let (maybe_tree,min) = drop_min(t, &mut 0);
     assert!( maybe_tree.is_none() );
     assert!( min.key == Range::new(3,3));
 }
@@ -349,22 +1031,22 @@ fn test_drop_min(){
 #[test]
 fn test_drop_root(){
     let mut t = simple_tree(3);
-    let maybe_tree = delete_root(t);
+    let maybe_tree = delete_root(t, &mut 0);
     t = maybe_tree.expect("failure to get tree for first root drop");
     assert!(is_interval_node(&t));
     assert!( t.height == 2);
-    assert!( contains::<i32>(&Range::new(1,1),&t));
-    assert!(!contains::<i32>(&Range::new(2,2),&t));
-    assert!( contains::<i32>(&Range::new(3,3),&t));
+    assert!( contains(&Range::new(1,1),&t));
+    assert!(!contains(&Range::new(2,2),&t));
+    assert!( contains(&Range::new(3,3),&t));
 
-    let maybe_tree = delete_root(t);
+    let maybe_tree = delete_root(t, &mut 0);
     t = maybe_tree.expect("failure to get tree for second root drop");
     assert!(is_interval_node(&t));
-    assert!( contains::<i32>(&Range::new(1,1),&t));
-    assert!(!contains::<i32>(&Range::new(2,2),&t));
-    assert!(!contains::<i32>(&Range::new(3,3),&t));
+    assert!( contains(&Range::new(1,1),&t));
+    assert!(!contains(&Range::new(2,2),&t));
+    assert!(!contains(&Range::new(3,3),&t));
 
-    let maybe_tree = delete_root(t);
+    let maybe_tree = delete_root(t, &mut 0);
     assert!( maybe_tree.is_none() );
 }
 
@@ -372,14 +1054,14 @@ fn test_drop_root(){
 fn test_delete(){
     let mut t = simple_tree(10);
     for i in 1..10 {
-        assert!(contains::<i32>(&Range::new(i,i),&t));
-        let maybe_tree = delete(Range::new(i,i),t);
+        assert!(contains(&Range::new(i,i),&t));
+        let maybe_tree = delete(Range::new(i,i),t, &mut 0);
         t = maybe_tree.expect("failure to get tree for delete");
-        assert!(!contains::<i32>(&Range::new(i,i),&t));
+        assert!(!contains(&Range::new(i,i),&t));
         assert!(is_interval_node(&t));
     }
-    assert!(contains::<i32>(&Range::new(10,10),&t));
-    let maybe_tree = delete(Range::new(10,10),t);
+    assert!(contains(&Range::new(10,10),&t));
+    let maybe_tree = delete(Range::new(10,10),t, &mut 0);
     assert!(maybe_tree.is_none());
 }
 
@@ -403,3 +1085,133 @@ fn test_min_after(){
         }
     }
 }
+
+#[test]
+fn test_from_sorted(){
+    assert!(from_sorted::<i32, NoAggregate, _>(0, &mut Vec::new().into_iter()).is_none());
+    for size in 1..70 {
+        let t: Option<Box<Node<u64>>> = from_sorted(size, &mut (0..size as u64).map(|i| (Range::new(i,i+3), i)));
+        assert!(is_interval_tree(&t));
+        assert!(height(&t) as f64 <= (size as f64 + 1.0).log2().ceil());
+        let t = t.expect("non empty tree");
+        for i in 0..size as u64 { assert_eq!(search(&Range::new(i,i+3), &t), Some(&i)) }
+    }
+}
+
+#[test]
+fn test_format_tree(){
+    let t = simple_tree(3);
+    let mut out = String::new();
+    format_tree(&t, 0, &mut out);
+    assert_eq!(out, "    [3, 3] max=3 h=1\n[2, 2] max=3 h=2\n    [1, 1] max=1 h=1\n");
+}
+
+#[test]
+fn test_join(){
+    for lsize in 0..20 {
+        for rsize in 0..40 {
+            let l: Option<Box<Node<i32>>> = from_sorted(lsize, &mut (0..lsize as u64).map(|i| (Range::new(i,i), 0)));
+            let r = from_sorted(rsize, &mut (0..rsize as u64).map(|i| (Range::new(100+i,100+i), 0)));
+            let t = Some(join(l, Box::new(Node::new(Range::new(50,50), 0)), r, &mut 0));
+            assert!(is_interval_tree(&t));
+            let t = t.expect("joined tree");
+            assert!(contains(&Range::new(50,50), &t));
+            for i in 0..rsize as u64 { assert!(contains(&Range::new(100+i,100+i), &t)) }
+        }
+    }
+}
+
+#[test]
+fn test_retain(){
+    let t = retain(Some(simple_tree(100)), &mut |k: &Range, _: &mut i32| k.min % 3 == 0, &mut 0);
+    assert!(is_interval_tree(&t));
+    let t = t.expect("some keys remain");
+    for i in 1..101 { assert_eq!(contains(&Range::new(i,i), &t), i % 3 == 0) }
+}
+
+#[test]
+fn test_split(){
+    for at in 0..60 {
+        let (before, after) = split(Some(simple_tree(50)), at, &mut 0);
+        assert!(is_interval_tree(&before));
+        assert!(is_interval_tree(&after));
+        for i in 1..51 {
+            assert_eq!(before.as_ref().map_or(false, |t| contains(&Range::new(i,i), t)), i < at);
+            assert_eq!(after.as_ref().map_or(false, |t| contains(&Range::new(i,i), t)), i >= at);
+        }
+    }
+}
+
+#[test]
+fn test_max_gap(){
+    let mut t: Box<Node<i32>> = Box::new(Node::new(Range::new(10,20), 0));
+    t = insert(Range::new(0,3), 0, t, &mut 0);
+    t = insert(Range::new(30,31), 0, t, &mut 0);
+    assert!(is_interval_node(&t));
+    assert_eq!(t.min_start, 0);
+    assert_eq!(t.max_gap, 9);
+    t = insert(Range::new(5,29), 0, t, &mut 0);
+    assert_eq!(t.max_gap, 1);
+    let mut free = Some(0);
+    assert_eq!(find_gap(&t, 1, &mut free), Some(4));
+    let mut free = Some(0);
+    assert_eq!(find_gap(&t, 2, &mut free), None);
+    assert_eq!(free, Some(32));
+}
+
+#[cfg(test)]
+fn is_relaxed_tree<D, A: Aggregate<D>>(root: &Option<Box<Node<D, A>>>) -> bool {
+    (*root).as_ref().map_or(true, |node| {
+        is_interval_node(node) && is_relaxed_tree(&node.left) && is_relaxed_tree(&node.right)
+    })
+}
+
+#[test]
+fn test_remove_relaxed(){
+    let mut t = Some(simple_tree(200));
+    for i in 1..101 {
+        let (root, removed) = remove_relaxed(&Range::new(i,i), t.take().expect("keys remain"), &mut 0);
+        assert_eq!(removed.map(|(k,_)| k), Some(Range::new(i,i)));
+        assert!(is_relaxed_tree(&root));
+        t = root;
+    }
+    assert!(!is_interval_tree(&t));
+    for i in 1..101 {
+        t = Some(insert(Range::new(i,i), 0, t.take().expect("keys remain"), &mut 0));
+        assert!(is_relaxed_tree(&t));
+    }
+    for i in 1..201 { assert!(contains(&Range::new(i,i), t.as_ref().expect("keys remain"))) }
+}
+
+#[test]
+fn test_check_invariants(){
+    let mut t = simple_tree(100);
+    assert_eq!(check_invariants(&Some(t), true), Ok(()));
+    t = simple_tree(100);
+    let key = Range::new(t.key.min-1, t.key.max-1);
+    t.key = key;
+    assert_eq!(check_invariants(&Some(t), true), Err((Invariant::Ordering, key)));
+    t = simple_tree(100);
+    t.max = 7;
+    let key = t.key;
+    assert_eq!(check_invariants(&Some(t), true), Err((Invariant::MaxEnd, key)));
+    t = simple_tree(100);
+    t.size = 3;
+    let key = t.key;
+    assert_eq!(check_invariants(&Some(t), true), Err((Invariant::Cache, key)));
+    let mut sums = Box::new(Node::<u64, ::aggregate::Sum>::new(Range::new(0, 0), 0));
+    for i in 1..100 { sums = insert(Range::new(i, i), i, sums, &mut 0) }
+    let mut sums = Some(sums);
+    assert_eq!(check_invariants(&sums, true), Ok(()));
+    sums.as_mut().unwrap().left.as_mut().unwrap().data = 0;
+    let key = sums.as_ref().unwrap().left.as_ref().unwrap().key;
+    assert_eq!(check_invariants(&sums, true), Err((Invariant::Aggregate, key)));
+    t = simple_tree(100);
+    t.height = 42;
+    let key = t.key;
+    assert_eq!(check_invariants(&Some(t), true), Err((Invariant::Height, key)));
+    t = simple_tree(100);
+    t.left = None;
+    update_height(&mut t);
+    assert_eq!(check_invariants(&Some(t), true), Err((Invariant::Balance, key)));
+}
@@ -0,0 +1,54 @@
+extern crate memrange;
+extern crate time03;
+
+use self::memrange::Range;
+use self::time03::OffsetDateTime;
+use bounds::IntoRange;
+use time_keys::time_bounds;
+use std::cmp::Ordering;
+use std::ops;
+
+fn checked_offset_datetime_key(t: &OffsetDateTime) -> Result<u64, Ordering> {
+    let nanos = t.unix_timestamp_nanos();
+    if nanos < 0 { return Err(Ordering::Less) }
+    if nanos > 0xffff_ffff_ffff_ffff { return Err(Ordering::Greater) }
+    Ok(nanos as u64)
+}
+
+/// This function will return the nanoseconds between the unix epoch and t, or None if t lies
+/// before the epoch or too far after it. Ranges of `OffsetDateTime`s with any offset can be used
+/// as keys directly, like ranges of `SystemTime`, see `time_keys`. Only available with the `time`
+/// feature.
+/// # Examples
+/// ```
+/// extern crate interval_tree;
+/// use interval_tree::time_keys::{offset_datetime_key, key_offset_datetime};
+///
+/// let start = key_offset_datetime(1_700_000_000_000_000_000);
+/// let end = key_offset_datetime(1_700_003_600_000_000_000);
+/// let mut t=interval_tree::IntervalTree::<&str>::new();
+/// t.insert(start..end, "shift");
+/// let (key, _) = t.range(start..=start).next().unwrap();
+/// assert_eq!(key.max, offset_datetime_key(&end).unwrap() - 1);
+/// assert_eq!(key_offset_datetime(key.min), start);
+/// assert_eq!(start.unix_timestamp(), 1_700_000_000);
+/// ```
+pub fn offset_datetime_key(t: &OffsetDateTime) -> Option<u64> {
+    checked_offset_datetime_key(t).ok()
+}
+
+/// This function will return the point in time key nanoseconds after the unix epoch, with UTC as
+/// offset. Only available with the `time` feature.
+pub fn key_offset_datetime(key: u64) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp_nanos(key as i128).expect("all keys lie before the year 9999")
+}
+
+macro_rules! impl_into_range_for_offset_datetime_bounds {
+    ($($t:ident),*) => {$(
+        impl IntoRange for ops::$t<OffsetDateTime> {
+            fn into_range(self) -> Option<Range> { time_bounds(&self, checked_offset_datetime_key) }
+        }
+    )*}
+}
+
+impl_into_range_for_offset_datetime_bounds!(Range, RangeInclusive, RangeFrom, RangeTo, RangeToInclusive);
@@ -0,0 +1,159 @@
+extern crate memrange;
+extern crate rayon;
+
+use self::memrange::Range;
+use self::rayon::iter::ParallelIterator;
+use self::rayon::slice::ParallelSliceMut;
+use self::rayon::iter::plumbing::{UnindexedConsumer, UnindexedProducer, Folder, bridge_unindexed};
+use node::{Node, from_sorted, join};
+use tree::IntervalTree;
+use bounds::IntoRange;
+use aggregate::Aggregate;
+
+/// number of pairs up to which `par_from_vec` builds a subtree on a single thread
+const SEQUENTIAL_BUILD: usize = 4096;
+
+/// A parallel iterator over the key,value pairs of an `IntervalTree` whose keys intersect a
+/// range. The work is split at subtree boundaries, so every worker walks a disjoint subtree;
+/// collecting the pairs keeps the ascending order of the keys.
+pub struct ParRangeIter<'a, D: 'a, A: 'a + Aggregate<D>> {
+    root: Option<&'a Node<D, A>>,
+    range: Range,
+}
+
+/// The pairs of the subtree of node which intersect range, preceded by the pair of prefix if that
+/// intersects range. The key of prefix is smaller than all keys in the subtree.
+struct SubtreeProducer<'a, D: 'a, A: 'a + Aggregate<D>> {
+    prefix: Option<&'a Node<D, A>>,
+    node: Option<&'a Node<D, A>>,
+    range: Range,
+}
+
+impl<'a, D: 'a, A: 'a + Aggregate<D>> SubtreeProducer<'a, D, A> {
+    /// returns the subtree of node if it can hold keys intersecting the range
+    fn relevant(&self, node: &'a Option<Box<Node<D, A>>>) -> Option<&'a Node<D, A>> {
+        node.as_ref().map(|n| &**n).filter(|n| n.left_subtree_relevant(&self.range))
+    }
+}
+
+impl<'a, D: 'a + Sync, A: 'a + Aggregate<D>> UnindexedProducer for SubtreeProducer<'a, D, A> where A::Value: Sync {
+
+    type Item = (&'a Range, &'a D);
+
+    fn split(self) -> (Self, Option<Self>) {
+        let node = match self.node { Some(node) => node, None => return (self, None) };
+        let left = SubtreeProducer{prefix: self.prefix, node: self.relevant(&node.left), range: self.range};
+        let right = if node.right_subtree_relevant(&self.range) { self.relevant(&node.right) } else { None };
+        let right = SubtreeProducer{prefix: Some(node), node: right, range: self.range};
+        (left, Some(right))
+    }
+
+    fn fold_with<F>(self, mut folder: F) -> F where F: Folder<Self::Item> {
+        if let Some(prefix) = self.prefix {
+            if prefix.key.intersect(&self.range) { folder = folder.consume((&prefix.key, &prefix.data)) }
+        }
+        let mut stack = Vec::new();
+        let mut current = self.node;
+        loop {
+            while let Some(node) = current {
+                stack.push(node);
+                current = self.relevant(&node.left);
+            }
+            let node = match stack.pop() { Some(node) => node, None => return folder };
+            if !node.right_subtree_relevant(&self.range) || folder.full() { return folder }
+            if node.key.intersect(&self.range) { folder = folder.consume((&node.key, &node.data)) }
+            current = self.relevant(&node.right);
+        }
+    }
+}
+
+impl<'a, D: 'a + Sync, A: 'a + Aggregate<D>> ParallelIterator for ParRangeIter<'a, D, A> where A::Value: Sync {
+
+    type Item = (&'a Range, &'a D);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result where C: UnindexedConsumer<Self::Item> {
+        let range = self.range;
+        let node = self.root.filter(|n| n.left_subtree_relevant(&range));
+        bridge_unindexed(SubtreeProducer{prefix: None, node: node, range: range}, consumer)
+    }
+}
+
+impl<D: Sync, A: Aggregate<D>> IntervalTree<D, A> where A::Value: Sync {
+
+/// This function will return a parallel iterator over all key,value pairs, see `par_range`.
+/// Only available with the `rayon` feature.
+    pub fn par_iter(&self) -> ParRangeIter<D, A> {
+        self.par_range(..)
+    }
+
+/// This function will return a parallel iterator over all key,value pairs whose keys intersect the
+/// bounds. Only available with the `rayon` feature.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// extern crate rayon;
+/// use rayon::prelude::*;
+///
+/// let mut t=interval_tree::IntervalTree::<u64>::new();
+/// for i in 0..1000 { t.insert(memrange::Range::new(i*10,i*10+15), i) }
+/// assert_eq!(t.par_range(95..=105).collect::<Vec<_>>(), t.range(95..=105).collect::<Vec<_>>());
+/// assert_eq!(t.par_iter().count(), 1000);
+/// ```
+    pub fn par_range<K: IntoRange>(&self, bounds: K) -> ParRangeIter<D, A> {
+        let range = bounds.into_range().and_then(|range| self.clip_query(range.min, range.max));
+        match range {
+            Some(range) => ParRangeIter{root: self.root.as_ref().map(|n| &**n), range: range},
+            None => ParRangeIter{root: None, range: Range::new(0,0)}
+        }
+    }
+}
+
+/// builds a perfectly balanced tree from the sorted, unique pairs, which are taken out of the
+/// slice. Both halves are built in parallel and joined below the middle pair, which yields the
+/// same shape as `from_sorted`.
+fn build<D: Send>(pairs: &mut [Option<(Range, D)>]) -> Option<Box<Node<D>>> {
+    let len = pairs.len();
+    if len <= SEQUENTIAL_BUILD {
+        return from_sorted(len, &mut pairs.iter_mut().map(|pair| pair.take().expect("pair taken twice")))
+    }
+    let (left, rest) = pairs.split_at_mut(len/2);
+    let (mid, right) = rest.split_first_mut().expect("right half is not empty");
+    let (key, data) = mid.take().expect("pair taken twice");
+    let (left, right) = rayon::join(|| build(left), || build(right));
+    //the halves differ in height by at most one, so the join never rotates
+    Some(join(left, Box::new(Node::new(key, data)), right, &mut 0))
+}
+
+impl<D: Send> IntervalTree<D> {
+
+/// This function will construct a perfectly balanced IntervalTree from pairs in arbitrary order
+/// like `from_vec`, but sorts the pairs and builds the subtrees on all threads of the rayon pool.
+/// If a key occurs several times, the pair that comes last in pairs is kept. Only available with
+/// the `rayon` feature.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let pairs = (0..100000u64).rev().map(|i| (Range::new(i*10,i*10+15), i)).collect::<Vec<_>>();
+/// let t=interval_tree::IntervalTree::par_from_vec(pairs);
+/// assert_eq!(t.height(), 17);
+/// assert_eq!(t.get(Range::new(50,65)), Some(&5));
+/// ```
+    pub fn par_from_vec(mut pairs: Vec<(Range, D)>) -> IntervalTree<D> {
+        pairs.par_sort_by(|a, b| a.0.cmp(&b.0));
+        let mut unique: Vec<Option<(Range, D)>> = Vec::with_capacity(pairs.len());
+        for (key, data) in pairs {
+            if key.min > key.max { continue }
+            if unique.last().and_then(|last| last.as_ref()).map_or(false, |&(ref last, _)| *last == key) {
+                unique.pop();
+            }
+            unique.push(Some((key, data)));
+        }
+        let mut tree = IntervalTree::new();
+        tree.root = build(&mut unique);
+        tree
+    }
+}
@@ -0,0 +1,520 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use bounds::IntoRange;
+use std::cmp;
+use std::cmp::Ordering;
+use std::mem;
+use std::sync::Arc;
+
+/// An immutable node of a `PersistentIntervalTree`, shared between all versions that contain it.
+#[derive(Debug, Clone)]
+struct PersistentNode<D> {
+    key: Range,
+    data: D,
+    height: u32,
+    max: u64,
+    left: Link<D>,
+    right: Link<D>,
+}
+
+type Link<D> = Option<Arc<PersistentNode<D>>>;
+
+fn height<D>(link: &Link<D>) -> u32 {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+fn max<D>(link: &Link<D>) -> u64 {
+    link.as_ref().map_or(0, |node| node.max)
+}
+
+fn node<D>(key: Range, data: D, left: Link<D>, right: Link<D>) -> Arc<PersistentNode<D>> {
+    let height = cmp::max(height(&left), height(&right)) + 1;
+    let max = cmp::max(key.max, cmp::max(max(&left), max(&right)));
+    Arc::new(PersistentNode{key: key, data: data, height: height, max: max, left: left, right: right})
+}
+
+/// Builds a new node from key, data and both successors, applying the rotations necessary to keep
+/// the AVL balance. Nodes that are rotated are copied, all other subtrees are shared.
+fn balanced<D: Clone>(key: Range, data: D, left: Link<D>, right: Link<D>) -> Arc<PersistentNode<D>> {
+    let (hl, hr) = (height(&left), height(&right));
+    if hl > hr + 1 {
+        let l = left.expect("Avl broken");
+        if height(&l.left) >= height(&l.right) {
+            return node(l.key, l.data.clone(), l.left.clone(), Some(node(key, data, l.right.clone(), right)))
+        }
+        let lr = l.right.as_ref().expect("Avl broken");
+        return node(lr.key, lr.data.clone(), Some(node(l.key, l.data.clone(), l.left.clone(), lr.left.clone())),
+                    Some(node(key, data, lr.right.clone(), right)))
+    }
+    if hr > hl + 1 {
+        let r = right.expect("Avl broken");
+        if height(&r.right) >= height(&r.left) {
+            return node(r.key, r.data.clone(), Some(node(key, data, left, r.left.clone())), r.right.clone())
+        }
+        let rl = r.left.as_ref().expect("Avl broken");
+        return node(rl.key, rl.data.clone(), Some(node(key, data, left, rl.left.clone())),
+                    Some(node(r.key, r.data.clone(), rl.right.clone(), r.right.clone())))
+    }
+    node(key, data, left, right)
+}
+
+/// returns the new subtree with key inserted and (a clone of) the data it replaced
+fn insert<D: Clone>(link: &Link<D>, key: Range, data: D) -> (Arc<PersistentNode<D>>, Option<D>) {
+    let n = match *link { Some(ref n) => n, None => return (node(key, data, None, None), None) };
+    match n.key.cmp(&key) {
+        Ordering::Equal => (node(key, data, n.left.clone(), n.right.clone()), Some(n.data.clone())),
+        Ordering::Less => {
+            let (right, replaced) = insert(&n.right, key, data);
+            (balanced(n.key, n.data.clone(), n.left.clone(), Some(right)), replaced)
+        },
+        Ordering::Greater => {
+            let (left, replaced) = insert(&n.left, key, data);
+            (balanced(n.key, n.data.clone(), Some(left), n.right.clone()), replaced)
+        }
+    }
+}
+
+/// returns the subtree without its minimal node and the pair of that node
+fn remove_min<D: Clone>(n: &Arc<PersistentNode<D>>) -> (Link<D>, (Range, D)) {
+    match n.left {
+        None => (n.right.clone(), (n.key, n.data.clone())),
+        Some(ref left) => {
+            let (left, min) = remove_min(left);
+            (Some(balanced(n.key, n.data.clone(), left, n.right.clone())), min)
+        }
+    }
+}
+
+/// returns the subtree without key and the removed data, or None if key is not part of it
+fn remove<D: Clone>(link: &Link<D>, key: &Range) -> Option<(Link<D>, D)> {
+    let n = match *link { Some(ref n) => n, None => return None };
+    match n.key.cmp(key) {
+        Ordering::Less => remove(&n.right, key).map(|(right, data)| (Some(balanced(n.key, n.data.clone(), n.left.clone(), right)), data)),
+        Ordering::Greater => remove(&n.left, key).map(|(left, data)| (Some(balanced(n.key, n.data.clone(), left, n.right.clone())), data)),
+        Ordering::Equal => {
+            let subtree = match (&n.left, &n.right) {
+                (&None, right) => right.clone(),
+                (left, &None) => left.clone(),
+                (left, &Some(ref right)) => {
+                    let (right, (min, data)) = remove_min(right);
+                    Some(balanced(min, data, left.clone(), right))
+                }
+            };
+            Some((subtree, n.data.clone()))
+        }
+    }
+}
+
+/// updates the cached height and max of n from its successors
+fn update<D>(n: &mut PersistentNode<D>) {
+    n.height = cmp::max(height(&n.left), height(&n.right)) + 1;
+    n.max = cmp::max(n.key.max, cmp::max(max(&n.left), max(&n.right)));
+}
+
+/// returns the node of link for modification, which is copied first if it is shared
+fn unique<D: Clone>(link: &mut Link<D>) -> &mut PersistentNode<D> {
+    Arc::make_mut(link.as_mut().expect("node to modify"))
+}
+
+/// takes the node out of link, copying it if it is shared
+fn take<D: Clone>(link: &mut Link<D>) -> PersistentNode<D> {
+    let n = link.take().expect("node to take");
+    Arc::try_unwrap(n).unwrap_or_else(|shared| (*shared).clone())
+}
+
+fn rotate_right<D: Clone>(link: &mut Link<D>) {
+    let mut l = unique(link).left.take();
+    unique(link).left = unique(&mut l).right.take();
+    update(unique(link));
+    unique(&mut l).right = link.take();
+    update(unique(&mut l));
+    *link = l;
+}
+
+fn rotate_left<D: Clone>(link: &mut Link<D>) {
+    let mut r = unique(link).right.take();
+    unique(link).right = unique(&mut r).left.take();
+    update(unique(link));
+    unique(&mut r).left = link.take();
+    update(unique(&mut r));
+    *link = r;
+}
+
+/// restores the cached values and the AVL balance of the unique node of link, whose subtrees
+/// differ in height by at most two
+fn rebalance<D: Clone>(link: &mut Link<D>) {
+    let (hl, hr) = {
+        let n = unique(link);
+        (height(&n.left), height(&n.right))
+    };
+    if hl > hr + 1 {
+        let n = unique(link);
+        if n.left.as_ref().map_or(false, |l| height(&l.left) < height(&l.right)) { rotate_left(&mut n.left) }
+        rotate_right(link)
+    } else if hr > hl + 1 {
+        let n = unique(link);
+        if n.right.as_ref().map_or(false, |r| height(&r.right) < height(&r.left)) { rotate_right(&mut n.right) }
+        rotate_left(link)
+    } else {
+        update(unique(link))
+    }
+}
+
+/// inserts into the subtree of link, changing nodes only this subtree refers to in place and
+/// copying the shared ones, and returns the data key had before
+fn insert_mut<D: Clone>(link: &mut Link<D>, key: Range, data: D) -> Option<D> {
+    if link.is_none() {
+        *link = Some(node(key, data, None, None));
+        return None
+    }
+    let replaced = {
+        let n = unique(link);
+        match n.key.cmp(&key) {
+            Ordering::Equal => return Some(mem::replace(&mut n.data, data)),
+            Ordering::Less => insert_mut(&mut n.right, key, data),
+            Ordering::Greater => insert_mut(&mut n.left, key, data)
+        }
+    };
+    rebalance(link);
+    replaced
+}
+
+/// removes the minimal node of the subtree of link like `insert_mut` and returns its pair
+fn remove_min_mut<D: Clone>(link: &mut Link<D>) -> (Range, D) {
+    if unique(link).left.is_some() {
+        let min = remove_min_mut(&mut unique(link).left);
+        rebalance(link);
+        return min
+    }
+    let n = take(link);
+    *link = n.right;
+    (n.key, n.data)
+}
+
+/// removes key, which has to be part of the subtree of link, like `insert_mut` and returns its data
+fn remove_mut<D: Clone>(link: &mut Link<D>, key: &Range) -> D {
+    let order = link.as_ref().expect("key is part of the subtree").key.cmp(key);
+    let removed = match order {
+        Ordering::Less => remove_mut(&mut unique(link).right, key),
+        Ordering::Greater => remove_mut(&mut unique(link).left, key),
+        Ordering::Equal => {
+            let n = take(link);
+            *link = match (n.left, n.right) {
+                (None, right) => right,
+                (left, None) => left,
+                (left, right) => {
+                    let mut right = right;
+                    let (min, data) = remove_min_mut(&mut right);
+                    Some(balanced(min, data, left, right))
+                }
+            };
+            return n.data
+        }
+    };
+    rebalance(link);
+    removed
+}
+
+/// An immutable interval map. `insert` and `remove` leave the tree unchanged and return a new
+/// version that shares all subtrees not on the path to the changed key with the old one, so every
+/// change costs O(log n) time and memory no matter how many versions are kept alive. Cloning a
+/// version is O(1). Nodes on the changed path are copied together with their data, use an `Arc`
+/// as data if cloning it is expensive. Versions can be shared between threads.
+#[derive(Debug)]
+pub struct PersistentIntervalTree<D> {
+    root: Link<D>,
+    len: usize,
+}
+
+impl<D> Clone for PersistentIntervalTree<D> {
+    fn clone(&self) -> PersistentIntervalTree<D> {
+        PersistentIntervalTree{root: self.root.clone(), len: self.len}
+    }
+}
+
+/// An iterator over the key,value pairs of a `PersistentIntervalTree` whose keys intersect a
+/// range, in ascending order of the keys.
+pub struct PersistentIter<'a, D: 'a> {
+    range: Range,
+    stack: Vec<&'a PersistentNode<D>>,
+    current: Option<&'a PersistentNode<D>>,
+}
+
+impl<'a, D: 'a> Iterator for PersistentIter<'a, D> {
+
+    type Item = (&'a Range, &'a D);
+
+    fn next(&mut self) -> Option<(&'a Range, &'a D)> {
+        loop {
+            while let Some(node) = self.current {
+                if node.max < self.range.min { break }
+                self.stack.push(node);
+                self.current = node.left.as_ref().map(|n| &**n);
+            }
+            let node = match self.stack.pop() { Some(node) => node, None => return None };
+            if node.key.min > self.range.max {
+                self.stack.clear();
+                return None
+            }
+            self.current = node.right.as_ref().map(|n| &**n);
+            if node.key.intersect(&self.range) { return Some((&node.key, &node.data)) }
+        }
+    }
+}
+
+impl<D> PersistentIntervalTree<D> {
+
+/// This function will construct a new empty PersistentIntervalTree.
+/// # Examples
+/// ```
+/// let t=interval_tree::PersistentIntervalTree::<i32>::new();
+/// assert!(t.empty());
+/// ```
+    pub fn new() -> PersistentIntervalTree<D> {
+        PersistentIntervalTree{root: None, len: 0}
+    }
+
+/// This function will return the number of key,value pairs in this version.
+    pub fn len(&self) -> usize { self.len }
+
+/// This function will return true if this version is empty, false otherwise.
+    pub fn empty(&self) -> bool { self.root.is_none() }
+
+/// This function will return the height of the tree, 0 for an empty tree.
+    pub fn height(&self) -> usize { height(&self.root) as usize }
+
+/// This function will return the data stored under exactly key.
+    pub fn get<K: IntoRange>(&self, key: K) -> Option<&D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let mut current = &self.root;
+        while let Some(ref node) = *current {
+            current = match node.key.cmp(&key) {
+                Ordering::Equal => return Some(&node.data),
+                Ordering::Less => &node.right,
+                Ordering::Greater => &node.left
+            };
+        }
+        None
+    }
+
+/// This function will return an iterator over all key,value pairs in ascending order of the keys.
+    pub fn iter(&self) -> PersistentIter<D> {
+        self.range(..)
+    }
+
+/// This function will return an iterator over all key,value pairs whose keys intersect the
+/// bounds, in ascending order of the keys.
+    pub fn range<K: IntoRange>(&self, bounds: K) -> PersistentIter<D> {
+        match bounds.into_range() {
+            Some(range) => PersistentIter{range: range, stack: Vec::new(), current: self.root.as_ref().map(|n| &**n)},
+            None => PersistentIter{range: Range::new(0,0), stack: Vec::new(), current: None}
+        }
+    }
+
+/// This function will return true if both versions share the same root, which means that one was
+/// cloned from the other without changes.
+    pub fn ptr_eq(&self, other: &PersistentIntervalTree<D>) -> bool {
+        match (&self.root, &other.root) {
+            (&Some(ref a), &Some(ref b)) => Arc::ptr_eq(a, b),
+            (&None, &None) => true,
+            _ => false
+        }
+    }
+}
+
+impl<D: Clone> PersistentIntervalTree<D> {
+
+/// This function will return a new version that additionally contains the key,value pair,
+/// replacing the data of key if it is already part of this version. Empty keys are not inserted.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let v1=interval_tree::PersistentIntervalTree::new().insert(Range::new(2,8),25);
+/// let v2=v1.insert(Range::new(5,6),50);
+/// let v3=v2.insert(Range::new(2,8),75);
+/// assert_eq!(v1.iter().map(|(_,v)| *v).collect::<Vec<i32>>(), vec![25]);
+/// assert_eq!(v2.iter().map(|(_,v)| *v).collect::<Vec<i32>>(), vec![25,50]);
+/// assert_eq!(v3.iter().map(|(_,v)| *v).collect::<Vec<i32>>(), vec![75,50]);
+/// ```
+    pub fn insert<K: IntoRange>(&self, key: K, data: D) -> PersistentIntervalTree<D> {
+        let key = match key.into_range() { Some(key) => key, None => return self.clone() };
+        let (root, replaced) = insert(&self.root, key, data);
+        PersistentIntervalTree{root: Some(root), len: if replaced.is_some() { self.len } else { self.len + 1 }}
+    }
+
+/// This function will return a new version without key, or a clone of this version if key is not
+/// part of it.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let v1=interval_tree::PersistentIntervalTree::new().insert(Range::new(2,8),25);
+/// let v2=v1.remove(Range::new(2,8));
+/// assert!(v2.empty());
+/// assert_eq!(v1.get(Range::new(2,8)), Some(&25));
+/// assert!(v2.remove(Range::new(2,8)).ptr_eq(&v2));
+/// ```
+    pub fn remove<K: IntoRange>(&self, key: K) -> PersistentIntervalTree<D> {
+        let key = match key.into_range() { Some(key) => key, None => return self.clone() };
+        match remove(&self.root, &key) {
+            Some((root, _)) => PersistentIntervalTree{root: root, len: self.len - 1},
+            None => self.clone()
+        }
+    }
+
+/// This function will insert the key,value pair into this version itself and return the data
+/// previously stored under key, if any. Nodes on the path to key that no other version or
+/// snapshot refers to are changed in place, the shared ones are copied, so all clones and
+/// snapshots taken before are not affected.
+    pub fn insert_in_place<K: IntoRange>(&mut self, key: K, data: D) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let replaced = insert_mut(&mut self.root, key, data);
+        if replaced.is_none() { self.len += 1 }
+        replaced
+    }
+
+/// This function will remove key from this version itself and return its data, or None if key
+/// is not part of it. Like `insert_in_place`, only shared nodes are copied, and clones and
+/// snapshots taken before are not affected.
+    pub fn remove_in_place<K: IntoRange>(&mut self, key: K) -> Option<D> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        if self.get(key).is_none() { return None }
+        self.len -= 1;
+        Some(remove_mut(&mut self.root, &key))
+    }
+
+/// This function will return a frozen view of the current contents in O(1). The writer can keep
+/// changing the tree with `insert_in_place` and `remove_in_place` while the snapshot, which can
+/// be sent to another thread, keeps seeing exactly the contents at the time it was taken: nodes
+/// shared with a snapshot are copied when a write reaches them instead of being changed, while
+/// nodes the writer has copied since, or that all snapshots have dropped, are changed in place.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::PersistentIntervalTree::new();
+/// t.insert_in_place(Range::new(2,8),25);
+/// let snapshot = t.snapshot();
+/// t.insert_in_place(Range::new(5,6),50);
+/// t.remove_in_place(Range::new(2,8));
+/// let reader = std::thread::spawn(move || snapshot.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>());
+/// assert_eq!(reader.join().unwrap(), vec![(Range::new(2,8),25)]);
+/// assert_eq!(t.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(), vec![(Range::new(5,6),50)]);
+/// ```
+    pub fn snapshot(&self) -> Snapshot<D> {
+        Snapshot{tree: self.clone()}
+    }
+}
+
+/// A frozen, read-only view of a `PersistentIntervalTree`, see `PersistentIntervalTree::snapshot`.
+#[derive(Debug)]
+pub struct Snapshot<D> {
+    tree: PersistentIntervalTree<D>,
+}
+
+impl<D> Clone for Snapshot<D> {
+    fn clone(&self) -> Snapshot<D> {
+        Snapshot{tree: self.tree.clone()}
+    }
+}
+
+impl<D> Snapshot<D> {
+
+/// This function will return the number of key,value pairs in the snapshot.
+    pub fn len(&self) -> usize { self.tree.len() }
+
+/// This function will return true if the snapshot is empty, false otherwise.
+    pub fn empty(&self) -> bool { self.tree.empty() }
+
+/// This function will return the data stored under exactly key.
+    pub fn get<K: IntoRange>(&self, key: K) -> Option<&D> { self.tree.get(key) }
+
+/// This function will return an iterator over all key,value pairs in ascending order of the keys.
+    pub fn iter(&self) -> PersistentIter<D> { self.tree.iter() }
+
+/// This function will return an iterator over all key,value pairs whose keys intersect the
+/// bounds, in ascending order of the keys.
+    pub fn range<K: IntoRange>(&self, bounds: K) -> PersistentIter<D> { self.tree.range(bounds) }
+
+/// This function will return the snapshot as version of a `PersistentIntervalTree`, e.g. to
+/// continue writing from the state it captured.
+    pub fn to_tree(&self) -> PersistentIntervalTree<D> { self.tree.clone() }
+}
+
+#[cfg(test)]
+fn is_persistent_tree<D>(link: &Link<D>) -> bool {
+    link.as_ref().map_or(true, |n| {
+        (height(&n.left) as i32 - height(&n.right) as i32).abs() <= 1 &&
+            n.height == cmp::max(height(&n.left), height(&n.right)) + 1 &&
+            n.max == cmp::max(n.key.max, cmp::max(max(&n.left), max(&n.right))) &&
+            n.left.as_ref().map_or(true, |l| l.key < n.key) && n.right.as_ref().map_or(true, |r| r.key > n.key) &&
+            is_persistent_tree(&n.left) && is_persistent_tree(&n.right)
+    })
+}
+
+#[test]
+fn test_persistent(){
+    let mut versions = vec![PersistentIntervalTree::new()];
+    for i in 0..200 {
+        let next = versions.last().unwrap().insert(Range::new(i,i+3), i);
+        versions.push(next);
+    }
+    for i in 0..200 {
+        if i % 2 == 0 {
+            let next = versions.last().unwrap().remove(Range::new(i,i+3));
+            versions.push(next);
+        }
+    }
+    for (n, version) in versions.iter().enumerate() {
+        assert!(is_persistent_tree(&version.root));
+        let expected = if n <= 200 { (0..n as u64).collect::<Vec<u64>>() }
+            else { (0..200).filter(|&i| i % 2 == 1 || i >= 2*(n as u64 - 200)).collect() };
+        assert_eq!(version.iter().map(|(_,v)| *v).collect::<Vec<u64>>(), expected);
+        assert_eq!(version.len(), expected.len());
+    }
+    let last = versions.last().unwrap();
+    assert_eq!(last.range(10..=12).map(|(_,v)| *v).collect::<Vec<u64>>(), vec![7,9,11]);
+    // a single insert copies a path instead of the whole tree
+    let root = last.root.as_ref().unwrap();
+    let changed = last.insert(Range::new(1000,1000), 0);
+    assert!(Arc::ptr_eq(root.left.as_ref().unwrap(), changed.root.as_ref().unwrap().left.as_ref().unwrap()));
+}
+
+#[test]
+fn test_snapshot(){
+    let mut t = PersistentIntervalTree::new();
+    let mut snapshots = Vec::new();
+    for i in 0..100 {
+        assert_eq!(t.insert_in_place(Range::new(i,i), i), None);
+        if i % 10 == 0 {
+            let contents = t.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>();
+            snapshots.push((t.snapshot(), contents));
+        }
+        if i % 3 == 0 { assert!(t.remove_in_place(Range::new(i/2,i/2)).is_some()) }
+        t.insert_in_place(Range::new(i/2,i/2), 1000);
+        assert!(is_persistent_tree(&t.root));
+    }
+    assert_eq!(t.len(), 100);
+    for &(ref snapshot, ref contents) in snapshots.iter() {
+        assert_eq!(&snapshot.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(), contents);
+        assert_eq!(snapshot.len(), contents.len());
+    }
+    //unshared nodes are changed in place, shared ones are copied
+    let root = |t: &PersistentIntervalTree<u64>| &**t.root.as_ref().unwrap() as *const PersistentNode<u64>;
+    let before = root(&t);
+    let key = t.root.as_ref().unwrap().key;
+    t.insert_in_place(key, 7);
+    assert_eq!(root(&t), before);
+    let snapshot = t.snapshot();
+    t.insert_in_place(key, 8);
+    assert!(root(&t) != before);
+    assert_eq!((snapshot.get(key), t.get(key)), (Some(&7), Some(&8)));
+}
@@ -0,0 +1,234 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use std::cmp;
+use std::error;
+use std::fmt;
+use std::ops;
+
+/// Returned by `Range::try_new` if min is bigger than max.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeError {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid range [{}, {}]: min is bigger than max", self.min, self.max)
+    }
+}
+
+impl error::Error for RangeError {}
+
+/// Additional functions on `memrange::Range`. Bring the trait into scope to call them as if they
+/// were defined on `Range` itself. All bounds are inclusive. The number of values in a range is
+/// available as `len` on `Range` directly; `checked_len` also handles the full range
+/// [0, u64::MAX], which holds 2^64 values.
+///
+/// `Range::new` and the public fields of `Range` do not stop anyone from building a range with
+/// min > max. The tree treats such inverted ranges like empty ones (see `IntoRange`): they are
+/// never inserted and never match a query. Use `Range::try_new` to catch them where they are
+/// created instead.
+///
+/// As both `Range` and the std range types are defined in other crates, `From` cannot be
+/// implemented between them here. Everything taking a key accepts std ranges directly instead
+/// (see `IntoRange`), so `tree.insert(10..20, v)` needs no conversion, and `to_inclusive` and
+/// `to_exclusive` convert back.
+///
+/// `Range` is `Copy` and implements `Eq` and `Ord`, so it can be used as the key of a `BTreeMap`
+/// next to a tree. Ranges are ordered by min and then by max, which is exactly the order in which
+/// the tree stores and iterates its keys. For the same reason as above, std traits that `Range`
+/// lacks, such as `Hash`, cannot be added here; `to_pair` returns (min, max), which compares,
+/// orders and hashes consistently with the range, e.g. as the key of a `HashMap`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::RangeExt;
+/// use std::collections::{BTreeMap, HashMap};
+///
+/// assert!(Range::new(1,9) < Range::new(2,3));
+/// assert!(Range::new(2,3) < Range::new(2,4));
+/// let mut t=interval_tree::IntervalTree::new();
+/// let mut ordered=BTreeMap::new();
+/// let mut names=HashMap::new();
+/// for &(key, name) in &[(Range::new(2,4),"b"), (Range::new(1,9),"a"), (Range::new(2,3),"c")] {
+///     t.insert(key, name);
+///     ordered.insert(key, name);
+///     names.insert(key.to_pair(), name);
+/// }
+/// assert!(t.iter().eq(ordered.iter()));
+/// assert_eq!(names[&Range::new(2,3).to_pair()], "c");
+/// ```
+pub trait RangeExt: Sized {
+    /// returns the range [min, max], or an error if min > max.
+    /// # Examples
+    /// ```
+    /// extern crate memrange;
+    /// extern crate interval_tree;
+    /// use memrange::Range;
+    /// use interval_tree::RangeExt;
+    ///
+    /// assert_eq!(Range::try_new(1,5), Ok(Range::new(1,5)));
+    /// assert!(Range::try_new(5,1).is_err());
+    /// ```
+    fn try_new(min: u64, max: u64) -> Result<Self, RangeError>;
+
+    /// returns true if both ranges share at least one value.
+    /// # Examples
+    /// ```
+    /// extern crate memrange;
+    /// extern crate interval_tree;
+    /// use memrange::Range;
+    /// use interval_tree::RangeExt;
+    ///
+    /// assert!(Range::new(0,10).overlaps(&Range::new(10,20)));
+    /// assert!(!Range::new(0,9).overlaps(&Range::new(10,20)));
+    /// ```
+    fn overlaps(&self, other: &Self) -> bool;
+
+    /// returns true if p lies within the range.
+    fn contains_point(&self, p: u64) -> bool;
+
+    /// returns true if every value of other lies within the range.
+    /// # Examples
+    /// ```
+    /// extern crate memrange;
+    /// extern crate interval_tree;
+    /// use memrange::Range;
+    /// use interval_tree::RangeExt;
+    ///
+    /// assert!(Range::new(0,10).contains(&Range::new(0,10)));
+    /// assert!(!Range::new(0,10).contains(&Range::new(5,11)));
+    /// ```
+    fn contains(&self, other: &Self) -> bool;
+
+    /// returns the values shared by both ranges, or None if they do not overlap.
+    /// # Examples
+    /// ```
+    /// extern crate memrange;
+    /// extern crate interval_tree;
+    /// use memrange::Range;
+    /// use interval_tree::RangeExt;
+    ///
+    /// assert_eq!(Range::new(0,10).intersection(&Range::new(5,20)), Some(Range::new(5,10)));
+    /// assert_eq!(Range::new(0,4).intersection(&Range::new(5,20)), None);
+    /// ```
+    fn intersection(&self, other: &Self) -> Option<Self>;
+
+    /// returns the smallest range containing both ranges, including the values between them if
+    /// they do not overlap.
+    /// # Examples
+    /// ```
+    /// extern crate memrange;
+    /// extern crate interval_tree;
+    /// use memrange::Range;
+    /// use interval_tree::RangeExt;
+    ///
+    /// assert_eq!(Range::new(0,4).union_hull(&Range::new(10,20)), Range::new(0,20));
+    /// ```
+    fn union_hull(&self, other: &Self) -> Self;
+
+    /// returns the number of values in the range, or None for the full range [0, u64::MAX]. Inverted
+    /// ranges hold no values.
+    fn checked_len(&self) -> Option<u64>;
+
+    /// returns the range as `min..=max`.
+    /// # Examples
+    /// ```
+    /// extern crate memrange;
+    /// extern crate interval_tree;
+    /// use memrange::Range;
+    /// use interval_tree::RangeExt;
+    ///
+    /// assert_eq!(Range::new(10,19).to_inclusive(), 10..=19);
+    /// ```
+    fn to_inclusive(&self) -> ops::RangeInclusive<u64>;
+
+    /// returns the range as `min..max+1`, or None if max is u64::MAX and the end is therefore not
+    /// representable.
+    /// # Examples
+    /// ```
+    /// extern crate memrange;
+    /// extern crate interval_tree;
+    /// use memrange::Range;
+    /// use interval_tree::RangeExt;
+    ///
+    /// assert_eq!(Range::new(10,19).to_exclusive(), Some(10..20));
+    /// assert_eq!(Range::new(10,0xffff_ffff_ffff_ffff).to_exclusive(), None);
+    /// ```
+    fn to_exclusive(&self) -> Option<ops::Range<u64>>;
+
+    /// returns the bounds as (min, max), which compares, orders and hashes like the range itself.
+    fn to_pair(&self) -> (u64, u64);
+}
+
+impl RangeExt for Range {
+    fn try_new(min: u64, max: u64) -> Result<Range, RangeError> {
+        if min > max { return Err(RangeError{min: min, max: max}) }
+        Ok(Range::new(min, max))
+    }
+
+    fn overlaps(&self, other: &Range) -> bool {
+        self.min <= self.max && other.min <= other.max && self.min <= other.max && other.min <= self.max
+    }
+
+    fn contains_point(&self, p: u64) -> bool {
+        self.min <= p && p <= self.max
+    }
+
+    fn contains(&self, other: &Range) -> bool {
+        self.min <= other.min && other.max <= self.max
+    }
+
+    fn intersection(&self, other: &Range) -> Option<Range> {
+        if !self.overlaps(other) { return None }
+        Some(Range::new(cmp::max(self.min, other.min), cmp::min(self.max, other.max)))
+    }
+
+    fn union_hull(&self, other: &Range) -> Range {
+        Range::new(cmp::min(self.min, other.min), cmp::max(self.max, other.max))
+    }
+
+    fn checked_len(&self) -> Option<u64> {
+        if self.min > self.max { return Some(0) }
+        (self.max - self.min).checked_add(1)
+    }
+
+    fn to_inclusive(&self) -> ops::RangeInclusive<u64> {
+        self.min..=self.max
+    }
+
+    fn to_exclusive(&self) -> Option<ops::Range<u64>> {
+        self.max.checked_add(1).map(|end| self.min..end)
+    }
+
+    fn to_pair(&self) -> (u64, u64) {
+        (self.min, self.max)
+    }
+}
+
+#[test]
+fn test_range_ext(){
+    let full = Range::new(0, 0xffff_ffff_ffff_ffff);
+    assert_eq!(full.checked_len(), None);
+    assert_eq!(Range::new(5,5).checked_len(), Some(1));
+    let inverted = Range{min: 9, max: 3};
+    assert_eq!(inverted.checked_len(), Some(0));
+    assert!(!inverted.overlaps(&full) && inverted.intersection(&full).is_none());
+    assert!(full.contains(&Range::new(7,9)) && full.contains_point(0xffff_ffff_ffff_ffff));
+    assert!(!Range::new(5,9).contains_point(4) && Range::new(5,9).contains_point(9));
+    for a in 0..8 { for b in a..8 { for c in 0..8 { for d in c..8 {
+        let (x, y) = (Range::new(a,b), Range::new(c,d));
+        let shared = (0..8).filter(|&p| x.contains_point(p) && y.contains_point(p)).collect::<Vec<u64>>();
+        assert_eq!(x.overlaps(&y), !shared.is_empty());
+        assert_eq!(x.intersection(&y), shared.first().map(|&min| Range::new(min, *shared.last().unwrap())));
+        assert_eq!(x.contains(&y), (c..d+1).all(|p| x.contains_point(p)));
+        assert_eq!(x.union_hull(&y), Range::new(cmp::min(a,c), cmp::max(b,d)));
+        assert_eq!(x.to_exclusive().and_then(|r| ::bounds::normalize(&r)), Some(x));
+        assert_eq!(::bounds::normalize(&x.to_inclusive()), Some(x));
+        assert_eq!(x.cmp(&y), x.to_pair().cmp(&y.to_pair()));
+    }}}}
+}
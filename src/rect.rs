@@ -0,0 +1,154 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use iterators::RangePairIter;
+use bounds::IntoRange;
+use node::search_mut;
+
+/// A map from axis-aligned rectangles, given as a range of x and a range of y values, to data.
+/// The rectangles are stored in nested interval trees: an outer tree over the x ranges whose data
+/// is an inner tree over the y ranges of all rectangles sharing that x range. A query for a box
+/// visits the inner trees of the x ranges overlapping the box and searches each of them for the y
+/// range of the box. This suits data with few distinct x ranges, such as chromosome × position.
+#[derive(Debug)]
+pub struct IntervalTree2D<D> {
+    tree: IntervalTree<IntervalTree<D>>,
+    len: usize,
+}
+
+/// An iterator over the rectangles of an `IntervalTree2D` that overlap a box, yielding the x range,
+/// the y range and the data, ordered by x range and then by y range.
+pub struct RectIter<'a, D: 'a> {
+    outer: RangePairIter<'a, IntervalTree<D>>,
+    inner: Option<(&'a Range, RangePairIter<'a, D>)>,
+    y: Option<Range>,
+}
+
+impl<'a, D: 'a> Iterator for RectIter<'a, D> {
+
+    type Item = (&'a Range, &'a Range, &'a D);
+
+    fn next(&mut self) -> Option<(&'a Range, &'a Range, &'a D)> {
+        let y = match self.y { Some(y) => y, None => return None };
+        loop {
+            if let Some((x, ref mut inner)) = self.inner {
+                if let Some((key, data)) = inner.next() { return Some((x, key, data)) }
+            }
+            match self.outer.next() {
+                Some((x, inner)) => self.inner = Some((x, inner.range(y))),
+                None => return None
+            }
+        }
+    }
+}
+
+impl<D> IntervalTree2D<D> {
+
+/// This function will construct a new empty IntervalTree2D.
+/// # Examples
+/// ```
+/// let t=interval_tree::IntervalTree2D::<i32>::new();
+/// assert_eq!(t.len(), 0);
+/// ```
+    pub fn new() -> IntervalTree2D<D> {
+        IntervalTree2D{tree: IntervalTree::new(), len: 0}
+    }
+
+/// This function will return the number of rectangles in the tree.
+    pub fn len(&self) -> usize { self.len }
+
+/// This function will insert the rectangle x × y with data, overwriting the old data if the
+/// rectangle is already part of the tree. Rectangles with an empty side are not inserted.
+    pub fn insert<K: IntoRange, L: IntoRange>(&mut self, x: K, y: L, data: D) {
+        let (x, y) = match (x.into_range(), y.into_range()) { (Some(x), Some(y)) => (x, y), _ => return };
+        if let Some(inner) = self.tree.root.as_mut().and_then(|root| search_mut(&x, root)) {
+            let before = inner.len();
+            inner.insert(y, data);
+            self.len += inner.len() - before;
+            return;
+        }
+        let mut inner = IntervalTree::new();
+        inner.insert(y, data);
+        self.tree.insert(x, inner);
+        self.len += 1;
+    }
+
+/// This function will return the data of exactly the rectangle x × y.
+    pub fn get<K: IntoRange, L: IntoRange>(&self, x: K, y: L) -> Option<&D> {
+        match (self.tree.get(x), y.into_range()) {
+            (Some(inner), Some(y)) => inner.get(y),
+            _ => None
+        }
+    }
+
+/// This function will remove the rectangle x × y from the tree and return its data.
+    pub fn remove<K: IntoRange, L: IntoRange>(&mut self, x: K, y: L) -> Option<D> {
+        let (x, y) = match (x.into_range(), y.into_range()) { (Some(x), Some(y)) => (x, y), _ => return None };
+        let (data, now_empty) = match self.tree.root.as_mut().and_then(|root| search_mut(&x, root)) {
+            Some(inner) => (inner.remove_entry(y).map(|(_, data)| data), inner.empty()),
+            None => return None
+        };
+        if now_empty { self.tree.remove_entry(x); }
+        if data.is_some() { self.len -= 1 }
+        data
+    }
+
+/// This function will return an iterator over all rectangles that overlap the box x × y, i.e.
+/// whose x range intersects x and whose y range intersects y.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree2D::new();
+/// t.insert(Range::new(0,100), Range::new(0,20), "toolbar");
+/// t.insert(Range::new(10,30), Range::new(10,40), "button");
+/// t.insert(Range::new(50,90), Range::new(30,80), "canvas");
+/// let hits = t.overlapping(Range::new(20,60), Range::new(15,35)).map(|(_,_,v)| *v).collect::<Vec<_>>();
+/// assert_eq!(hits, vec!["toolbar", "button", "canvas"]);
+/// assert_eq!(t.containing_point(15, 30).map(|(_,_,v)| *v).collect::<Vec<_>>(), vec!["button"]);
+/// ```
+    pub fn overlapping<K: IntoRange, L: IntoRange>(&self, x: K, y: L) -> RectIter<D> {
+        RectIter{outer: self.tree.range(x), inner: None, y: y.into_range()}
+    }
+
+/// This function will return an iterator over all rectangles that contain the point (x, y).
+    pub fn containing_point(&self, x: u64, y: u64) -> RectIter<D> {
+        self.overlapping(x..=x, y..=y)
+    }
+
+/// This function will return an iterator over all rectangles, ordered by x range and then by y
+/// range.
+    pub fn iter(&self) -> RectIter<D> {
+        self.overlapping(.., ..)
+    }
+}
+
+#[test]
+fn test_rect(){
+    let mut t = IntervalTree2D::new();
+    let mut all = Vec::new();
+    for i in 0..20u64 {
+        for j in 0..20u64 {
+            let (x, y) = (Range::new(i % 5 * 10, i % 5 * 10 + i), Range::new(j * 7, j * 7 + j));
+            t.insert(x, y, (i, j));
+            all.push((x, y, (i, j)));
+        }
+    }
+    all.sort_by_key(|&(x, y, _)| (x, y));
+    all.dedup_by_key(|&mut (x, y, _)| (x, y));
+    assert_eq!(t.len(), all.len());
+    assert!(t.iter().map(|(x, y, v)| (*x, *y, *v)).eq(all.iter().cloned()));
+    for px in 0..70 {
+        for py in (0..160).step_by(3) {
+            let expected = t.iter().filter(|&(x, y, _)| x.min <= px && px <= x.max && y.min <= py && py <= y.max).count();
+            assert_eq!(t.containing_point(px, py).count(), expected);
+        }
+    }
+    let removed = t.remove(Range::new(0, 0), Range::new(0, 0));
+    assert_eq!(removed, Some((0, 0)));
+    assert_eq!(t.remove(Range::new(0, 0), Range::new(0, 0)), None);
+    assert_eq!(t.len(), t.iter().count());
+}
@@ -0,0 +1,133 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use iterators::RangePairIter;
+use bounds::IntoRange;
+
+/// A set of u64 stored as disjoint ranges. Inserting a range merges it with all stored ranges it
+/// overlaps or touches, removing a range cuts it out of the stored ranges, so the set always
+/// holds the minimal number of maximal ranges.
+#[derive(Debug)]
+pub struct IntervalSet {
+    tree: IntervalTree<()>
+}
+
+/// An iterator over the ranges of an `IntervalSet` in ascending order.
+pub struct SetIter<'a> {
+    inner: RangePairIter<'a, ()>
+}
+
+impl<'a> Iterator for SetIter<'a> {
+
+    type Item = &'a Range;
+
+    fn next(&mut self) -> Option<&'a Range> {
+        self.inner.next().map(|(k,_)| k)
+    }
+}
+
+impl IntervalSet {
+
+/// This function will construct a new empty IntervalSet.
+/// # Examples
+/// ```
+/// let s=interval_tree::IntervalSet::new();
+/// assert!(s.empty());
+/// ```
+    pub fn new() -> IntervalSet {
+        IntervalSet{tree: IntervalTree::new()}
+    }
+
+/// This function will add all values of key to the set, merging it with every stored range it
+/// overlaps or that ends directly before or starts directly after it.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut s=interval_tree::IntervalSet::new();
+/// s.insert(Range::new(0,4));
+/// s.insert(Range::new(10,12));
+/// s.insert(Range::new(5,9));
+/// assert_eq!(s.iter().collect::<Vec<&Range>>(), vec![&Range::new(0,12)]);
+/// ```
+    pub fn insert<K: IntoRange>(&mut self, key: K) {
+        let key = match key.into_range() { Some(key) => key, None => return };
+        let probe = Range::new(key.min.saturating_sub(1), key.max.saturating_add(1));
+        let merged = self.tree.delete_range(probe).iter().fold(key, |hull, &(ref r, _)| hull.get_union(r));
+        self.tree.insert(merged, ());
+    }
+
+/// This function will remove all values of key from the set, splitting stored ranges that
+/// reach over both ends of key.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut s=interval_tree::IntervalSet::new();
+/// s.insert(Range::new(0,20));
+/// s.remove(Range::new(5,9));
+/// assert_eq!(s.iter().collect::<Vec<&Range>>(), vec![&Range::new(0,4), &Range::new(10,20)]);
+/// ```
+    pub fn remove<K: IntoRange>(&mut self, key: K) {
+        let key = match key.into_range() { Some(key) => key, None => return };
+        for (range, _) in self.tree.delete_range(key) {
+            if range.min < key.min { self.tree.insert(Range::new(range.min, key.min-1), ()) }
+            if range.max > key.max { self.tree.insert(Range::new(key.max+1, range.max), ()) }
+        }
+    }
+
+/// This function will return true if every value of key is part of the set.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut s=interval_tree::IntervalSet::new();
+/// s.insert(Range::new(0,4));
+/// s.insert(Range::new(5,9));
+/// assert!(s.contains(Range::new(3,7)));
+/// assert!(!s.contains(Range::new(3,10)));
+/// ```
+    pub fn contains<K: IntoRange>(&self, key: K) -> bool {
+        let key = match key.into_range() { Some(key) => key, None => return true };
+        match self.tree.first_overlap(key) {
+            Some((range, _)) => range.min <= key.min && key.max <= range.max,
+            None => false
+        }
+    }
+
+/// This function will return true if at least one value of key is part of the set.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut s=interval_tree::IntervalSet::new();
+/// s.insert(Range::new(0,4));
+/// assert!(s.overlaps(Range::new(4,7)));
+/// assert!(!s.overlaps(Range::new(5,7)));
+/// ```
+    pub fn overlaps<K: IntoRange>(&self, key: K) -> bool {
+        self.tree.first_overlap(key).is_some()
+    }
+
+/// This function will return true if the set is empty, false otherwise.
+    pub fn empty(&self) -> bool { self.tree.empty() }
+
+/// This function will return an iterator over all stored ranges in ascending order.
+    pub fn iter(&self) -> SetIter {
+        SetIter{inner: self.tree.iter()}
+    }
+
+/// This function will return an iterator over all stored ranges that intersect the bounds.
+    pub fn range<K: IntoRange>(&self, bounds: K) -> SetIter {
+        SetIter{inner: self.tree.range(bounds)}
+    }
+}
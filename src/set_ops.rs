@@ -0,0 +1,109 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use multimap::IntervalMultiMap;
+
+impl<D> IntervalTree<D> {
+
+/// This function will return a map that contains the intersection of every pair of overlapping
+/// keys from self and other, with the data computed by f from the data of both keys. Both trees
+/// are swept once side by side (see `join`). If several pairs intersect in the same range, all of
+/// their results are kept under that range, in the order in which the sweep finds the pairs.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut a=interval_tree::IntervalTree::<i32>::new();
+/// let mut b=interval_tree::IntervalTree::<i32>::new();
+/// a.insert(Range::new(0,10),1);
+/// a.insert(Range::new(5,10),2);
+/// b.insert(Range::new(5,20),10);
+/// let both = a.intersection(&b, |x, y| x+y);
+/// assert_eq!(both.get(Range::new(5,10)), &[11, 12]);
+/// ```
+    pub fn intersection<E, R, F: FnMut(&D, &E) -> R>(&self, other: &IntervalTree<E>, mut f: F) -> IntervalMultiMap<R> {
+        let mut res = IntervalMultiMap::new();
+        for ((key, data), (other_key, other_data)) in self.join(other) {
+            res.insert(key.get_intersection(other_key), f(data, other_data));
+        }
+        res
+    }
+
+/// This function will return a map that contains the parts of the keys of self that are not
+/// covered by any key of other. Every part keeps a clone of the data of the key it was cut from;
+/// if several keys leave the same part, all of their data is kept under it. Both trees are
+/// traversed once.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut a=interval_tree::IntervalTree::<i32>::new();
+/// let mut b=interval_tree::IntervalTree::<i32>::new();
+/// a.insert(Range::new(0,10),1);
+/// b.insert(Range::new(3,4),2);
+/// b.insert(Range::new(8,20),2);
+/// let rest = a.difference(&b);
+/// assert_eq!(rest.iter().map(|(k,_)| *k).collect::<Vec<Range>>(),
+///            vec![Range::new(0,2), Range::new(5,7)]);
+/// ```
+    pub fn difference<E>(&self, other: &IntervalTree<E>) -> IntervalMultiMap<D> where D: Clone {
+        let mut res = IntervalMultiMap::new();
+        for (piece, data) in self.uncovered_parts(other) {
+            res.insert(piece, data.clone());
+        }
+        res
+    }
+
+    /// returns the parts of the keys of self not covered by other together with the data of their key
+    fn uncovered_parts<'a, E>(&'a self, other: &IntervalTree<E>) -> Vec<(Range, &'a D)> {
+        let covered = other.iter_coalesced().collect::<Vec<Range>>();
+        let mut first = 0;
+        let mut parts = Vec::new();
+        for (key, data) in self.iter() {
+            //the keys start in ascending order, so covered ranges that end before this key end
+            //before all later keys as well
+            while first < covered.len() && covered[first].max < key.min { first += 1 }
+            let mut from = Some(key.min);
+            for range in covered[first..].iter().take_while(|range| range.min <= key.max) {
+                let start = from.expect("loop stops once the key is covered up to its end");
+                if range.min > start { parts.push((Range::new(start, range.min - 1), data)) }
+                from = if range.max >= key.max { None } else { Some(range.max + 1) };
+                if from.is_none() { break }
+            }
+            if let Some(start) = from { parts.push((Range::new(start, key.max), data)) }
+        }
+        parts
+    }
+
+/// This function will return a map that contains the parts of all keys that are covered by only
+/// one of the trees with their data cloned, and the intersections of overlapping keys from both
+/// trees with the data computed by f, i.e. the union of `self.difference(other)`,
+/// `other.difference(self)` and `self.intersection(other, f)`. Like in those, no result is
+/// dropped if several of them share a range.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut a=interval_tree::IntervalTree::<i32>::new();
+/// let mut b=interval_tree::IntervalTree::<i32>::new();
+/// a.insert(Range::new(0,10),1);
+/// b.insert(Range::new(5,20),2);
+/// let all = a.union(&b, |x, y| x+y);
+/// assert_eq!(all.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(),
+///            vec![(Range::new(0,4),1), (Range::new(5,10),3), (Range::new(11,20),2)]);
+/// ```
+    pub fn union<F: FnMut(&D, &D) -> D>(&self, other: &IntervalTree<D>, f: F) -> IntervalMultiMap<D> where D: Clone {
+        let mut res = self.intersection(other, f);
+        for (piece, data) in self.uncovered_parts(other).into_iter().chain(other.uncovered_parts(self)) {
+            res.insert(piece, data.clone());
+        }
+        res
+    }
+}
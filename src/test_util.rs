@@ -0,0 +1,88 @@
+extern crate memrange;
+
+use self::memrange::Range;
+use tree::IntervalTree;
+use bounds::IntoRange;
+use std::cmp;
+
+/// A small deterministic pseudo random number generator (splitmix64) for building reproducible
+/// test inputs. The same seed always yields the same sequence, on every platform and in every
+/// version of this crate. It is not suitable for anything but tests. Only available with the
+/// `test_util` feature.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::TestRng;
+///
+/// let mut rng = TestRng::new(7);
+/// let range = rng.range(Range::new(100,200));
+/// assert!(100 <= range.min && range.max <= 200);
+/// assert_eq!(TestRng::new(7).range(Range::new(100,200)), range);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestRng {
+    state: u64,
+}
+
+impl TestRng {
+
+/// This function will construct a new generator from seed.
+    pub fn new(seed: u64) -> TestRng {
+        TestRng{state: seed}
+    }
+
+/// This function will return the next pseudo random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+/// This function will return a pseudo random number in [0, bound), or any number if bound is 0.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        let x = self.next_u64();
+        if bound == 0 { x } else { x % bound }
+    }
+
+/// This function will return a pseudo random range inside key_space. Like the fuzz tests of this
+/// crate, half of the ranges are shorter than 500, the others may reach up to the end of the key
+/// space.
+    pub fn range(&mut self, key_space: Range) -> Range {
+        let offset = key_space.min + self.below((key_space.max - key_space.min).wrapping_add(1));
+        let rest = key_space.max - offset;
+        let len = if self.next_u64() & 1 == 0 {
+            cmp::min(self.below(500), rest)
+        } else {
+            self.below(rest.wrapping_add(1))
+        };
+        Range::new(offset, offset + len)
+    }
+}
+
+impl IntervalTree<u64> {
+
+/// This function will construct a pseudo random tree from n random keys inside key_space, see
+/// `TestRng::range`. The data of every key is the index of the step that generated it; if a key is
+/// generated several times the last index is kept, so the tree can hold fewer than n pairs. The
+/// same seed, n and key_space always produce the same tree. Only available with the `test_util`
+/// feature.
+/// # Examples
+/// ```
+/// extern crate interval_tree;
+/// use interval_tree::IntervalTree;
+///
+/// let t = IntervalTree::random(42, 1000, 0..=1_000_000);
+/// assert_eq!(t.len(), 1000);
+/// assert!(t.iter().eq(IntervalTree::random(42, 1000, 0..=1_000_000).iter()));
+/// assert!(!t.iter().eq(IntervalTree::random(43, 1000, 0..=1_000_000).iter()));
+/// ```
+    pub fn random<K: IntoRange>(seed: u64, n: usize, key_space: K) -> IntervalTree<u64> {
+        let key_space = match key_space.into_range() { Some(key_space) => key_space, None => return IntervalTree::new() };
+        let mut rng = TestRng::new(seed);
+        IntervalTree::from_vec((0..n as u64).map(|i| (rng.range(key_space), i)).collect())
+    }
+}
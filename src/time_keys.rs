@@ -0,0 +1,192 @@
+//! Conversions between points in time and the u64 keys of the trees. A point in time becomes the
+//! number of nanoseconds since an epoch: `Duration`s count from zero, `SystemTime`s from the unix
+//! epoch and `Instant`s, which have no fixed epoch, from the one given to `InstantKeys`. The
+//! nanoseconds of a u64 reach about 584 years past the epoch.
+//!
+//! Std ranges of `Duration` and `SystemTime` can be used directly wherever a key is expected.
+//! Bounds outside the representable span are clamped, so a range starting before the unix epoch
+//! starts at key 0 and a range ending after year 2554 reaches the biggest key; a range that lies
+//! completely outside is empty. With the `chrono` feature, the same holds for ranges of chrono's
+//! `DateTime`s, see `datetime_key`, and with the `time` feature for ranges of the `OffsetDateTime`s
+//! of the time crate, see `offset_datetime_key`.
+//! # Examples
+//! ```
+//! extern crate memrange;
+//! extern crate interval_tree;
+//! use std::time::{Duration, UNIX_EPOCH};
+//! use interval_tree::time_keys::{key_system_time, system_time_key};
+//!
+//! let mut t=interval_tree::IntervalTree::<&str>::new();
+//! let noon = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+//! t.insert(noon..noon + Duration::from_secs(3600), "meeting");
+//! t.insert(Duration::from_millis(1500)..=Duration::from_secs(2), "warmup");
+//! let (key, _) = t.range(noon + Duration::from_secs(60)..).next().unwrap();
+//! assert_eq!(key_system_time(key.min), noon);
+//! assert_eq!(key.max, system_time_key(noon).unwrap() + 3_599_999_999_999);
+//! assert_eq!(t.range(Duration::from_secs(2)..=Duration::from_secs(2)).count(), 1);
+//! ```
+
+extern crate memrange;
+
+use self::memrange::Range;
+use bounds::{IntoRange, normalize};
+use std::cmp::Ordering;
+use std::collections::Bound;
+use std::ops;
+use std::ops::RangeBounds;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "chrono")]
+pub use chrono_keys::{datetime_key, key_datetime};
+#[cfg(feature = "time")]
+pub use offset_datetime_keys::{offset_datetime_key, key_offset_datetime};
+
+/// returns the key of a point in time, or whether it lies before (Less) or after (Greater) all keys
+type KeyOf<T> = fn(&T) -> Result<u64, Ordering>;
+
+/// normalizes bounds over points in time into an inclusive Range, clamping points outside the keys
+pub(crate) fn time_bounds<T, R: RangeBounds<T>>(bounds: &R, key: KeyOf<T>) -> Option<Range> {
+    let min = match bounds.start_bound() {
+        Bound::Included(t) => match key(t) { Ok(k) => Bound::Included(k), Err(Ordering::Greater) => return None, Err(_) => Bound::Unbounded },
+        Bound::Excluded(t) => match key(t) { Ok(k) => Bound::Excluded(k), Err(Ordering::Greater) => return None, Err(_) => Bound::Unbounded },
+        Bound::Unbounded => Bound::Unbounded
+    };
+    let max = match bounds.end_bound() {
+        Bound::Included(t) => match key(t) { Ok(k) => Bound::Included(k), Err(Ordering::Less) => return None, Err(_) => Bound::Unbounded },
+        Bound::Excluded(t) => match key(t) { Ok(k) => Bound::Excluded(k), Err(Ordering::Less) => return None, Err(_) => Bound::Unbounded },
+        Bound::Unbounded => Bound::Unbounded
+    };
+    normalize(&(min, max))
+}
+
+fn checked_duration_key(d: &Duration) -> Result<u64, Ordering> {
+    duration_key(*d).ok_or(Ordering::Greater)
+}
+
+pub(crate) fn checked_system_time_key(t: &SystemTime) -> Result<u64, Ordering> {
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => checked_duration_key(&d),
+        Err(_) => Err(Ordering::Less)
+    }
+}
+
+/// This function will return the number of nanoseconds in d, or None if they do not fit into u64.
+pub fn duration_key(d: Duration) -> Option<u64> {
+    d.as_secs().checked_mul(1_000_000_000).and_then(|nanos| nanos.checked_add(d.subsec_nanos() as u64))
+}
+
+/// This function will return the duration of key nanoseconds.
+pub fn key_duration(key: u64) -> Duration {
+    Duration::new(key / 1_000_000_000, (key % 1_000_000_000) as u32)
+}
+
+/// This function will return the nanoseconds between the unix epoch and t, or None if t lies
+/// before the epoch or too far after it.
+pub fn system_time_key(t: SystemTime) -> Option<u64> {
+    checked_system_time_key(&t).ok()
+}
+
+/// This function will return the point in time key nanoseconds after the unix epoch.
+pub fn key_system_time(key: u64) -> SystemTime {
+    UNIX_EPOCH + key_duration(key)
+}
+
+macro_rules! impl_into_range_for_time_bounds {
+    ($key:ident, $($t:ty),*) => {$(
+        impl IntoRange for $t {
+            fn into_range(self) -> Option<Range> { time_bounds(&self, $key) }
+        }
+    )*}
+}
+
+impl_into_range_for_time_bounds!(checked_duration_key, ops::Range<Duration>, ops::RangeInclusive<Duration>,
+                                 ops::RangeFrom<Duration>, ops::RangeTo<Duration>, ops::RangeToInclusive<Duration>);
+impl_into_range_for_time_bounds!(checked_system_time_key, ops::Range<SystemTime>, ops::RangeInclusive<SystemTime>,
+                                 ops::RangeFrom<SystemTime>, ops::RangeTo<SystemTime>, ops::RangeToInclusive<SystemTime>);
+
+/// Converts `Instant`s into keys, counting nanoseconds from a fixed epoch such as the start of the
+/// program. Instants before the epoch are clamped to key 0 in ranges.
+/// # Examples
+/// ```
+/// extern crate interval_tree;
+/// use std::time::{Duration, Instant};
+/// use interval_tree::InstantKeys;
+///
+/// let keys = InstantKeys::new(Instant::now());
+/// let start = keys.epoch() + Duration::from_millis(20);
+/// let mut t=interval_tree::IntervalTree::<&str>::new();
+/// t.insert(keys.range(start..start + Duration::from_millis(16)).unwrap(), "frame 1");
+/// let (key, _) = t.range(keys.range(start + Duration::from_millis(5)..).unwrap()).next().unwrap();
+/// assert_eq!(keys.instant(key.min), start);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstantKeys {
+    epoch: Instant,
+}
+
+impl InstantKeys {
+
+/// This function will construct a converter counting from epoch.
+    pub fn new(epoch: Instant) -> InstantKeys {
+        InstantKeys{epoch: epoch}
+    }
+
+/// This function will return the instant of key 0.
+    pub fn epoch(&self) -> Instant {
+        self.epoch
+    }
+
+/// This function will return the key of instant, or None if it lies before the epoch or too far
+/// after it.
+    pub fn key(&self, instant: Instant) -> Option<u64> {
+        if instant < self.epoch { return None }
+        duration_key(instant.duration_since(self.epoch))
+    }
+
+/// This function will return the instant of key.
+    pub fn instant(&self, key: u64) -> Instant {
+        self.epoch + key_duration(key)
+    }
+
+/// This function will return the key range covering the bounds, clamped to the keys, or None if
+/// it is empty.
+    pub fn range<R: RangeBounds<Instant>>(&self, bounds: R) -> Option<Range> {
+        let shifted = (self.shift(bounds.start_bound()), self.shift(bounds.end_bound()));
+        time_bounds(&shifted, checked_signed_key)
+    }
+
+    /// returns the signed distance of an instant bound from the epoch
+    fn shift(&self, bound: Bound<&Instant>) -> Bound<(bool, Duration)> {
+        let distance = |t: &Instant| if *t < self.epoch { (false, self.epoch.duration_since(*t)) } else { (true, t.duration_since(self.epoch)) };
+        match bound {
+            Bound::Included(t) => Bound::Included(distance(t)),
+            Bound::Excluded(t) => Bound::Excluded(distance(t)),
+            Bound::Unbounded => Bound::Unbounded
+        }
+    }
+}
+
+fn checked_signed_key(&(after, d): &(bool, Duration)) -> Result<u64, Ordering> {
+    if !after && d > Duration::new(0, 0) { return Err(Ordering::Less) }
+    checked_duration_key(&d)
+}
+
+#[test]
+fn test_time_keys(){
+    let max = Duration::new(18_446_744_073, 709_551_615);
+    assert_eq!(duration_key(max), Some(0xffff_ffff_ffff_ffff));
+    assert_eq!(duration_key(max + Duration::new(0, 1)), None);
+    assert_eq!(key_duration(0xffff_ffff_ffff_ffff), max);
+    assert_eq!((Duration::new(1, 0)..Duration::new(2, 0)).into_range(), Some(Range::new(1_000_000_000, 1_999_999_999)));
+    assert_eq!((Duration::new(1, 0)..max * 2).into_range(), Some(Range::new(1_000_000_000, 0xffff_ffff_ffff_ffff)));
+    assert_eq!((max * 2..).into_range(), None);
+    let before = UNIX_EPOCH - Duration::new(10, 0);
+    assert_eq!((before..UNIX_EPOCH + Duration::new(0, 5)).into_range(), Some(Range::new(0, 4)));
+    assert_eq!((..before).into_range(), None);
+    assert_eq!(system_time_key(before), None);
+    let keys = InstantKeys::new(Instant::now());
+    let later = keys.epoch() + Duration::new(3, 0);
+    assert_eq!(keys.key(later), Some(3_000_000_000));
+    assert_eq!(keys.range(..=later), Some(Range::new(0, 3_000_000_000)));
+    assert_eq!(keys.range(later..later), None);
+    assert_eq!(keys.instant(3_000_000_000), later);
+}
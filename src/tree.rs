@@ -1,15 +1,259 @@
-extern crate rand;
-extern crate test;
 extern crate memrange;
 
-use node::Node;
+use node::{Node, Invariant, VisitControl, visit_preorder, visit_inorder, visit_postorder};
 use self::memrange::Range;
-use node::{insert,delete,search,min_pair, max_pair, height};
-use iterators::RangePairIter;
+use node::{insert,insert_with,shift,map_values,remove,remove_relaxed,total_depth,retain,split,join_two,search,search_pair,max_end_starting_before,min_starting_after,count_overlaps,batch_overlaps,aggregate_overlaps,size,select,select_by_len,total_len,rank,min_pair, max_pair, height, first_overlap, last_overlap, from_sorted, write_dot, format_tree, find_gap, check_invariants};
+use iterators::{RangePairIter, Direction, IntoIter, ExtractIf, GapIter, Keys, Values, ValuesMut, NearestIter, JoinIter, DiffIter, EventIter, Coalesced, CoalescedWith, EndOrderIter, ContainmentIter};
+use binary::{BinaryData, MAGIC, VERSION, invalid_data};
+use bounds::IntoRange;
+use range_ext::RangeError;
+use aggregate::{Aggregate, NoAggregate, LenSum};
+use std::cmp;
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::io;
+use std::iter;
+use std::mem;
+use std::ops::Index;
+use std::io::{Read, Write};
+
+/// Decides what happens to keys that are inserted into a tree with a universe but reach outside
+/// of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniversePolicy {
+    /// keys not fully contained in the universe are not inserted
+    Reject,
+    /// keys are cut down to the part that lies within the universe, keys completely outside of
+    /// the universe are not inserted
+    Clamp,
+}
+
+/// Decides how much work the tree spends on keeping itself balanced, trading the depth of lookups
+/// for the cost of deletions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalancePolicy {
+    /// the tree is kept balanced as AVL tree on every insert and delete, so its height is at most
+    /// about 1.44 times the minimal height. This is the default.
+    Strict,
+    /// deletes only update the cached values on the path to the removed key and never rotate, so
+    /// the tree may drift out of balance. Once its height exceeds the given factor (at least 2)
+    /// times the minimal height ceil(log2(n+1)), the whole tree is rebuilt in O(n). As that only
+    /// happens after a large fraction of the keys has been deleted, the rebuild is cheap in the
+    /// amortized sense. Inserts still rotate.
+    Relaxed(u32),
+}
+
+/// The shape of a tree and the work spent on balancing it, as returned by `stats`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeStats {
+    /// the number of nodes, one per key
+    pub len: usize,
+    /// the height of the tree, see `height`
+    pub height: usize,
+    /// the smallest possible height for len nodes, ceil(log2(len+1))
+    pub min_height: usize,
+    /// the average number of nodes on the path from the root to a node, 0 for an empty tree
+    pub average_depth: f64,
+    /// the number of rotations performed on the tree since it was constructed
+    pub rotations: u64,
+}
+
+/// The memory used by a tree, as returned by `memory_stats`. All sizes are in bytes and do not
+/// include the bookkeeping of the allocator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// the number of nodes, one per key
+    pub nodes: usize,
+    /// the size of a single node including its key, data and cached values
+    pub node_size: usize,
+    /// the part of node_size taken by the data
+    pub data_size: usize,
+    /// the heap memory owned by the data of all nodes, as reported by the caller
+    pub data_heap: usize,
+}
+
+impl MemoryUsage {
+    /// returns the bytes used by all nodes together with the heap memory owned by their data
+    pub fn total(&self) -> usize {
+        self.nodes * self.node_size + self.data_heap
+    }
+}
+
+/// Returned in `Error::Overlap` by `try_insert` if the new key overlaps a key that is already part
+/// of the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlapError {
+    /// the lowest stored key that overlaps the rejected key
+    pub existing: Range,
+}
+
+impl fmt::Display for OverlapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "key overlaps existing interval [{}, {}]", self.existing.min, self.existing.max)
+    }
+}
+
+impl error::Error for OverlapError {}
+
+/// Returned by `check_invariants` if the tree is corrupted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvariantError {
+    /// the violated property
+    pub invariant: Invariant,
+    /// the key of the node where the violation was found
+    pub key: Range,
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let property = match self.invariant {
+            Invariant::Ordering => "keys out of order",
+            Invariant::Balance => "subtree heights differ by more than one",
+            Invariant::Height => "wrong cached height",
+            Invariant::MaxEnd => "wrong cached maximal end",
+            Invariant::Cache => "wrong cached subtree values",
+            Invariant::Aggregate => "wrong cached aggregate",
+        };
+        write!(f, "{} at key [{}, {}]", property, self.key.min, self.key.max)
+    }
+}
+
+impl error::Error for InvariantError {}
+
+/// Returned by `shift` if a key would leave the key space of the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShiftError {
+    /// the lowest stored key that cannot be moved
+    pub key: Range,
+    /// the rejected offset
+    pub delta: i64,
+}
+
+impl fmt::Display for ShiftError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot move key [{}, {}] by {}", self.key.min, self.key.max, self.delta)
+    }
+}
+
+impl error::Error for ShiftError {}
+
+/// Returned by `map_keys` if the mapped keys are not strictly increasing or a mapped key is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyOrderError {
+    /// the lowest stored key whose mapped key is out of order or empty
+    pub key: Range,
+    /// the mapped key
+    pub mapped: Range,
+}
+
+impl fmt::Display for KeyOrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "key [{}, {}] mapped out of order to [{}, {}]", self.key.min, self.key.max, self.mapped.min, self.mapped.max)
+    }
+}
+
+impl error::Error for KeyOrderError {}
+
+/// Returned by `map_keys` if the mapped keys are out of order, with the tree that was to be mapped.
+#[derive(Debug)]
+pub struct MapKeysError<D, A: Aggregate<D> = NoAggregate> {
+    /// the tree map_keys was called on, unchanged
+    pub tree: IntervalTree<D, A>,
+    /// the key that broke the order
+    pub error: KeyOrderError,
+}
+
+impl<D, A: Aggregate<D>> fmt::Display for MapKeysError<D, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<D: fmt::Debug, A: Aggregate<D> + fmt::Debug> error::Error for MapKeysError<D, A> {}
+
+impl<D, A: Aggregate<D>> From<MapKeysError<D, A>> for Error {
+    fn from(e: MapKeysError<D, A>) -> Error { Error::KeyOrder(e.error) }
+}
+
+/// The errors of all fallible operations of the crate, so they can be propagated with `?` into a
+/// single type. Every specific error converts into it.
+///
+/// Apart from broken internal invariants, which `check_invariants` detects, only a few functions
+/// panic, each documented in a `# Panics` section and paired with a non-panicking variant:
+/// `IntervalTree::from_sorted_iter` (`try_from_sorted_iter`), `IntervalTree::extend_sorted`
+/// (`try_extend_sorted`) and indexing with `t[key]` (`try_get`). Inverted ranges never panic in
+/// the tree, they are treated as empty (see `IntoRange`); `Range::try_new` rejects them where they
+/// are built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// a range with min > max, see `RangeExt::try_new`
+    InvalidRange(RangeError),
+    /// the keys of a sorted run are not strictly increasing, key came right after previous
+    Unsorted{previous: Range, key: Range},
+    /// the key is not part of the tree
+    KeyNotFound,
+    /// the key holds no values, e.g. `5..5` or an inverted range, see `try_insert`
+    EmptyKey,
+    /// the universe of the tree does not accept key, see `try_insert`
+    OutsideUniverse{key: Range},
+    /// see `try_insert`
+    Overlap(OverlapError),
+    /// see `shift`
+    Shift(ShiftError),
+    /// see `map_keys`
+    KeyOrder(KeyOrderError),
+    /// see `check_invariants`
+    Invariant(InvariantError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidRange(ref e) => e.fmt(f),
+            Error::Unsorted{previous, key} => write!(f, "keys not strictly increasing: [{}, {}] before [{}, {}]", previous.min, previous.max, key.min, key.max),
+            Error::KeyNotFound => write!(f, "key not found"),
+            Error::EmptyKey => write!(f, "empty key"),
+            Error::OutsideUniverse{key} => write!(f, "key [{}, {}] is outside the universe of the tree", key.min, key.max),
+            Error::Overlap(ref e) => e.fmt(f),
+            Error::Shift(ref e) => e.fmt(f),
+            Error::KeyOrder(ref e) => e.fmt(f),
+            Error::Invariant(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+macro_rules! impl_from_error {
+    ($($variant:ident($t:ty)),*) => {$(
+        impl From<$t> for Error {
+            fn from(e: $t) -> Error { Error::$variant(e) }
+        }
+    )*}
+}
+
+impl_from_error!(InvalidRange(RangeError), Overlap(OverlapError), Shift(ShiftError), KeyOrder(KeyOrderError), Invariant(InvariantError));
+
+/// collects pairs whose keys have to be strictly increasing, skipping empty keys
+fn sorted_pairs<D, K: IntoRange, I: IntoIterator<Item=(K, D)>>(iter: I) -> Result<Vec<(Range, D)>, Error> {
+    let mut pairs: Vec<(Range, D)> = Vec::new();
+    for (key, data) in iter {
+        let key = match key.into_range() { Some(key) => key, None => continue };
+        if let Some(&(previous, _)) = pairs.last() {
+            if previous >= key { return Err(Error::Unsorted{previous: previous, key: key}) }
+        }
+        pairs.push((key, data));
+    }
+    Ok(pairs)
+}
 
 #[derive(Debug)]
-pub struct IntervalTree<D> {
-    pub root: Option<Box<Node<D>>>
+pub struct IntervalTree<D, A: Aggregate<D> = NoAggregate> {
+    pub root: Option<Box<Node<D, A>>>,
+    universe: Option<(Range, UniversePolicy)>,
+    balance: BalancePolicy,
+    rotations: u64,
 }
 
 impl <D> IntervalTree<D>{
@@ -22,11 +266,131 @@ impl <D> IntervalTree<D>{
 /// let mut t=interval_tree::IntervalTree::<i32>::new();
 /// ```
     pub fn new() -> IntervalTree<D>{
-        IntervalTree{root: None}
+        IntervalTree{root: None, universe: None, balance: BalancePolicy::Strict, rotations: 0}
+    }
+
+/// This function will construct a perfectly balanced IntervalTree from pairs that are sorted by
+/// key and free of duplicate keys in O(n), which is much faster than inserting them one by one.
+/// Empty keys are skipped.
+/// # Panics
+/// Panics if the keys are not strictly increasing.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let t=interval_tree::IntervalTree::from_sorted_iter((0..100).map(|i| (i*10..i*10+15, i)));
+/// assert_eq!(t.height(), 7);
+/// assert_eq!(t.get(memrange::Range::new(50,64)), Some(&5));
+/// ```
+    pub fn from_sorted_iter<K: IntoRange, I: IntoIterator<Item=(K, D)>>(iter: I) -> IntervalTree<D>{
+        match IntervalTree::try_from_sorted_iter(iter) {
+            Ok(tree) => tree,
+            Err(e) => panic!("from_sorted_iter: {}", e)
+        }
+    }
+
+/// This function will construct a tree like `from_sorted_iter`, but returns `Error::Unsorted`
+/// instead of panicking if the keys are not strictly increasing.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::{Error, IntervalTree};
+///
+/// let res = IntervalTree::try_from_sorted_iter(vec![(Range::new(5,6),1), (Range::new(1,2),2)]);
+/// assert_eq!(res.unwrap_err(), Error::Unsorted{previous: Range::new(5,6), key: Range::new(1,2)});
+/// ```
+    pub fn try_from_sorted_iter<K: IntoRange, I: IntoIterator<Item=(K, D)>>(iter: I) -> Result<IntervalTree<D>, Error> {
+        let pairs = sorted_pairs(iter)?;
+        let len = pairs.len();
+        Ok(IntervalTree{root: from_sorted(len, &mut pairs.into_iter()), universe: None, balance: BalancePolicy::Strict, rotations: 0})
+    }
+
+/// This function will construct a perfectly balanced IntervalTree from pairs in arbitrary order in
+/// O(n log n). If a key occurs several times, the pair that comes last in pairs is kept, just as if
+/// all pairs had been inserted one after another.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let t=interval_tree::IntervalTree::from_vec(vec![(Range::new(5,6),1), (Range::new(1,2),2), (Range::new(5,6),3)]);
+/// assert_eq!(t.iter().map(|(_,v)| *v).collect::<Vec<i32>>(), vec![2,3]);
+/// ```
+    pub fn from_vec(mut pairs: Vec<(Range, D)>) -> IntervalTree<D>{
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut unique: Vec<(Range, D)> = Vec::with_capacity(pairs.len());
+        for (key, data) in pairs {
+            if unique.last().map_or(false, |&(ref last, _)| *last == key) {
+                unique.pop();
+            }
+            unique.push((key, data));
+        }
+        IntervalTree::from_sorted_iter(unique)
+    }
+
+/// This function will construct a new empty IntervalTree whose keys are restricted to universe.
+/// Inserted keys that reach outside of the universe are handled according to policy and all
+/// queries are clipped to the universe.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use interval_tree::UniversePolicy;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::with_universe(memrange::Range::new(10,20), UniversePolicy::Clamp);
+/// assert_eq!(t.clamp_insert(memrange::Range::new(5,12),25), Some(memrange::Range::new(10,12)));
+/// assert_eq!(t.clamp_insert(memrange::Range::new(30,40),25), None);
+/// assert_eq!(t.universe(), Some(memrange::Range::new(10,20)));
+/// ```
+    pub fn with_universe(universe: Range, policy: UniversePolicy) -> IntervalTree<D>{
+        IntervalTree{root: None, universe: Some((universe, policy)), balance: BalancePolicy::Strict, rotations: 0}
+    }
+
+}
+
+impl<D, A: Aggregate<D>> IntervalTree<D, A>{
+
+/// This function will construct a new empty IntervalTree that maintains the aggregate A for every
+/// subtree. The argument only serves to name the aggregate, see `Aggregate`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use interval_tree::aggregate::Sum;
+///
+/// let mut t=interval_tree::IntervalTree::with_aggregate(Sum);
+/// t.insert(memrange::Range::new(2,8),25u64);
+/// t.insert(memrange::Range::new(5,6),50u64);
+/// assert_eq!(t.aggregate(..), 75);
+/// ```
+    pub fn with_aggregate(_aggregate: A) -> IntervalTree<D, A>{
+        IntervalTree{root: None, universe: None, balance: BalancePolicy::Strict, rotations: 0}
+    }
+
+/// This function will return the universe the keys of this tree are restricted to, if any.
+    pub fn universe(&self) -> Option<Range> {
+        self.universe.map(|(range, _)| range)
+    }
+
+/// This function will return the part of the query [min, max] that lies within the universe, or
+/// None if the query is completely outside of it.
+    pub fn clip_query(&self, min: u64, max: u64) -> Option<Range> {
+        let query = Range::new(min, max);
+        match self.universe {
+            Some((universe, _)) if !universe.intersect(&query) => None,
+            Some((universe, _)) => Some(universe.get_intersection(&query)),
+            None => Some(query)
+        }
     }
 
 /// This function will insert the key,value pair into the tree, overwriting the old data if the key is allready
-/// part of the tree.
+/// part of the tree. If the tree was created with a universe, the key is handled as in
+/// `clamp_insert`. Like all functions taking a key, this accepts a `Range` as well as the std
+/// range types (see `IntoRange`); empty keys such as `5..5` are not inserted.
 /// # Examples
 /// ```
 /// extern crate memrange;
@@ -37,12 +401,103 @@ impl <D> IntervalTree<D>{
 /// assert_eq!(t.get(memrange::Range::new(2,2)), Some(&25));
 /// t.insert(memrange::Range::new(2,2),30);
 /// assert_eq!(t.get(memrange::Range::new(2,2)), Some(&30));
+/// t.insert(3..10,40);
+/// assert_eq!(t.get(memrange::Range::new(3,9)), Some(&40));
+/// assert_eq!(t.get(3..=9), Some(&40));
 /// ```
-    pub fn insert(&mut self, key: Range, data: D) {
+    pub fn insert<K: IntoRange>(&mut self, key: K, data: D) {
+        self.clamp_insert(key, data);
+    }
+
+/// This function will insert the key,value pair into the tree like `insert`, but first applies
+/// the universe policy of the tree to the key. It returns the key that was actually stored or
+/// None if the key was rejected. Trees without universe store every key unchanged.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use interval_tree::UniversePolicy;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::with_universe(memrange::Range::new(10,20), UniversePolicy::Reject);
+/// assert_eq!(t.clamp_insert(memrange::Range::new(5,12),25), None);
+/// assert_eq!(t.clamp_insert(memrange::Range::new(11,12),25), Some(memrange::Range::new(11,12)));
+/// ```
+    pub fn clamp_insert<K: IntoRange>(&mut self, key: K, data: D) -> Option<Range> {
+        let key = match key.into_range().and_then(|key| self.fit_universe(key)) { Some(key) => key, None => return None };
+        self.insert_fitted(key, data, |_, new| new);
+        Some(key)
+    }
+
+    /// inserts key, which has to lie within the universe, merging its data like `insert_with`
+    fn insert_fitted<F: FnOnce(D, D) -> D>(&mut self, key: Range, data: D, merge: F) {
         match self.root.take() {
-            Some(box_to_node) => self.root = Some(insert::<D>(key, data, box_to_node)),
+            Some(box_to_node) => self.root = Some(insert_with(key, data, box_to_node, merge, &mut self.rotations)),
             None => self.root = Some(Box::new(Node::new(key,data))),
         }
+        self.rebalance_if_degraded();
+    }
+
+    /// returns the part of key that is inserted under the universe of the tree, or None if key is rejected
+    fn fit_universe(&self, key: Range) -> Option<Range> {
+        match self.universe {
+            Some((universe, _)) if !universe.intersect(&key) => None,
+            Some((universe, UniversePolicy::Reject)) if universe.get_intersection(&key) != key => None,
+            Some((universe, _)) => Some(universe.get_intersection(&key)),
+            None => Some(key)
+        }
+    }
+
+/// This function will insert the key,value pair like `insert`, but only if the key does not
+/// overlap any key that is already part of the tree. Otherwise the tree is left unchanged and the
+/// lowest overlapping key is returned in `Error::Overlap`. This keeps trees that must never contain
+/// overlapping keys, such as memory maps, free of silently coexisting entries. Keys that would not
+/// be inserted at all are reported as well: empty keys with `Error::EmptyKey`, keys rejected by the
+/// universe of the tree with `Error::OutsideUniverse`. With `UniversePolicy::Clamp`, the clamped key
+/// is checked for overlaps.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// assert!(t.try_insert(Range::new(0,9),1).is_ok());
+/// let err = t.try_insert(Range::new(5,20),2).unwrap_err();
+/// assert_eq!(err, interval_tree::Error::Overlap(interval_tree::OverlapError{existing: Range::new(0,9)}));
+/// assert!(t.try_insert(Range::new(10,20),2).is_ok());
+/// assert_eq!(t.try_insert(30..30,3), Err(interval_tree::Error::EmptyKey));
+/// ```
+    pub fn try_insert<K: IntoRange>(&mut self, key: K, data: D) -> Result<(), Error> {
+        let requested = match key.into_range() { Some(key) => key, None => return Err(Error::EmptyKey) };
+        let key = match self.fit_universe(requested) { Some(key) => key, None => return Err(Error::OutsideUniverse{key: requested}) };
+        if let Some((existing, _)) = self.first_overlap(key) {
+            return Err(Error::Overlap(OverlapError{existing: *existing}));
+        }
+        self.clamp_insert(key, data);
+        Ok(())
+    }
+
+/// This function will insert the key,value pair like `insert`, but if the key is already part of
+/// the tree, the stored data and data are combined with merge(old, new) instead of the new data
+/// replacing the old one. The universe policy is applied first, so a clamped key merges with the
+/// data already stored under the clamped key. This takes a single descent of O(log n) and keeps
+/// cached aggregates up to date.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<Vec<&str>>::new();
+/// for &(start, tag) in [(0, "a"), (10, "b"), (0, "c")].iter() {
+///     t.insert_with(Range::new(start, start+9), vec![tag], |mut old, new| { old.extend(new); old });
+/// }
+/// assert_eq!(t.get(Range::new(0,9)), Some(&vec!["a", "c"]));
+/// assert_eq!(t.get(Range::new(10,19)), Some(&vec!["b"]));
+/// ```
+    pub fn insert_with<K: IntoRange, F: FnOnce(D, D) -> D>(&mut self, key: K, data: D, merge: F) {
+        let key = match key.into_range().and_then(|key| self.fit_universe(key)) { Some(key) => key, None => return };
+        self.insert_fitted(key, data, merge);
     }
 
 /// This function will remove the key,value pair from the tree, doing nothing if the key is not
@@ -60,174 +515,1809 @@ impl <D> IntervalTree<D>{
 /// t.delete(memrange::Range::new(3,3)); 
 /// assert!(t.empty());
 /// ```
-    pub fn delete(&mut self, key: Range){
-        match self.root.take() {
-            Some(box_to_node) => self.root = delete(key,box_to_node),
-            None => return
-        }
+    pub fn delete<K: IntoRange>(&mut self, key: K){
+        self.remove_entry(key);
     }
 
-/// This function will return the Some(data) stored under the given key or None if the key is not
-/// known.
+/// This function will remove the key,value pair from the tree and return it, or None if the key is
+/// not part of the tree.
 /// # Examples
 /// ```
 /// extern crate memrange;
 /// extern crate interval_tree;
+/// use memrange::Range;
 ///
 /// let mut t=interval_tree::IntervalTree::<i32>::new();
-/// t.insert(memrange::Range::new(2,2),25);
-/// assert_eq!(t.get(memrange::Range::new(2,2)), Some(&25));
-/// assert_eq!(t.get(memrange::Range::new(3,3)), None);
-///
+/// t.insert(Range::new(2,2),25);
+/// assert_eq!(t.remove_entry(2..=2), Some((Range::new(2,2),25)));
+/// assert_eq!(t.remove_entry(2..=2), None);
 /// ```
-    pub fn get(&self, key: Range) -> Option<&D>{
-        match self.root {
-            Some(ref box_to_node) =>search(&key, box_to_node),
-            None => None
-        }
+    pub fn remove_entry<K: IntoRange>(&mut self, key: K) -> Option<(Range, D)> {
+        let key = match key.into_range() { Some(key) => key, None => return None };
+        let root = match self.root.take() { Some(root) => root, None => return None };
+        let (root, removed) = match self.balance {
+            BalancePolicy::Strict => remove(&key, root, &mut self.rotations),
+            BalancePolicy::Relaxed(_) => remove_relaxed(&key, root, &mut self.rotations)
+        };
+        self.root = root;
+        self.rebalance_if_degraded();
+        removed
     }
 
-/// This function will return the data stored under the given key or the default if the key is not
-/// known.
+/// This function will remove all key,value pairs whose keys intersect the given range from the
+/// tree and return them in ascending order of their keys. Every removed pair costs O(log n).
 /// # Examples
 /// ```
 /// extern crate memrange;
 /// extern crate interval_tree;
+/// use memrange::Range;
 ///
 /// let mut t=interval_tree::IntervalTree::<i32>::new();
-/// t.insert(memrange::Range::new(2,2),25);
-/// assert_eq!(t.get_or(memrange::Range::new(2,2),&2000), &25);
-/// assert_eq!(t.get_or(memrange::Range::new(3,3),&2000), &2000);
-///
+/// t.insert(Range::new(2,8),25);
+/// t.insert(Range::new(5,6),50);
+/// t.insert(Range::new(12,14),75);
+/// assert_eq!(t.delete_range(Range::new(7,12)), vec![(Range::new(2,8),25), (Range::new(12,14),75)]);
+/// assert_eq!(t.iter().count(), 1);
 /// ```
-    pub fn get_or<'a>(&'a self, key: Range, default: &'a D) -> &D{
-        self.get(key).map_or(default, |data| data)
+    pub fn delete_range<K: IntoRange>(&mut self, key: K) -> Vec<(Range, D)> {
+        let mut removed = Vec::new();
+        let key = match key.into_range() { Some(key) => key, None => return removed };
+        loop {
+            let next = match self.root {
+                Some(ref root) => first_overlap(&key, root).map(|(k,_)| *k),
+                None => None
+            };
+            let next = match next { Some(next) => next, None => return removed };
+            removed.extend(self.remove_entry(next));
+        }
     }
 
-/// This function will return true if the tree contains the given key, false otherwise
+/// This function will cut key out of all stored keys: keys that lie completely within key are
+/// removed, keys reaching over one or both ends of key are trimmed (or split in two) so that they
+/// no longer intersect it and keep (a clone of) their data. The parts that were cut out are
+/// returned in ascending order together with the data of the key they were cut from.
 /// # Examples
 /// ```
 /// extern crate memrange;
 /// extern crate interval_tree;
+/// use memrange::Range;
 ///
-/// let mut t=interval_tree::IntervalTree::<i32>::new();
-/// t.insert(memrange::Range::new(2,2),25);
-/// assert!(!t.contains(memrange::Range::new(3,3)));
-/// assert!(t.contains(memrange::Range::new(2,2)));
-///
+/// let mut t=interval_tree::IntervalTree::<char>::new();
+/// t.insert(Range::new(0,20),'r');
+/// assert_eq!(t.carve(Range::new(5,9)), vec![(Range::new(5,9),'r')]);
+/// assert_eq!(t.iter().map(|(k,_)| *k).collect::<Vec<Range>>(), vec![Range::new(0,4), Range::new(10,20)]);
 /// ```
-    pub fn contains(&self, key: Range) -> bool {
-        self.get(key).is_some()
+    pub fn carve<K: IntoRange>(&mut self, key: K) -> Vec<(Range, D)> where D: Clone {
+        let key = match key.into_range() { Some(key) => key, None => return Vec::new() };
+        let removed = self.delete_range(key);
+        let mut cut = Vec::with_capacity(removed.len());
+        for (range, data) in removed {
+            if range.min < key.min { self.insert(Range::new(range.min, key.min-1), data.clone()) }
+            if range.max > key.max { self.insert(Range::new(key.max+1, range.max), data.clone()) }
+            cut.push((range.get_intersection(&key), data));
+        }
+        cut
     }
 
-/// This function will return true if the tree is empty, false otherwise.
+/// This function will insert the key,value pair after cutting key out of all stored keys as
+/// `carve` does, so that the inserted key does not intersect any other key. If the tree only holds
+/// non-intersecting keys, it still does so afterwards, which mirrors how mmap or mprotect update
+/// a map of memory regions.
 /// # Examples
 /// ```
 /// extern crate memrange;
 /// extern crate interval_tree;
-/// 
-/// let mut t=interval_tree::IntervalTree::<i32>::new();
-/// assert!(t.empty());
-/// t.insert(memrange::Range::new(2,2),25);
-/// assert!(!t.empty());
+/// use memrange::Range;
 ///
+/// let mut t=interval_tree::IntervalTree::<&str>::new();
+/// t.insert(Range::new(0,0xfff),"r-x");
+/// t.insert(Range::new(0x1000,0x1fff),"rw-");
+/// t.insert_replace_overlaps(Range::new(0x800,0x17ff),"r--");
+/// assert_eq!(t.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(),
+///            vec![(Range::new(0,0x7ff),"r-x"), (Range::new(0x800,0x17ff),"r--"), (Range::new(0x1800,0x1fff),"rw-")]);
 /// ```
-    pub fn empty(&self) -> bool { self.root.is_none() }
+    pub fn insert_replace_overlaps<K: IntoRange>(&mut self, key: K, data: D) where D: Clone {
+        let key = match key.into_range() { Some(key) => key, None => return };
+        self.carve(key);
+        self.insert(key, data);
+    }
 
-/// This function will return the key/value pair with the smallest key in the tree, or None if the
-/// tree is empty.
+/// This function will remove all key,value pairs for which f returns false. f is called once per
+/// pair in ascending order of the keys and may modify the data of pairs it keeps. The tree is
+/// filtered in a single pass without collecting keys.
 /// # Examples
 /// ```
 /// extern crate memrange;
 /// extern crate interval_tree;
-/// 
-/// let mut t=interval_tree::IntervalTree::<u64>::new();
-/// t.insert(memrange::Range::new(2,2),25);
-/// t.insert(memrange::Range::new(3,3),50);
-/// assert_eq!(t.min().unwrap().0, &memrange::Range::new(2,2));
-/// assert_eq!(t.min().unwrap().1, &25);
 ///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for i in 0..10 { t.insert(memrange::Range::new(i,i+2), i as i32) }
+/// t.retain(|_, data| { *data *= 10; *data % 20 == 0 });
+/// assert_eq!(t.iter().map(|(_,v)| *v).collect::<Vec<i32>>(), vec![0,20,40,60,80]);
 /// ```
-    pub fn min<'a>(&'a self) -> Option<(&'a Range,&'a D)> {
-        match self.root {
-            Some(ref root) => Some(min_pair(root)),
-            None => None
-        }
+    pub fn retain<F: FnMut(&Range, &mut D) -> bool>(&mut self, mut f: F) {
+        let root = self.root.take();
+        self.root = retain(root, &mut f, &mut self.rotations);
     }
 
-/// This function will return the key/value pair with the biggest key in the tree, or None if the
-/// tree is empty.
+/// This function will move every key of the tree by delta. As the order of the keys does not
+/// change, the nodes stay where they are and only their cached values are updated, which takes
+/// O(n) steps without any allocation. If a key would leave the u64 key space or the universe of
+/// the tree, nothing is changed and the lowest such key is returned in the error.
 /// # Examples
 /// ```
 /// extern crate memrange;
 /// extern crate interval_tree;
+/// use memrange::Range;
 ///
 /// let mut t=interval_tree::IntervalTree::<i32>::new();
-/// t.insert(memrange::Range::new(2,2),25);
-/// t.insert(memrange::Range::new(3,3),50);
-/// assert_eq!(t.max().unwrap().0, &memrange::Range::new(3,3));
-/// assert_eq!(t.max().unwrap().1, &50);
-///
+/// t.insert(Range::new(0x1000,0x1fff),1);
+/// t.insert(Range::new(0x3000,0x3fff),2);
+/// t.shift(0x7f00_0000).unwrap();
+/// assert_eq!(t.keys().cloned().collect::<Vec<Range>>(), vec![Range::new(0x7f00_1000,0x7f00_1fff), Range::new(0x7f00_3000,0x7f00_3fff)]);
+/// assert_eq!(t.shift(-0x7f00_2000).unwrap_err().key, Range::new(0x7f00_1000,0x7f00_1fff));
+/// assert_eq!(t.get(Range::new(0x7f00_1000,0x7f00_1fff)), Some(&1));
 /// ```
-    pub fn max<'a>(&'a self) -> Option<(&'a Range,&'a D)> {
-        match self.root {
-            Some(ref root) => Some(max_pair(root)),
-            None => None
+    pub fn shift(&mut self, delta: i64) -> Result<(), ShiftError> {
+        let (lowest, highest) = self.universe.map_or((0, u64::max_value()), |(universe, _)| (universe.min, universe.max));
+        let offset = delta.wrapping_abs() as u64;
+        let fits = |key: &Range| if delta < 0 { key.min.checked_sub(offset).map_or(false, |min| min >= lowest) }
+                                 else { key.max.checked_add(offset).map_or(false, |max| max <= highest) };
+        let extreme = match self.root {
+            Some(ref root) if delta < 0 => root.subtree_min_start(),
+            Some(ref root) => root.subtree_max_end(),
+            None => return Ok(())
+        };
+        if !fits(&Range::new(extreme, extreme)) {
+            let key = *self.keys().find(|key| !fits(key)).expect("the extreme end belongs to a key");
+            return Err(ShiftError{key: key, delta: delta})
         }
+        shift(&mut self.root, delta);
+        Ok(())
     }
 
-/// This function will return the hieght of the tree. An empty tree hash height 0, one with only
-/// one elemente has height 1 etc.
+/// This function will split the tree at the given point: all key,value pairs whose keys start at or
+/// after at are moved into the returned tree, the pairs starting before at stay in self. Both trees
+/// keep the universe of self. Splitting needs O(log n) steps.
 /// # Examples
 /// ```
 /// extern crate memrange;
 /// extern crate interval_tree;
 ///
 /// let mut t=interval_tree::IntervalTree::<i32>::new();
-/// assert_eq!(t.height(), 0);
-/// t.insert(memrange::Range::new(2,2),3);
-/// assert_eq!(t.height(), 1);
+/// for i in 0..10 { t.insert(memrange::Range::new(i*10,i*10+15), i as i32) }
+/// let upper = t.split_off(50);
+/// assert_eq!(t.iter().map(|(_,v)| *v).collect::<Vec<i32>>(), vec![0,1,2,3,4]);
+/// assert_eq!(upper.iter().map(|(_,v)| *v).collect::<Vec<i32>>(), vec![5,6,7,8,9]);
+/// ```
+    pub fn split_off(&mut self, at: u64) -> IntervalTree<D, A> {
+        let root = self.root.take();
+        let (before, after) = split(root, at, &mut self.rotations);
+        self.root = before;
+        IntervalTree{root: after, universe: self.universe, balance: self.balance, rotations: 0}
+    }
+
+/// This function will move all key,value pairs from other into self, leaving other empty. If a
+/// key is part of both trees, the data from other replaces the data in self. If all keys of one
+/// tree are smaller than those of the other, both are joined in O(log n); small trees are
+/// inserted pair by pair and otherwise both trees are merged and rebuilt in O(n+m). If self has a
+/// universe, every pair is inserted as by `insert`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
 ///
+/// let mut a=interval_tree::IntervalTree::<i32>::new();
+/// let mut b=interval_tree::IntervalTree::<i32>::new();
+/// a.insert(memrange::Range::new(2,8),25);
+/// a.insert(memrange::Range::new(5,6),50);
+/// b.insert(memrange::Range::new(5,6),75);
+/// b.insert(memrange::Range::new(1,3),100);
+/// a.append(&mut b);
+/// assert!(b.empty());
+/// assert_eq!(a.iter().map(|(_,v)| *v).collect::<Vec<i32>>(), vec![100,25,75]);
 /// ```
-    pub fn height(&self) -> usize {
-        height(&self.root) as usize
+    pub fn append(&mut self, other: &mut IntervalTree<D, A>) {
+        if other.empty() { return }
+        if self.universe.is_none() {
+            let other_min = other.min().map(|(k,_)| *k);
+            let other_max = other.max().map(|(k,_)| *k);
+            if self.max().map_or(true, |(k,_)| Some(*k) < other_min) {
+                let (l, r) = (self.root.take(), other.root.take());
+                self.root = join_two(l, r, &mut self.rotations);
+                return
+            }
+            if self.min().map_or(false, |(k,_)| Some(*k) > other_max) {
+                let (l, r) = (other.root.take(), self.root.take());
+                self.root = join_two(l, r, &mut self.rotations);
+                return
+            }
+        }
+        if self.insert_pairwise(other.len()) {
+            for (key, data) in other.drain() { self.insert(key, data) }
+            return
+        }
+        let mut merged: Vec<(Range, D)> = Vec::with_capacity(self.len() + other.len());
+        let mut mine = self.drain().peekable();
+        let mut theirs = other.drain().peekable();
+        loop {
+            let order = match (mine.peek(), theirs.peek()) {
+                (Some(a), Some(b)) => a.0.cmp(&b.0),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break
+            };
+            match order {
+                Ordering::Less => merged.extend(mine.next()),
+                Ordering::Equal => { mine.next(); merged.extend(theirs.next()) },
+                Ordering::Greater => merged.extend(theirs.next())
+            }
+        }
+        let len = merged.len();
+        self.root = from_sorted(len, &mut merged.into_iter());
     }
 
-/// This function will return a read only iterator for all (key,value) pairs in the tree.
+/// This function will insert a run of pairs sorted by strictly increasing keys, as by `append`:
+/// the run is built into a balanced tree in O(k) and then joined with self if all its keys are
+/// smaller or bigger than the existing ones, merged with the tree in a single O(n+k) rebuild if it
+/// is large, and only inserted pair by pair if it is small compared to the tree. Data of keys that
+/// are already part of the tree is replaced. Empty keys are skipped.
+///
+/// # Panics
+/// Panics if the keys are not strictly increasing.
 /// # Examples
 /// ```
-/// # let mut t=interval_tree::IntervalTree::<i32>::new();
-/// for (key,val) in t.iter() {
-///     println!("{:?} -> {}",key,val)
-/// }
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
 ///
+/// let mut t=interval_tree::IntervalTree::<u32>::new();
+/// t.extend_sorted((0..1000).map(|i| (Range::new(i*10, i*10+5), 0)));
+/// t.extend_sorted((0..2000).map(|i| (Range::new(i*5, i*5+1), 1)));
+/// assert_eq!(t.len(), 3000);
+/// assert_eq!(t.get(Range::new(10,15)), Some(&0));
 /// ```
-    pub fn iter(&self) -> RangePairIter<D>{
-        RangePairIter::new(self, 0, 0xffff_ffff_ffff_ffff)
+    pub fn extend_sorted<K: IntoRange, I: IntoIterator<Item=(K, D)>>(&mut self, iter: I) {
+        if let Err(e) = self.try_extend_sorted(iter) { panic!("extend_sorted: {}", e) }
+    }
+
+/// This function will insert a sorted run like `extend_sorted`, but returns `Error::Unsorted`
+/// instead of panicking if the keys are not strictly increasing. The tree is left unchanged then.
+    pub fn try_extend_sorted<K: IntoRange, I: IntoIterator<Item=(K, D)>>(&mut self, iter: I) -> Result<(), Error> {
+        let pairs = sorted_pairs(iter)?;
+        let disjoint = match (pairs.first(), pairs.last()) {
+            (Some(first), Some(last)) => self.universe.is_none() &&
+                (self.max().map_or(true, |(k,_)| *k < first.0) || self.min().map_or(false, |(k,_)| *k > last.0)),
+            _ => return Ok(())
+        };
+        //a run that append would insert pair by pair is not built into a tree first
+        if !disjoint && self.insert_pairwise(pairs.len()) {
+            for (key, data) in pairs { self.insert(key, data) }
+            return Ok(())
+        }
+        let len = pairs.len();
+        let mut run = IntervalTree{root: from_sorted(len, &mut pairs.into_iter()), universe: None, balance: self.balance, rotations: 0};
+        self.append(&mut run);
+        Ok(())
+    }
+
+    //true if inserting other_len pairs one by one is cheaper than merging them with the tree in a
+    //rebuild, or if the universe has to see every pair
+    fn insert_pairwise(&self, other_len: usize) -> bool {
+        let total = self.len() + other_len;
+        let log = 64 - (total as u64).leading_zeros() as usize;
+        self.universe.is_some() || other_len * log < total
     }
 
-/// This function will return a read only iterator for all (key,value) pairs between the two bounds (which can
-/// be inclusive, exclusive or unbounded).
+/// This function will return the Some(data) stored under the given key or None if the key is not
+/// known.
 /// # Examples
 /// ```
-/// //[...]
-/// # let mut t=interval_tree::IntervalTree::<i32>::new();
-/// for (key,val) in t.range(9, 100) {
-///     println!("{:?} -> {}",key,val)
-/// }
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,2),25);
+/// assert_eq!(t.get(memrange::Range::new(2,2)), Some(&25));
+/// assert_eq!(t.get(memrange::Range::new(3,3)), None);
 ///
 /// ```
-    pub fn range(&self, min: u64, max: u64) -> RangePairIter<D>{
-        RangePairIter::new(self, min, max)
+    pub fn get<K: IntoRange>(&self, key: K) -> Option<&D>{
+        match (&self.root, key.into_range()) {
+            (&Some(ref box_to_node), Some(key)) =>search(&key, box_to_node),
+            _ => None
+        }
     }
 
+/// This function will return the data stored under the given key like `get`, or
+/// `Error::KeyNotFound` if the key is not part of the tree. It is the non-panicking counterpart of
+/// indexing with `t[key]`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::Error;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(2,2),25);
+/// assert_eq!(t.try_get(2..=2), Ok(&25));
+/// assert_eq!(t.try_get(3..=3), Err(Error::KeyNotFound));
+/// ```
+    pub fn try_get<K: IntoRange>(&self, key: K) -> Result<&D, Error> {
+        self.get(key).ok_or(Error::KeyNotFound)
+    }
+
+/// This function will return the stored key together with its data if the key is part of the
+/// tree, or None otherwise. This yields the canonical stored `Range` when the lookup was done with
+/// a probe of another type, such as a std range or a tuple.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,9),25);
+/// assert_eq!(t.get_key_value(2..10), Some((&memrange::Range::new(2,9), &25)));
+/// assert_eq!(t.get_key_value(2..9), None);
+///
+/// ```
+    pub fn get_key_value<'a, K: IntoRange>(&'a self, key: K) -> Option<(&'a Range,&'a D)>{
+        match (&self.root, key.into_range()) {
+            (&Some(ref box_to_node), Some(key)) => search_pair(&key, box_to_node),
+            _ => None
+        }
+    }
+
+/// This function will return the data stored under the given key or the default if the key is not
+/// known.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,2),25);
+/// assert_eq!(t.get_or(memrange::Range::new(2,2),&2000), &25);
+/// assert_eq!(t.get_or(memrange::Range::new(3,3),&2000), &2000);
+///
+/// ```
+    pub fn get_or<'a, K: IntoRange>(&'a self, key: K, default: &'a D) -> &D{
+        self.get(key).map_or(default, |data| data)
+    }
+
+/// This function will return a clone of the data stored under the given key or `D::default()` if
+/// the key is not known, e.g. zero for counters.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<u32>::new();
+/// t.insert(memrange::Range::new(2,2),25);
+/// assert_eq!(t.get_or_default(memrange::Range::new(2,2)), 25);
+/// assert_eq!(t.get_or_default(memrange::Range::new(3,3)), 0);
+/// ```
+    pub fn get_or_default<K: IntoRange>(&self, key: K) -> D where D: Default + Clone {
+        self.get(key).cloned().unwrap_or_default()
+    }
+
+/// This function will return true if the tree contains the given key, false otherwise
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,2),25);
+/// assert!(!t.contains(memrange::Range::new(3,3)));
+/// assert!(t.contains(memrange::Range::new(2,2)));
+///
+/// ```
+    pub fn contains<K: IntoRange>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+/// This function will return the pair with the lowest key that contains the point p, or None
+/// if p is not covered by any key. Use `range(p..=p)` to visit all keys containing p.
+/// # Examples
+/// ```
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert((2,8),25);
+/// t.insert((5,10),50);
+/// assert_eq!(t.get_at_point(6).map(|(_,v)| *v), Some(25));
+/// assert_eq!(t.get_at_point(9).map(|(_,v)| *v), Some(50));
+/// assert_eq!(t.get_at_point(11), None);
+/// assert_eq!(t.get((5,10)), Some(&50));
+/// ```
+    pub fn get_at_point<'a>(&'a self, p: u64) -> Option<(&'a Range,&'a D)> {
+        self.first_overlap(p..=p)
+    }
+
+/// This function will return true if the point p is covered by at least one key.
+    pub fn contains_point(&self, p: u64) -> bool {
+        self.get_at_point(p).is_some()
+    }
+
+/// This function will return the number of keys in the tree in O(1).
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,8),25);
+/// t.insert(memrange::Range::new(2,8),50);
+/// t.insert(memrange::Range::new(5,6),75);
+/// assert_eq!(t.len(), 2);
+/// ```
+    pub fn len(&self) -> usize { size(&self.root) }
+
+/// This function will return the bytes used by the nodes of the tree in O(1). Memory that the
+/// data owns on the heap, such as the buffer of a `String`, is not included, see `memory_stats`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<u64>::new();
+/// for i in 0..100 { t.insert(memrange::Range::new(i,i), i) }
+/// assert_eq!(t.memory_usage(), 100 * t.memory_stats(|_| 0).node_size);
+/// ```
+    pub fn memory_usage(&self) -> usize {
+        self.len() * mem::size_of::<Node<D, A>>()
+    }
+
+/// This function will return a breakdown of the memory used by the tree. heap_size is called once
+/// per stored data and returns the heap memory it owns, which the tree cannot know by itself.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<String>::new();
+/// t.insert(memrange::Range::new(0,9), "text".to_string());
+/// t.insert(memrange::Range::new(10,19), "data".to_string());
+/// let usage = t.memory_stats(|s| s.capacity());
+/// assert_eq!(usage.nodes, 2);
+/// assert_eq!(usage.data_heap, 8);
+/// assert_eq!(usage.total(), t.memory_usage() + 8);
+/// ```
+    pub fn memory_stats<F: FnMut(&D) -> usize>(&self, mut heap_size: F) -> MemoryUsage {
+        MemoryUsage{
+            nodes: self.len(),
+            node_size: mem::size_of::<Node<D, A>>(),
+            data_size: mem::size_of::<D>(),
+            data_heap: self.values().map(|data| heap_size(data)).sum(),
+        }
+    }
+
+/// This function will return the pair with the k-th smallest key, counting from 0, or None if the
+/// tree holds no more than k keys. This takes O(log n).
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for i in 0..100 { t.insert(Range::new(i*10, i*10+9), i as i32) }
+/// assert_eq!(t.select(0).map(|(_,v)| *v), Some(0));
+/// assert_eq!(t.select(t.len()/2).map(|(k,_)| *k), Some(Range::new(500,509)));
+/// assert_eq!(t.select(100), None);
+/// ```
+    pub fn select<'a>(&'a self, k: usize) -> Option<(&'a Range,&'a D)> {
+        self.root.as_ref().and_then(|root| select(k, root))
+    }
+
+/// This function will return a random stored pair, where every pair is equally likely to be
+/// picked. Each call of rng has to return a uniformly random u64; it is called once per sample.
+/// This takes O(log n). Returns None if the tree is empty.
+    pub fn sample<'a, R: FnMut() -> u64>(&'a self, mut rng: R) -> Option<(&'a Range,&'a D)> {
+        if self.empty() { return None }
+        self.select((rng() % self.len() as u64) as usize)
+    }
+
+/// This function will return how many stored keys are smaller than key, i.e. the position key
+/// has or would have in ascending order. Keys are ordered by their start and then by their end.
+/// This takes O(log n) and is the inverse of `select` for stored keys.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for i in 0..100 { t.insert(Range::new(i*10, i*10+9), i as i32) }
+/// assert_eq!(t.rank(Range::new(500,509)), 50);
+/// assert_eq!(t.rank(Range::new(501,501)), 51);
+/// assert_eq!(t.rank(Range::new(0,0)), 0);
+/// ```
+    pub fn rank<K: IntoRange>(&self, key: K) -> usize {
+        match key.into_range() {
+            Some(key) => rank(&key, &self.root),
+            None => 0
+        }
+    }
+
+/// This function will return the stored pair whose key is closest to the point p together with
+/// their distance, which is p - key.max for keys before p and key.min - p for keys after p. Keys
+/// containing p have distance 0. If a key before and a key after p are equally close, the one
+/// before p is returned. This takes O(log n).
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,9),1);
+/// t.insert(Range::new(2,3),2);
+/// t.insert(Range::new(30,40),3);
+/// assert_eq!(t.nearest_interval(5), Some((&Range::new(0,9), &1, 0)));
+/// assert_eq!(t.nearest_interval(12), Some((&Range::new(0,9), &1, 3)));
+/// assert_eq!(t.nearest_interval(25), Some((&Range::new(30,40), &3, 5)));
+/// ```
+    pub fn nearest_interval<'a>(&'a self, p: u64) -> Option<(&'a Range, &'a D, u64)> {
+        let root = match self.root { Some(ref root) => root, None => return None };
+        let before = max_end_starting_before(p, root).map(|(k, v)| (k, v, p.saturating_sub(k.max)));
+        let after = min_starting_after(p, root).map(|(k, v)| (k, v, k.min - p));
+        match (before, after) {
+            (Some(b), Some(a)) => Some(if a.2 < b.2 { a } else { b }),
+            (b, None) => b,
+            (None, a) => a
+        }
+    }
+
+/// This function will return an iterator over all pairs in increasing distance of their keys to
+/// the point p, yielding the distance as in `nearest_interval` with every pair. Keys at the same
+/// distance are yielded in ascending order of their start. The tree is searched best first, so
+/// taking the first k pairs only visits O(k log n) nodes in typical trees.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,9),1);
+/// t.insert(Range::new(14,20),2);
+/// t.insert(Range::new(30,40),3);
+/// let near = t.nearest(12).map(|(_,v,d)| (*v,d)).collect::<Vec<_>>();
+/// assert_eq!(near, vec![(2,2), (1,3), (3,18)]);
+/// ```
+    pub fn nearest(&self, p: u64) -> NearestIter<D, A>{
+        NearestIter::new(self, p)
+    }
+
+/// This function will return an iterator over the (at most) k pairs whose keys are closest to the
+/// point p, in increasing distance, see `nearest`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for i in 0..100 { t.insert(Range::new(i*10, i*10+4), i as i32) }
+/// assert_eq!(t.k_nearest(503, 3).map(|(_,v,_)| *v).collect::<Vec<i32>>(), vec![50,51,49]);
+/// ```
+    pub fn k_nearest(&self, p: u64, k: usize) -> iter::Take<NearestIter<D, A>>{
+        self.nearest(p).take(k)
+    }
+
+/// This function will return true if the tree is empty, false otherwise.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// 
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// assert!(t.empty());
+/// t.insert(memrange::Range::new(2,2),25);
+/// assert!(!t.empty());
+///
+/// ```
+    pub fn empty(&self) -> bool { self.root.is_none() }
+
+/// This function will return the key/value pair with the smallest key in the tree, or None if the
+/// tree is empty.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// 
+/// let mut t=interval_tree::IntervalTree::<u64>::new();
+/// t.insert(memrange::Range::new(2,2),25);
+/// t.insert(memrange::Range::new(3,3),50);
+/// assert_eq!(t.min().unwrap().0, &memrange::Range::new(2,2));
+/// assert_eq!(t.min().unwrap().1, &25);
+///
+/// ```
+    pub fn min<'a>(&'a self) -> Option<(&'a Range,&'a D)> {
+        match self.root {
+            Some(ref root) => Some(min_pair(root)),
+            None => None
+        }
+    }
+
+/// This function will return the key/value pair with the biggest key in the tree, or None if the
+/// tree is empty.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,2),25);
+/// t.insert(memrange::Range::new(3,3),50);
+/// assert_eq!(t.max().unwrap().0, &memrange::Range::new(3,3));
+/// assert_eq!(t.max().unwrap().1, &50);
+///
+/// ```
+    pub fn max<'a>(&'a self) -> Option<(&'a Range,&'a D)> {
+        match self.root {
+            Some(ref root) => Some(max_pair(root)),
+            None => None
+        }
+    }
+
+/// This function will return the key/value pair with the smallest key that intersects the given
+/// range, or None if no stored key intersects it.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,8),25);
+/// t.insert(memrange::Range::new(5,6),50);
+/// t.insert(memrange::Range::new(12,14),75);
+/// assert_eq!(t.first_overlap(memrange::Range::new(6,13)).unwrap().0, &memrange::Range::new(2,8));
+/// assert_eq!(t.first_overlap(memrange::Range::new(9,11)), None);
+///
+/// ```
+    pub fn first_overlap<'a, K: IntoRange>(&'a self, key: K) -> Option<(&'a Range,&'a D)> {
+        match (&self.root, key.into_range().and_then(|key| self.clip_query(key.min, key.max))) {
+            (&Some(ref root), Some(key)) => first_overlap(&key, root),
+            _ => None
+        }
+    }
+
+/// This function will return the key/value pair with the biggest key that intersects the given
+/// range, or None if no stored key intersects it.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,8),25);
+/// t.insert(memrange::Range::new(5,6),50);
+/// t.insert(memrange::Range::new(12,14),75);
+/// assert_eq!(t.last_overlap(memrange::Range::new(3,7)).unwrap().0, &memrange::Range::new(5,6));
+/// assert_eq!(t.last_overlap(memrange::Range::new(15,20)), None);
+/// assert_eq!(t.last_overlap(..).unwrap().1, &75);
+///
+/// ```
+    pub fn last_overlap<'a, K: IntoRange>(&'a self, key: K) -> Option<(&'a Range,&'a D)> {
+        match (&self.root, key.into_range().and_then(|key| self.clip_query(key.min, key.max))) {
+            (&Some(ref root), Some(key)) => last_overlap(&key, root),
+            _ => None
+        }
+    }
+
+/// This function will rebuild the tree into a perfectly balanced shape in O(n), so that its height
+/// is the minimal ceil(log2(n+1)). Inserts and deletes keep the tree balanced as AVL tree, whose
+/// height may be up to about 1.44 times the minimum; calling this during idle periods after heavy
+/// churn restores the optimal depth for subsequent queries.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<u64>::new();
+/// for i in 0..1000 { t.insert(memrange::Range::new(i,i), i) }
+/// for i in 0..1000 { if i % 3 != 0 { t.delete(memrange::Range::new(i,i)) } }
+/// t.rebalance();
+/// assert_eq!(t.height(), 9);
+/// assert_eq!(t.len(), 334);
+/// ```
+    pub fn rebalance(&mut self) {
+        let len = self.len();
+        self.root = from_sorted(len, &mut IntoIter::new(self.root.take()));
+    }
+
+/// This function will verify the structure of the tree: the keys have to be in ascending order,
+/// the subtrees of every node have to be balanced (unless a relaxed `BalancePolicy` is set) and the
+/// cached heights, maximal ends, aggregates and other subtree values have to be correct. The
+/// first violation found in ascending key order is returned. Intended for tests and debugging
+/// after changing the tree through its public `root`. Every node is checked, which takes O(n).
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::{Invariant, InvariantError};
+///
+/// let mut t=interval_tree::IntervalTree::new();
+/// for i in 0..10 { t.insert(Range::new(i*10,i*10+5), i) }
+/// assert_eq!(t.check_invariants(), Ok(()));
+/// t.root.as_mut().unwrap().key = Range::new(0,0);
+/// assert_eq!(t.check_invariants(), Err(InvariantError{invariant: Invariant::Ordering, key: Range::new(0,0)}));
+/// ```
+    pub fn check_invariants(&self) -> Result<(), InvariantError> {
+        let balanced = self.balance == BalancePolicy::Strict;
+        check_invariants(&self.root, balanced).map_err(|(invariant, key)| InvariantError{invariant: invariant, key: key})
+    }
+
+/// This function will return the balancing policy of the tree, `BalancePolicy::Strict` unless
+/// changed with `set_balance_policy`.
+    pub fn balance_policy(&self) -> BalancePolicy { self.balance }
+
+/// This function will change how the tree keeps itself balanced, see `BalancePolicy`. Switching
+/// to `BalancePolicy::Strict` rebuilds the tree, as relaxed deletes may have left it unbalanced.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use interval_tree::BalancePolicy;
+///
+/// let mut t=interval_tree::IntervalTree::<u64>::new();
+/// t.set_balance_policy(BalancePolicy::Relaxed(2));
+/// for i in 0..1000 { t.insert(memrange::Range::new(i,i), i) }
+/// for i in 0..990 { t.delete(memrange::Range::new(i,i)) }
+/// assert!(t.height() <= 2*4);
+/// t.set_balance_policy(BalancePolicy::Strict);
+/// assert_eq!(t.height(), 4);
+/// ```
+    pub fn set_balance_policy(&mut self, policy: BalancePolicy) {
+        self.balance = policy;
+        if policy == BalancePolicy::Strict { self.rebalance() } else { self.rebalance_if_degraded() }
+    }
+
+/// This function will return the shape of the tree and the number of rotations performed on it so
+/// far. Computing the average depth visits every node once.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<u64>::new();
+/// for i in 0..3 { t.insert(memrange::Range::new(i,i), i) }
+/// let stats = t.stats();
+/// assert_eq!((stats.len, stats.height, stats.min_height), (3, 2, 2));
+/// assert_eq!(stats.average_depth, 5.0/3.0);
+/// assert_eq!(stats.rotations, 1);
+/// ```
+    pub fn stats(&self) -> TreeStats {
+        let len = self.len();
+        TreeStats{
+            len: len,
+            height: self.height(),
+            min_height: (64 - (len as u64).leading_zeros()) as usize,
+            average_depth: if len == 0 { 0.0 } else { total_depth(&self.root, 1) as f64 / len as f64 },
+            rotations: self.rotations,
+        }
+    }
+
+    fn rebalance_if_degraded(&mut self) {
+        if let BalancePolicy::Relaxed(factor) = self.balance {
+            let minimal = 64 - (self.len() as u64).leading_zeros();
+            if height(&self.root) > cmp::max(factor, 2).saturating_mul(minimal) { self.rebalance() }
+        }
+    }
+
+/// This function will return the hieght of the tree. An empty tree hash height 0, one with only
+/// one elemente has height 1 etc.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// assert_eq!(t.height(), 0);
+/// t.insert(memrange::Range::new(2,2),3);
+/// assert_eq!(t.height(), 1);
+///
+/// ```
+    pub fn height(&self) -> usize {
+        height(&self.root) as usize
+    }
+
+/// This function will return the smallest range covering all keys of the tree, from the smallest
+/// start to the biggest end, or None if the tree is empty. The extremes are cached in the root, so
+/// this takes O(1) steps.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// assert_eq!(t.span(), None);
+/// t.insert(Range::new(2,100),1);
+/// t.insert(Range::new(5,10),2);
+/// t.insert(Range::new(50,200),3);
+/// assert_eq!(t.span(), Some(Range::new(2,200)));
+///
+/// ```
+    pub fn span(&self) -> Option<Range> {
+        self.root.as_ref().map(|root| Range::new(root.subtree_min_start(), root.subtree_max_end()))
+    }
+
+/// This function will call f with the depth (0 for the root), key and data of every node, each
+/// node before the nodes of its subtrees. If f returns `VisitControl::Prune`, the subtrees of the
+/// node are skipped; `VisitControl::Stop` ends the traversal. The shape of the tree depends on the
+/// order of the changes that built it, only the set of pairs is part of its contract.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::VisitControl;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for i in 0..7 { t.insert(Range::new(i,i), i as i32) }
+/// let mut top = Vec::new();
+/// t.visit_preorder(|depth, key, _| {
+///     top.push((depth, key.min));
+///     if depth == 1 { VisitControl::Prune } else { VisitControl::Continue }
+/// });
+/// assert_eq!(top, vec![(0,3), (1,1), (1,5)]);
+/// ```
+    pub fn visit_preorder<F: FnMut(usize, &Range, &D) -> VisitControl>(&self, mut f: F) {
+        visit_preorder(&self.root, 0, &mut f);
+    }
+
+/// This function will call f with the depth, key and data of every node in ascending order of
+/// the keys, like `visit_preorder`. Each node is visited after its left and before its right
+/// subtree, so `VisitControl::Prune` skips only the right subtree.
+    pub fn visit_inorder<F: FnMut(usize, &Range, &D) -> VisitControl>(&self, mut f: F) {
+        visit_inorder(&self.root, 0, &mut f);
+    }
+
+/// This function will call f with the depth, key and data of every node, each node after the
+/// nodes of its subtrees, like `visit_preorder`. As all nodes below have been visited already,
+/// `VisitControl::Prune` has the same effect as `VisitControl::Continue`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::VisitControl;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for i in 0..7 { t.insert(Range::new(i,i), i as i32) }
+/// let mut order = Vec::new();
+/// t.visit_postorder(|_, key, _| { order.push(key.min); VisitControl::Continue });
+/// assert_eq!(order, vec![0,2,1,4,6,5,3]);
+/// ```
+    pub fn visit_postorder<F: FnMut(usize, &Range, &D) -> VisitControl>(&self, mut f: F) {
+        visit_postorder(&self.root, 0, &mut f);
+    }
+
+/// This function will write the structure of the tree as graphviz digraph to writer. Every node
+/// is labeled with its key, the maximum endpoint of its subtree, its height and its balance (height
+/// of the left minus height of the right subtree).
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,8),25);
+/// let mut out = Vec::new();
+/// t.write_dot(&mut out).unwrap();
+/// assert!(String::from_utf8(out).unwrap().starts_with("digraph IntervalTree {"));
+///
+/// ```
+    pub fn write_dot<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "digraph IntervalTree {{")?;
+        writeln!(writer, "    node [shape=box];")?;
+        if let Some(ref root) = self.root {
+            write_dot(root, &mut 0, writer)?;
+        }
+        writeln!(writer, "}}")
+    }
+
+/// This function will return the structure of the tree as graphviz digraph, see `write_dot`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,8),25);
+/// t.insert(memrange::Range::new(5,6),50);
+/// assert!(t.to_dot().contains("n0 -> n1"));
+///
+/// ```
+    pub fn to_dot(&self) -> String {
+        let mut out = Vec::new();
+        self.write_dot(&mut out).expect("writing to a Vec can not fail");
+        String::from_utf8(out).expect("dot output is valid utf8")
+    }
+
+/// This function will render the tree sideways as text: the root is in the first column, right
+/// subtrees are printed above and left subtrees below their parent. Every node shows its key,
+/// the maximum endpoint of its subtree and its height.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,8),25);
+/// t.insert(memrange::Range::new(5,6),50);
+/// assert_eq!(t.format_tree(), "    [5, 6] max=6 h=1\n[2, 8] max=8 h=2\n");
+///
+/// ```
+    pub fn format_tree(&self) -> String {
+        let mut out = String::new();
+        if let Some(ref root) = self.root {
+            format_tree(root, 0, &mut out);
+        }
+        out
+    }
+
+/// This function will print the output of `format_tree` to stdout.
+    pub fn debug_print(&self) {
+        print!("{}", self.format_tree())
+    }
+
+/// This function will return an iterator that moves all (key,value) pairs out of the tree in
+/// ascending order of their keys. The tree is empty afterwards, even if the iterator is not
+/// consumed completely.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<String>::new();
+/// t.insert(memrange::Range::new(5,6),"b".to_string());
+/// t.insert(memrange::Range::new(2,8),"a".to_string());
+/// let vals = t.drain().map(|(_,v)| v).collect::<Vec<String>>();
+/// assert_eq!(vals, vec!["a".to_string(), "b".to_string()]);
+/// assert!(t.empty());
+///
+/// ```
+    pub fn drain(&mut self) -> IntoIter<D, A>{
+        IntoIter::new(self.root.take())
+    }
+
+/// This function will consume the tree and return all key,value pairs in ascending order of the
+/// keys, e.g. for a binary search table. The vector is allocated once with exactly the capacity for
+/// all pairs.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(5,6),2);
+/// t.insert(Range::new(2,8),1);
+/// let pairs = t.into_sorted_vec();
+/// assert_eq!(pairs, vec![(Range::new(2,8),1), (Range::new(5,6),2)]);
+/// assert_eq!(pairs.capacity(), 2);
+/// ```
+    pub fn into_sorted_vec(self) -> Vec<(Range, D)> {
+        let mut pairs = Vec::with_capacity(self.len());
+        pairs.extend(self);
+        pairs
+    }
+
+/// This function will return clones of all key,value pairs in ascending order of the keys in a
+/// vector with exactly the capacity for all pairs, see `into_sorted_vec`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(5,6),2);
+/// t.insert(Range::new(2,8),1);
+/// assert_eq!(t.to_vec(), vec![(Range::new(2,8),1), (Range::new(5,6),2)]);
+/// ```
+    pub fn to_vec(&self) -> Vec<(Range, D)> where D: Clone {
+        let mut pairs = Vec::with_capacity(self.len());
+        pairs.extend(self.iter().map(|(key, data)| (*key, data.clone())));
+        pairs
+    }
+
+/// This function will return a read only iterator for all (key,value) pairs in the tree.
+/// # Examples
+/// ```
+/// # let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for (key,val) in t.iter() {
+///     println!("{:?} -> {}",key,val)
+/// }
+///
+/// ```
+    pub fn iter(&self) -> RangePairIter<D, A>{
+        RangePairIter::new(self, 0, 0xffff_ffff_ffff_ffff)
+    }
+
+/// This function will return an iterator over all pairs of key,value pairs of self and other whose
+/// keys overlap, in ascending order of the start of the later starting key. Both trees are
+/// traversed once side by side, so this takes O(n + m + k) steps for k overlapping pairs instead of
+/// a range query for every key.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut reads=interval_tree::IntervalTree::new();
+/// let mut genes=interval_tree::IntervalTree::new();
+/// reads.insert(Range::new(0,10),"r1");
+/// reads.insert(Range::new(40,60),"r2");
+/// genes.insert(Range::new(5,50),"g1");
+/// genes.insert(Range::new(70,80),"g2");
+/// let pairs = reads.join(&genes).map(|((_, r), (_, g))| (*r, *g)).collect::<Vec<_>>();
+/// assert_eq!(pairs, vec![("r1","g1"), ("r2","g1")]);
+/// ```
+    pub fn join<'a, E, B: Aggregate<E>>(&'a self, other: &'a IntervalTree<E, B>) -> JoinIter<'a, D, E, A, B>{
+        JoinIter::new(self, other)
+    }
+
+/// This function will return an iterator over the differences between self and other in ascending
+/// order of the keys: keys that are only part of self, keys that are only part of other and keys
+/// that are part of both but store different data. Both trees are traversed once side by side.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::Change;
+///
+/// let mut a=interval_tree::IntervalTree::new();
+/// let mut b=interval_tree::IntervalTree::new();
+/// a.insert(Range::new(0,10),1);
+/// a.insert(Range::new(20,30),2);
+/// b.insert(Range::new(20,30),3);
+/// b.insert(Range::new(40,50),4);
+/// assert_eq!(a.diff(&b).collect::<Vec<_>>(), vec![
+///     Change::OnlyInSelf(&Range::new(0,10), &1),
+///     Change::Differs(&Range::new(20,30), &2, &3),
+///     Change::OnlyInOther(&Range::new(40,50), &4)]);
+/// ```
+    pub fn diff<'a>(&'a self, other: &'a IntervalTree<D, A>) -> DiffIter<'a, D, A> where D: PartialEq {
+        DiffIter::new(self, other)
+    }
+
+/// This function will return an iterator over all keys in ascending order.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(5,6),50);
+/// t.insert(memrange::Range::new(2,8),25);
+/// assert_eq!(t.keys().collect::<Vec<_>>(), vec![&memrange::Range::new(2,8), &memrange::Range::new(5,6)]);
+/// ```
+    pub fn keys(&self) -> Keys<D, A>{
+        Keys::new(self.iter())
+    }
+
+/// This function will return an iterator over all data in ascending order of the keys.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<u64>::new();
+/// t.insert(memrange::Range::new(5,6),50);
+/// t.insert(memrange::Range::new(2,8),25);
+/// assert_eq!(t.values().sum::<u64>(), 75);
+/// ```
+    pub fn values(&self) -> Values<D, A>{
+        Values::new(self.iter())
+    }
+
+/// This function will return a read only iterator for all (key,value) pairs whose key intersects the
+/// bounds (which can be inclusive, exclusive or unbounded, see `IntoRange`). Empty bounds such as
+/// `5..5` yield nothing.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,8),25);
+/// t.insert(memrange::Range::new(9,9),50);
+/// t.insert(memrange::Range::new(0xffff_ffff_ffff_ffff,0xffff_ffff_ffff_ffff),75);
+/// for (key,val) in t.range(9..100) {
+///     println!("{:?} -> {}",key,val)
+/// }
+/// assert_eq!(t.range(..9).count(), 1);
+/// assert_eq!(t.range(8..=9).count(), 2);
+/// assert_eq!(t.range(10..).map(|(_,v)| *v).collect::<Vec<i32>>(), vec![75]);
+/// assert_eq!(t.range(..).count(), 3);
+/// assert_eq!(t.range(9..9).count(), 0);
+///
+/// ```
+    pub fn range<K: IntoRange>(&self, bounds: K) -> RangePairIter<D, A>{
+        match bounds.into_range() {
+            Some(range) => RangePairIter::new(self, range.min, range.max),
+            None => RangePairIter::empty()
+        }
+    }
+
+/// This function will answer many overlap queries in a single traversal and return the result of
+/// every query at its index, each in ascending order of the keys like `range`. Instead of one
+/// descent from the root per query, every node is visited once for all queries that can reach its
+/// subtree, so the upper levels of the tree are shared between nearby queries. The queries are
+/// processed in ascending order of their starts, so the queries handed down to a subtree are
+/// only scanned up to the first one that starts after all keys of the subtree end.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for i in 0..100 { t.insert(Range::new(i*10, i*10+9), i as i32) }
+/// let queries = [Range::new(15,25), Range::new(2000,3000), Range::new(0,0)];
+/// let results = t.find_overlaps_batch(&queries);
+/// assert_eq!(results[0].iter().map(|&(_,v)| *v).collect::<Vec<_>>(), vec![1,2]);
+/// assert!(results[1].is_empty());
+/// assert_eq!(results[2].len(), 1);
+/// ```
+    pub fn find_overlaps_batch<'a>(&'a self, queries: &[Range]) -> Vec<Vec<(&'a Range, &'a D)>> {
+        let mut out = (0..queries.len()).map(|_| Vec::new()).collect::<Vec<_>>();
+        if let Some(ref root) = self.root {
+            //inverted queries are empty, like in `range`
+            let mut buf = (0..queries.len()).filter(|&q| queries[q].min <= queries[q].max).collect::<Vec<_>>();
+            buf.sort_by_key(|&q| queries[q].min);
+            let len = buf.len();
+            batch_overlaps(root, queries, &mut buf, 0, len, &mut out);
+        }
+        out
+    }
+
+/// This function will return an iterator over the start and end events of all keys that intersect
+/// the bounds, in ascending order of their positions. A key starts at key.min and ends at key.max,
+/// at the same position starts come before ends, as both keys cover that point. Events of keys
+/// that reach outside the bounds are reported at their original positions.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::Event;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,10),1);
+/// t.insert(Range::new(5,20),2);
+/// t.insert(Range::new(8,9),3);
+/// let mut depth = 0;
+/// let mut max_depth = 0;
+/// for event in t.events(..) {
+///     match event {
+///         Event::Start(..) => { depth += 1; max_depth = std::cmp::max(depth, max_depth) },
+///         Event::End(..) => depth -= 1,
+///     }
+/// }
+/// assert_eq!(max_depth, 3);
+/// assert_eq!(t.events(15..).map(|e| e.position()).collect::<Vec<u64>>(), vec![5,20]);
+/// ```
+    pub fn events<K: IntoRange>(&self, bounds: K) -> EventIter<D, A>{
+        EventIter::new(self.range(bounds))
+    }
+
+/// This function will return an iterator over the maximal ranges that are covered by the keys of
+/// the tree, in ascending order. Overlapping and adjacent keys are merged into one range, like the
+/// ranges of an `IntervalSet`, without changing the tree.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,4),1);
+/// t.insert(Range::new(2,8),2);
+/// t.insert(Range::new(9,10),3);
+/// t.insert(Range::new(20,30),4);
+/// assert_eq!(t.iter_coalesced().collect::<Vec<Range>>(), vec![Range::new(0,10), Range::new(20,30)]);
+/// ```
+    pub fn iter_coalesced(&self) -> Coalesced<D, A>{
+        Coalesced::new(self.iter())
+    }
+
+/// This function will return an iterator over the maximal ranges that are covered by the keys of
+/// the tree like `iter_coalesced`. The data of the keys of every range is folded with f, starting
+/// from a clone of init, in ascending order of the keys.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,4),1);
+/// t.insert(Range::new(2,8),2);
+/// t.insert(Range::new(20,30),4);
+/// assert_eq!(t.iter_coalesced_with(0, |sum, _, v| sum + v).collect::<Vec<_>>(),
+///            vec![(Range::new(0,8), 3), (Range::new(20,30), 4)]);
+/// ```
+    pub fn iter_coalesced_with<R: Clone, F: FnMut(R, &Range, &D) -> R>(&self, init: R, f: F) -> CoalescedWith<D, R, F, A>{
+        CoalescedWith::new(self.iter(), init, f)
+    }
+
+/// This function will return an iterator over all key,value pairs in ascending order of the ends
+/// of the keys, keys with the same end are ordered by their starts. The search front is kept in a
+/// heap and skips subtrees by their smallest end, so every step takes O(log n) time.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,30),1);
+/// t.insert(Range::new(5,10),2);
+/// t.insert(Range::new(8,20),3);
+/// assert_eq!(t.iter_by_end().map(|(_,v)| *v).collect::<Vec<i32>>(), vec![2,3,1]);
+/// ```
+    pub fn iter_by_end(&self) -> EndOrderIter<D, A>{
+        EndOrderIter::new(self, 0, 0xffff_ffff_ffff_ffff)
+    }
+
+/// This function will return an iterator over the key,value pairs whose keys end inside the bounds,
+/// in ascending order of the ends of the keys like `iter_by_end`. E.g. `range_by_end(..t)` yields
+/// all keys that end before t, the earliest first.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,30),1);
+/// t.insert(Range::new(5,10),2);
+/// t.insert(Range::new(8,20),3);
+/// assert_eq!(t.range_by_end(..20).map(|(_,v)| *v).collect::<Vec<i32>>(), vec![2]);
+/// assert_eq!(t.range_by_end(15..).map(|(_,v)| *v).collect::<Vec<i32>>(), vec![3,1]);
+/// ```
+    pub fn range_by_end<K: IntoRange>(&self, bounds: K) -> EndOrderIter<D, A>{
+        match bounds.into_range() {
+            Some(range) => EndOrderIter::new(self, range.min, range.max),
+            None => EndOrderIter::empty()
+        }
+    }
+
+
+/// This function will return an iterator over the maximal sub ranges of the bounds that are not
+/// covered by any key of the tree, in ascending order. Like all queries, the bounds are clipped
+/// to the universe of the tree, so values outside of it are never reported as gaps.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(2,8),25);
+/// t.insert(Range::new(5,10),50);
+/// t.insert(Range::new(15,20),75);
+/// assert_eq!(t.gaps(0..=30).collect::<Vec<Range>>(), vec![Range::new(0,1), Range::new(11,14), Range::new(21,30)]);
+/// assert_eq!(t.gaps(3..=9).count(), 0);
+///
+/// ```
+    pub fn gaps<K: IntoRange>(&self, bounds: K) -> GapIter<D, A>{
+        match bounds.into_range().and_then(|range| self.clip_query(range.min, range.max)) {
+            Some(range) => GapIter::new(self, range.min, range.max),
+            None => GapIter::empty()
+        }
+    }
+
+/// This function will return an iterator over the key,value pairs whose keys lie completely inside
+/// the bounds, in ascending order of the keys. Unlike `range`, keys that only partially overlap the
+/// bounds are left out. Subtrees that can not hold such a key are skipped.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(2,8),25);
+/// t.insert(Range::new(5,10),50);
+/// t.insert(Range::new(15,20),75);
+/// assert_eq!(t.find_contained(0..=10).map(|(_,v)| *v).collect::<Vec<i32>>(), vec![25, 50]);
+/// assert_eq!(t.find_contained(3..=20).map(|(_,v)| *v).collect::<Vec<i32>>(), vec![50, 75]);
+/// assert_eq!(t.range(3..=20).count(), 3);
+///
+/// ```
+    pub fn find_contained<K: IntoRange>(&self, bounds: K) -> ContainmentIter<D, A>{
+        match bounds.into_range() {
+            Some(range) => ContainmentIter::contained(self, range),
+            None => ContainmentIter::empty()
+        }
+    }
+
+/// This function will return an iterator over the key,value pairs whose keys completely cover the
+/// bounds, in ascending order of the keys. A single point can be queried as `p..=p`, which yields
+/// the same keys as `range(p..=p)`. Subtrees that can not hold such a key are skipped.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,0xfff),25);
+/// t.insert(Range::new(0x800,0x1fff),50);
+/// t.insert(Range::new(0x1000,0x1fff),75);
+/// assert_eq!(t.find_enclosing(0x900..0xa00).map(|(_,v)| *v).collect::<Vec<i32>>(), vec![25, 50]);
+/// assert_eq!(t.find_enclosing(0xf00..0x1100).map(|(_,v)| *v).collect::<Vec<i32>>(), vec![50]);
+/// assert_eq!(t.find_enclosing(0x1000..=0x1000).count(), 2);
+///
+/// ```
+    pub fn find_enclosing<K: IntoRange>(&self, bounds: K) -> ContainmentIter<D, A>{
+        match bounds.into_range() {
+            Some(range) => ContainmentIter::enclosing(self, range),
+            None => ContainmentIter::empty()
+        }
+    }
+
+/// This function will return the number of stored keys that intersect key without iterating
+/// over them. Every subtree caches its size together with its smallest end and biggest start, so
+/// whole subtrees whose keys all intersect key are counted at once. If no stored key contains
+/// another one, this takes O(log n); otherwise keys nested inside keys not intersecting the window
+/// may still have to be visited, up to O(n) of them. `CountingIntervalTree` keeps an index over
+/// the ends of the keys to count in O(log n) for nested keys as well.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for i in 0..100 { t.insert(Range::new(i*10, i*10+9), 0) }
+/// assert_eq!(t.count_in_range(Range::new(15,54)), 5);
+/// assert_eq!(t.count_in_range(..), 100);
+/// assert_eq!(t.count_in_range(1000..), 0);
+/// ```
+    pub fn count_in_range<K: IntoRange>(&self, key: K) -> usize {
+        match key.into_range() {
+            Some(key) => count_overlaps(&key, &self.root),
+            None => 0
+        }
+    }
+
+/// This function will return the aggregate A over all stored pairs whose keys intersect key,
+/// combined in ascending order of the keys. Like `count_in_range` this combines the cached
+/// aggregates of whole subtrees instead of visiting every pair; it takes O(log n) if no stored key
+/// contains another one.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+/// use interval_tree::aggregate::Max;
+///
+/// let mut t=interval_tree::IntervalTree::with_aggregate(Max);
+/// for i in 0..100u64 { t.insert(Range::new(i*10, i*10+9), (i*37)%101) }
+/// assert_eq!(t.aggregate(Range::new(0,25)), Some(74));
+/// assert_eq!(t.aggregate(Range::new(2000,3000)), None);
+/// ```
+    pub fn aggregate<K: IntoRange>(&self, key: K) -> A::Value {
+        match key.into_range() {
+            Some(key) => aggregate_overlaps(&key, &self.root),
+            None => A::empty()
+        }
+    }
+
+/// This function will return how many values of key are covered by at least one stored key.
+/// Values covered by several keys are only counted once. Since a window of all 2^64 values can
+/// not be counted in a u64, the result saturates at u64::MAX.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(2,8),25);
+/// t.insert(Range::new(5,10),50);
+/// t.insert(Range::new(15,20),75);
+/// assert_eq!(t.coverage(Range::new(0,16)), 11);
+///
+/// ```
+    pub fn coverage<K: IntoRange>(&self, key: K) -> u64 {
+        let key = match key.into_range() { Some(key) => key, None => return 0 };
+        let mut covered = 0u64;
+        let mut next = Some(key.min);
+        for (range, _) in self.range(key) {
+            let start = match next { Some(start) => cmp::max(start, range.min), None => break };
+            let end = cmp::min(range.max, key.max);
+            if end >= start {
+                covered = covered.saturating_add(end - start).saturating_add(1);
+                next = end.checked_add(1);
+            }
+        }
+        covered
+    }
+
+/// This function will return true if every value of key is covered by at least one stored key,
+/// possibly by several keys together. The check stops at the first uncovered value. Empty keys
+/// are always covered, and values outside of the universe of the tree are ignored (see `gaps`).
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(2,8),25);
+/// t.insert(Range::new(5,10),50);
+/// t.insert(Range::new(11,20),75);
+/// assert!(t.is_covered(Range::new(3,15)));
+/// assert!(!t.is_covered(Range::new(0,15)));
+/// assert!(!t.is_covered(15..=21));
+/// ```
+    pub fn is_covered<K: IntoRange>(&self, key: K) -> bool {
+        self.gaps(key).next().is_none()
+    }
+
+/// This function will return true if every key of other is covered by the keys of self, possibly
+/// by several keys together; the data of both trees is ignored. The keys of other are swept in
+/// ascending order together with the coalesced keys of self, which takes O(n + m) steps instead of
+/// one `is_covered` query per key of other. An empty other is always covered.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut actual=interval_tree::IntervalTree::<&str>::new();
+/// actual.insert(Range::new(0,0xfff),"a");
+/// actual.insert(Range::new(0x1000,0x2fff),"b");
+/// let mut required=interval_tree::IntervalTree::<()>::new();
+/// required.insert(Range::new(0x800,0x17ff),());
+/// required.insert(Range::new(0x2000,0x2fff),());
+/// assert!(actual.covers(&required));
+/// required.insert(Range::new(0x2f00,0x3000),());
+/// assert!(!actual.covers(&required));
+/// assert!(!actual.is_covered_by(&required));
+/// ```
+    pub fn covers<E, B: Aggregate<E>>(&self, other: &IntervalTree<E, B>) -> bool {
+        let mut covered = self.iter_coalesced().peekable();
+        for (key, _) in other.iter() {
+            //the keys of other start in ascending order, so coalesced ranges ending before a key are never needed again
+            while covered.peek().map_or(false, |range| range.max < key.min) { covered.next(); }
+            match covered.peek() {
+                Some(range) if range.min <= key.min && key.max <= range.max => {},
+                _ => return false
+            }
+        }
+        true
+    }
+
+/// This function will return true if every key of self is covered by the keys of other, see
+/// `covers`.
+    pub fn is_covered_by<E, B: Aggregate<E>>(&self, other: &IntervalTree<E, B>) -> bool {
+        other.covers(self)
+    }
+
+/// This function will return the first (lowest) range of size values within the given window that
+/// does not intersect any stored key, or None if there is no such range or size is 0. The search
+/// skips subtrees using gap information cached in the nodes, which makes it logarithmic if the
+/// stored keys do not intersect each other.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,9),1);
+/// t.insert(Range::new(12,19),2);
+/// t.insert(Range::new(30,39),3);
+/// assert_eq!(t.find_free_gap(2, Range::new(0,100)), Some(Range::new(10,11)));
+/// assert_eq!(t.find_free_gap(5, Range::new(0,100)), Some(Range::new(20,24)));
+/// assert_eq!(t.find_free_gap(80, Range::new(0,100)), None);
+///
+/// ```
+    pub fn find_free_gap<K: IntoRange>(&self, size: u64, within: K) -> Option<Range> {
+        let within = match within.into_range() { Some(within) => within, None => return None };
+        if size == 0 { return None }
+        let mut free = Some(within.min);
+        let start = match self.root {
+            Some(ref root) => find_gap(root, size, &mut free).or(free),
+            None => free
+        };
+        match start.and_then(|start| start.checked_add(size-1).map(|end| (start, end))) {
+            Some((start, end)) if end <= within.max => Some(Range::new(start, end)),
+            _ => None
+        }
+    }
+
+/// This function will return a read only iterator for all (key,value) pairs between the two
+/// bounds like `range`, yielding them in the given direction. Descending scans are as cheap as
+/// ascending ones.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use interval_tree::Direction;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,8),25);
+/// t.insert(memrange::Range::new(5,6),50);
+/// t.insert(memrange::Range::new(12,14),75);
+/// let vals = t.range_directed(0..=100, Direction::Descending).map(|(_,v)| *v).collect::<Vec<i32>>();
+/// assert_eq!(vals, vec![75,50,25]);
+///
+/// ```
+    pub fn range_directed<K: IntoRange>(&self, bounds: K, direction: Direction) -> RangePairIter<D, A>{
+        match bounds.into_range() {
+            Some(range) => RangePairIter::new_directed(self, range.min, range.max, direction),
+            None => RangePairIter::empty()
+        }
+    }
+
+/// This function will apply f to every key and return a tree of the mapped keys with the data of
+/// the original keys, e.g. to convert between coordinate systems. f has to be strictly monotonic:
+/// the mapped keys must be in the same order as the original keys and must not be empty. Then the
+/// tree is rebuilt perfectly balanced in O(n) and keeps its aggregate. Otherwise the lowest key
+/// that breaks the order is returned in the error, together with the unchanged tree. The new tree
+/// has no universe.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0x0,0xfff),1);
+/// t.insert(Range::new(0x1000,0x1fff),2);
+/// let mapped = t.map_keys(|key| Range::new(key.min*2 + 0x40_0000, key.max*2 + 0x40_0001)).unwrap();
+/// assert_eq!(mapped.get(Range::new(0x40_2000,0x40_3fff)), Some(&2));
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(Range::new(0,10),1);
+/// t.insert(Range::new(20,30),2);
+/// let err = t.map_keys(|key| Range::new(100-key.max, 100-key.min)).unwrap_err();
+/// assert_eq!(err.error.key, Range::new(20,30));
+/// assert_eq!(err.tree.len(), 2);
+/// ```
+    pub fn map_keys<F: Fn(Range) -> Range>(self, f: F) -> Result<IntervalTree<D, A>, MapKeysError<D, A>> {
+        let mut mapped: Vec<Range> = Vec::with_capacity(self.len());
+        let mut error = None;
+        for key in self.keys() {
+            let new = f(*key);
+            if new.min > new.max || mapped.last().map_or(false, |last| *last >= new) {
+                error = Some(KeyOrderError{key: *key, mapped: new});
+                break
+            }
+            mapped.push(new);
+        }
+        if let Some(error) = error { return Err(MapKeysError{tree: self, error: error}) }
+        let len = mapped.len();
+        let mut pairs = mapped.into_iter().zip(self.into_iter().map(|(_, data)| data));
+        Ok(IntervalTree{root: from_sorted(len, &mut pairs), universe: None, balance: BalancePolicy::Strict, rotations: 0})
+    }
+}
+
+impl<D> IntervalTree<D, LenSum>{
+
+/// This function will return a random stored pair, where every pair is picked with probability
+/// proportional to the length of its key, i.e. a uniformly random covered value is picked and a
+/// key containing it is returned (with overlapping keys, values covered twice count twice). Each
+/// call of rng has to return a uniformly random u64; it is called twice per sample. This takes
+/// O(log n) as the `LenSum` aggregate caches the total length of the keys for every subtree. Returns None if the tree
+/// is empty.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::with_aggregate(interval_tree::aggregate::LenSum);
+/// t.insert(Range::new(0x1000,0x1fff), "text");
+/// t.insert(Range::new(0x7fff_0000_0000,0x7fff_ffff_ffff), "heap");
+/// let mut state = 1u64;
+/// let mut rng = || { state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407); state };
+/// let heap = (0..100).filter(|_| *t.sample_weighted(&mut rng).unwrap().1 == "heap").count();
+/// assert!(heap > 90);
+/// ```
+    pub fn sample_weighted<'a, R: FnMut() -> u64>(&'a self, mut rng: R) -> Option<(&'a Range,&'a D)> {
+        let root = match self.root { Some(ref root) => root, None => return None };
+        let random = ((rng() as u128) << 64) | rng() as u128;
+        select_by_len(random % total_len(&self.root), root)
+    }
+}
+
+// Functions handing out mutable references to stored data are only offered without aggregate,
+// as changing the data would leave the cached aggregates stale.
+impl <D> IntervalTree<D>{
+
+/// This function will convert the data of every pair with f, which is called in ascending order of
+/// the keys. The keys and the shape of the tree stay the same, so nothing is compared or
+/// rebalanced. The new tree keeps the universe and balancing policy of self.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::IntervalTree::new();
+/// t.insert(Range::new(0,10),vec![1,2,3]);
+/// t.insert(Range::new(5,20),vec![4]);
+/// let lengths = t.map_values(|v| v.len());
+/// assert_eq!(lengths.values().cloned().collect::<Vec<usize>>(), vec![3,1]);
+/// ```
+    pub fn map_values<E, F: FnMut(D) -> E>(self, mut f: F) -> IntervalTree<E> {
+        IntervalTree{root: map_values(self.root, &mut f), universe: self.universe, balance: self.balance, rotations: self.rotations}
+    }
+
+/// This function will return an iterator that lazily removes and yields all key,value pairs
+/// whose keys intersect range and for which pred returns true, in ascending order of their keys.
+/// pred may modify the data of pairs that are kept. Pairs that are not yet visited when the
+/// iterator is dropped stay in the tree.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// for i in 0..10 { t.insert(memrange::Range::new(i*10,i*10+5), i as i32) }
+/// let evicted = t.extract_if(20..=60, |_, data| *data % 2 == 0).map(|(_,v)| v).collect::<Vec<i32>>();
+/// assert_eq!(evicted, vec![2,4,6]);
+/// assert_eq!(t.iter().count(), 7);
+///
+/// ```
+    pub fn extract_if<K: IntoRange, F: FnMut(&Range, &mut D) -> bool>(&mut self, range: K, pred: F) -> ExtractIf<D, F>{
+        ExtractIf::new(self, range.into_range(), pred)
+    }
+
+/// This function will return an iterator over mutable references to all data in ascending order
+/// of the keys. The keys can not be changed this way, so the tree stays valid.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<u64>::new();
+/// t.insert(memrange::Range::new(5,6),50);
+/// t.insert(memrange::Range::new(2,8),25);
+/// for v in t.values_mut() { *v += 1 }
+/// assert_eq!(t.values().cloned().collect::<Vec<u64>>(), vec![26,51]);
+/// ```
+    pub fn values_mut(&mut self) -> ValuesMut<D>{
+        ValuesMut::new(self.root.as_mut())
+    }
+}
+
+/// Copies the whole tree, keeping its shape, universe and balancing policy. Every node is copied,
+/// which takes O(n); `IntervalTree` has no `snapshot`, for views in O(1) that stay consistent
+/// while the tree is changed use `PersistentIntervalTree::snapshot`.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::new();
+/// t.insert(memrange::Range::new(2,5),25);
+/// let mut c = t.clone();
+/// c.insert(memrange::Range::new(3,8),38);
+/// assert_eq!((t.len(), c.len()), (1, 2));
+/// ```
+impl<D: Clone, A: Aggregate<D>> Clone for IntervalTree<D, A> {
+    fn clone(&self) -> IntervalTree<D, A> {
+        IntervalTree{root: self.root.clone(), universe: self.universe, balance: self.balance, rotations: self.rotations}
+    }
+}
+
+impl<D, A: Aggregate<D>> IntoIterator for IntervalTree<D, A> {
+    type Item = (Range, D);
+    type IntoIter = IntoIter<D, A>;
+
+    fn into_iter(self) -> IntoIter<D, A> {
+        IntoIter::new(self.root)
+    }
+}
+
+/// Looks up the data stored under exactly the given key like `get`.
+///
+/// # Panics
+///
+/// Panics if the key is not part of the tree, see `try_get` for a non-panicking lookup.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<i32>::new();
+/// t.insert(memrange::Range::new(2,5),25);
+/// assert_eq!(t[memrange::Range::new(2,5)], 25);
+/// assert_eq!(t[2..=5], 25);
+/// ```
+impl<D, A: Aggregate<D>, K: IntoRange> Index<K> for IntervalTree<D, A> {
+    type Output = D;
+
+    fn index(&self, key: K) -> &D {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl <D: BinaryData> IntervalTree<D>{
+
+/// This function will write the tree to writer in a compact versioned binary format: a header
+/// followed by the number of pairs and all (key,value) pairs in sorted order. Wrap writer in a
+/// `BufWriter` when saving large trees.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+///
+/// let mut t=interval_tree::IntervalTree::<u32>::new();
+/// t.insert(memrange::Range::new(2,8),25);
+/// t.insert(memrange::Range::new(5,6),50);
+/// let mut buf = Vec::new();
+/// t.save_to(&mut buf).unwrap();
+/// let loaded = interval_tree::IntervalTree::<u32>::load_from(&mut &buf[..]).unwrap();
+/// assert_eq!(loaded.get(memrange::Range::new(5,6)), Some(&50));
+///
+/// ```
+    pub fn save_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        VERSION.write_binary(writer)?;
+        (self.len() as u64).write_binary(writer)?;
+        for (key, data) in self.iter() {
+            key.min.write_binary(writer)?;
+            key.max.write_binary(writer)?;
+            data.write_binary(writer)?;
+        }
+        Ok(())
+    }
+
+/// This function will read a tree written by `save_to` from reader. The tree is rebuilt in
+/// linear time and is perfectly balanced afterwards. Malformed input (wrong header, unknown
+/// version, unsorted or invalid keys, truncated data) results in an error.
+/// # Examples
+/// ```
+/// extern crate interval_tree;
+///
+/// let garbage = [1u8, 2, 3, 4];
+/// assert!(interval_tree::IntervalTree::<u32>::load_from(&mut &garbage[..]).is_err());
+///
+/// ```
+    pub fn load_from<R: Read>(reader: &mut R) -> io::Result<IntervalTree<D>> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC { return Err(invalid_data("not an interval tree")) }
+        if u32::read_binary(reader)? != VERSION { return Err(invalid_data("unsupported version")) }
+        let len = u64::read_binary(reader)?;
+        let mut pairs = Vec::new();
+        for _ in 0..len {
+            let min = u64::read_binary(reader)?;
+            let max = u64::read_binary(reader)?;
+            if min > max { return Err(invalid_data("invalid range")) }
+            let key = Range::new(min, max);
+            if pairs.last().map_or(false, |&(ref last, _)| *last >= key) {
+                return Err(invalid_data("keys not sorted"))
+            }
+            pairs.push((key, D::read_binary(reader)?));
+        }
+        let len = pairs.len();
+        Ok(IntervalTree{root: from_sorted(len, &mut pairs.into_iter()), universe: None, balance: BalancePolicy::Strict, rotations: 0})
+    }
 }
 
 #[cfg(test)]
 mod tests{
 
     extern crate rand;
-    extern crate test;
     extern crate memrange;
     use node::is_interval_tree;
 
@@ -0,0 +1,121 @@
+extern crate memrange;
+
+#[cfg(test)]
+use self::memrange::Range;
+use persistent::PersistentIntervalTree;
+use bounds::IntoRange;
+use std::collections::BTreeMap;
+
+/// An interval map that keeps its history: every mutation creates a new version, numbered
+/// consecutively starting with the empty version 0, and queries can be run against any version
+/// that is still retained via `as_of`. Versions are `PersistentIntervalTree`s sharing all nodes
+/// not on the changed path, so each version costs O(log n) memory instead of a full copy. Old
+/// versions are dropped with `forget_before`.
+#[derive(Debug)]
+pub struct VersionedIntervalTree<D> {
+    versions: BTreeMap<u64, PersistentIntervalTree<D>>,
+    current: u64,
+}
+
+impl<D: Clone> VersionedIntervalTree<D> {
+
+/// This function will construct a new VersionedIntervalTree whose only version is the empty
+/// version 0.
+/// # Examples
+/// ```
+/// let t=interval_tree::VersionedIntervalTree::<i32>::new();
+/// assert_eq!(t.version(), 0);
+/// assert!(t.latest().empty());
+/// ```
+    pub fn new() -> VersionedIntervalTree<D> {
+        let mut versions = BTreeMap::new();
+        versions.insert(0, PersistentIntervalTree::new());
+        VersionedIntervalTree{versions: versions, current: 0}
+    }
+
+/// This function will return the number of the latest version.
+    pub fn version(&self) -> u64 {
+        self.current
+    }
+
+/// This function will return the contents of the latest version.
+    pub fn latest(&self) -> &PersistentIntervalTree<D> {
+        &self.versions[&self.current]
+    }
+
+/// This function will return the contents of the given version, or None if it has not been
+/// created yet or has been forgotten.
+/// # Examples
+/// ```
+/// extern crate memrange;
+/// extern crate interval_tree;
+/// use memrange::Range;
+///
+/// let mut t=interval_tree::VersionedIntervalTree::new();
+/// let v1 = t.insert(Range::new(0x1000,0x1fff), "libc");
+/// let v2 = t.insert(Range::new(0x1000,0x1fff), "libm");
+/// let v3 = t.remove(Range::new(0x1000,0x1fff));
+/// assert_eq!(t.as_of(v1).unwrap().get(Range::new(0x1000,0x1fff)), Some(&"libc"));
+/// assert_eq!(t.as_of(v2).unwrap().range(0x1800..0x1900).count(), 1);
+/// assert!(t.as_of(v3).unwrap().empty());
+/// t.forget_before(v3);
+/// assert!(t.as_of(v1).is_none());
+/// ```
+    pub fn as_of(&self, version: u64) -> Option<&PersistentIntervalTree<D>> {
+        self.versions.get(&version)
+    }
+
+    fn commit(&mut self, tree: PersistentIntervalTree<D>) -> u64 {
+        self.current += 1;
+        self.versions.insert(self.current, tree);
+        self.current
+    }
+
+/// This function will create a new version that additionally contains the key,value pair,
+/// replacing the data of key if it is already part of the latest version, and return its number.
+    pub fn insert<K: IntoRange>(&mut self, key: K, data: D) -> u64 {
+        let next = self.latest().insert(key, data);
+        self.commit(next)
+    }
+
+/// This function will create a new version without key and return its number. A new version is
+/// created even if key is not part of the latest version.
+    pub fn remove<K: IntoRange>(&mut self, key: K) -> u64 {
+        let next = self.latest().remove(key);
+        self.commit(next)
+    }
+
+/// This function will drop all versions older than version, except for the latest one. Nodes that
+/// are only used by dropped versions are freed.
+    pub fn forget_before(&mut self, version: u64) {
+        let version = if version > self.current { self.current } else { version };
+        self.versions = self.versions.split_off(&version);
+    }
+
+/// This function will return the number of the oldest retained version.
+    pub fn oldest(&self) -> u64 {
+        *self.versions.keys().next().expect("the latest version is always retained")
+    }
+}
+
+#[test]
+fn test_versioned(){
+    let mut t = VersionedIntervalTree::new();
+    let mut expected = vec![Vec::new()];
+    for i in 0..100u64 {
+        if i % 3 == 2 { t.remove(Range::new(i - 1, i + 10)); } else { t.insert(Range::new(i, i + 10), i); }
+        let mut contents = expected.last().unwrap().clone();
+        if i % 3 == 2 { contents.retain(|&(k, _)| k != Range::new(i - 1, i + 10)) } else { contents.push((Range::new(i, i + 10), i)) }
+        expected.push(contents);
+        assert_eq!(t.version(), i + 1);
+    }
+    for (version, contents) in expected.iter().enumerate() {
+        assert_eq!(&t.as_of(version as u64).unwrap().iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), contents);
+    }
+    t.forget_before(50);
+    assert_eq!(t.oldest(), 50);
+    assert!(t.as_of(49).is_none() && t.as_of(50).is_some());
+    t.forget_before(1000);
+    assert_eq!(t.oldest(), 100);
+    assert_eq!(t.latest().len(), expected[100].len());
+}
@@ -2,6 +2,10 @@ extern crate interval_tree;
 extern crate rand;
 extern crate time;
 extern crate memrange;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "quickcheck")]
+extern crate quickcheck;
 
 use time::PreciseTime;
 use memrange::Range;
@@ -20,6 +24,8 @@ fn test_getters(){
     assert!(t.get_or(Range::new(3,3), &0) == &(data+2));
     assert!(t.get_or(Range::new(4,4), &0) == &0);
     assert!(t.get(Range::new(4,4)) == None);
+    assert_eq!(t.get_or_default(Range::new(3,3)), data+2);
+    assert_eq!(t.get_or_default(Range::new(4,4)), 0);
 }
 
 #[test]
@@ -136,7 +142,7 @@ fn test_range_iter(){
     t.insert(Range::new(34,34),1338);
     t.insert(Range::new(36,36),1339);
     t.insert(Range::new(38,38),1340);
-    for (i,pair) in t.range(34, 36).enumerate() {
+    for (i,pair) in t.range(34..=36).enumerate() {
         let (k,v) = pair;
         println!("{:?}, {}",k,v);
         let key = (i as u64)*2 +34;
@@ -154,7 +160,7 @@ fn test_range_iter_non_pointwise(){
     t.insert(Range::new(6,10),1338);
     t.insert(Range::new(12,36),1339);
     t.insert(Range::new(32,40),1340);
-    assert_eq!(t.range(9,14).map(|(&k,_)| k.min).collect::<Vec<u64>>(), vec![6,12])
+    assert_eq!(t.range(9..=14).map(|(&k,_)| k.min).collect::<Vec<u64>>(), vec![6,12])
 }
 
 fn random_range() -> Range {
@@ -189,7 +195,1339 @@ fn test_range_iter_nontrivial(){
         };
     let query = random_range();
     let should = set.iter().filter(|&r| query.intersect(r)).collect::<Vec<&Range>>();
-    let is = t.range(query.min, query.max).map(|(r,_)| r).collect::<Vec<&Range>>();
+    let is = t.range(query.min..=query.max).map(|(r,_)| r).collect::<Vec<&Range>>();
     assert_eq!(should, is);
     };
 }
+
+#[test]
+fn test_first_last_overlap(){
+    let mut t = interval_tree::IntervalTree::<i32>::new();
+    assert!(t.first_overlap(Range::new(0,10)).is_none());
+    for _ in 1..2000 {
+        let range = random_range();
+        t.insert(range, 1337);
+        let query = random_range();
+        let all = t.range(query.min..=query.max).map(|(r,_)| r).collect::<Vec<&Range>>();
+        assert_eq!(t.first_overlap(query).map(|(r,_)| r), all.first().map(|r| *r));
+        assert_eq!(t.last_overlap(query).map(|(r,_)| r), all.last().map(|r| *r));
+    }
+}
+
+#[test]
+fn test_save_load(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 1..1000 {
+        let range = random_range();
+        t.insert(range, i);
+    }
+    let mut buf = Vec::new();
+    t.save_to(&mut buf).expect("save to vec");
+    let loaded = interval_tree::IntervalTree::<u64>::load_from(&mut &buf[..]).expect("load what was saved");
+    assert_eq!(t.iter().collect::<Vec<_>>(), loaded.iter().collect::<Vec<_>>());
+    assert!(loaded.height() <= t.height());
+    assert!(interval_tree::IntervalTree::<u64>::load_from(&mut &buf[..buf.len()-1]).is_err());
+}
+
+#[test]
+fn test_universe(){
+    let universe = Range::new(100,200);
+    let mut t = interval_tree::IntervalTree::<i32>::with_universe(universe, interval_tree::UniversePolicy::Clamp);
+    t.insert(Range::new(50,120), 1);
+    t.insert(Range::new(150,250), 2);
+    t.insert(Range::new(0,10), 3);
+    assert_eq!(t.iter().map(|(&k,_)| k).collect::<Vec<Range>>(), vec![Range::new(100,120), Range::new(150,200)]);
+    assert_eq!(t.range(0..=99).count(), 0);
+    assert_eq!(t.range(0..=100).count(), 1);
+    assert!(t.first_overlap(Range::new(201,300)).is_none());
+
+    let mut t = interval_tree::IntervalTree::<i32>::with_universe(universe, interval_tree::UniversePolicy::Reject);
+    assert_eq!(t.clamp_insert(Range::new(100,200), 1), Some(universe));
+    assert_eq!(t.clamp_insert(Range::new(99,200), 1), None);
+    assert_eq!(t.clamp_insert(Range::new(100,201), 1), None);
+    assert_eq!(t.iter().count(), 1);
+}
+
+#[test]
+fn test_small_stack(){
+    let worker = std::thread::Builder::new().stack_size(16*1024).spawn(|| {
+        let mut t = interval_tree::IntervalTree::<u64>::new();
+        for i in 0..50000 { t.insert(Range::new(i,i+10), i) }
+        for i in 0..50000 { assert_eq!(t.get(Range::new(i,i+10)), Some(&i)) }
+        for i in 0..50000 { t.delete(Range::new(i,i+10)) }
+        assert!(t.empty());
+    }).expect("spawn worker");
+    worker.join().expect("worker with small stack");
+}
+
+#[test]
+fn test_std_range_keys(){
+    let mut t = interval_tree::IntervalTree::<i32>::new();
+    t.insert(10..20, 1);
+    t.insert(30..=40, 2);
+    t.insert(50.., 3);
+    t.insert(5..5, 4);
+    assert_eq!(t.iter().map(|(&k,_)| k).collect::<Vec<Range>>(),
+               vec![Range::new(10,19), Range::new(30,40), Range::new(50,0xffff_ffff_ffff_ffff)]);
+    assert!(t.contains(10..=19));
+    assert!(!t.contains(10..=20));
+    assert_eq!(t.first_overlap(15..35).map(|(_,&v)| v), Some(1));
+    assert_eq!(t.last_overlap(..45).map(|(_,&v)| v), Some(2));
+    assert!(t.first_overlap(20..20).is_none());
+    t.delete(30..41);
+    assert!(!t.contains(Range::new(30,40)));
+}
+
+#[test]
+fn test_from_sorted_iter(){
+    let mut set = BTreeSet::<Range>::new();
+    for _ in 1..3000 { set.insert(random_range()); }
+    let t = interval_tree::IntervalTree::from_sorted_iter(set.iter().map(|r| (*r, r.min)));
+    assert_eq!(t.iter().map(|(r,_)| r).collect::<Vec<&Range>>(), set.iter().collect::<Vec<&Range>>());
+    assert!(t.height() <= 12);
+    for _ in 1..100 {
+        let query = random_range();
+        let should = set.iter().filter(|&r| query.intersect(r)).collect::<Vec<&Range>>();
+        let is = t.range(query.min..=query.max).map(|(r,_)| r).collect::<Vec<&Range>>();
+        assert_eq!(should, is);
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_from_sorted_iter_unsorted(){
+    interval_tree::IntervalTree::from_sorted_iter(vec![(Range::new(5,6), 1), (Range::new(1,2), 2)]);
+}
+
+#[test]
+fn test_from_vec(){
+    let mut pairs = Vec::new();
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 1..3000 {
+        let range = random_range();
+        pairs.push((range, i));
+        t.insert(range, i);
+        if i % 3 == 0 {
+            pairs.push((range, i+1));
+            t.insert(range, i+1);
+        }
+    }
+    let built = interval_tree::IntervalTree::from_vec(pairs);
+    assert_eq!(t.iter().collect::<Vec<_>>(), built.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_delete_range(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for _ in 1..3000 {
+        let range = random_range();
+        set.insert(range);
+        t.insert(range, range.min);
+    }
+    for _ in 1..50 {
+        let query = random_range();
+        let should = set.iter().filter(|&r| query.intersect(r)).map(|r| (*r, r.min)).collect::<Vec<(Range,u64)>>();
+        for &(r,_) in should.iter() { set.remove(&r); }
+        assert_eq!(t.delete_range(query), should);
+        assert!(t.first_overlap(query).is_none());
+        assert_eq!(t.iter().map(|(r,_)| r).collect::<Vec<&Range>>(), set.iter().collect::<Vec<&Range>>());
+    }
+}
+
+#[test]
+fn test_drain(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for _ in 1..2000 {
+        let range = random_range();
+        set.insert(range);
+        t.insert(range, range.max);
+    }
+    let drained = t.drain().collect::<Vec<(Range,u64)>>();
+    assert!(t.empty());
+    assert_eq!(drained, set.iter().map(|r| (*r, r.max)).collect::<Vec<(Range,u64)>>());
+    t.insert(Range::new(1,2), 3);
+    assert_eq!(t.into_iter().collect::<Vec<(Range,u64)>>(), vec![(Range::new(1,2), 3)]);
+}
+
+#[test]
+fn test_extract_if(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for _ in 1..3000 {
+        let range = random_range();
+        set.insert(range);
+        t.insert(range, range.min);
+    }
+    for _ in 1..20 {
+        let query = random_range();
+        let should = set.iter().filter(|&r| query.intersect(r) && r.max % 2 == 0).map(|r| (*r, r.min)).collect::<Vec<(Range,u64)>>();
+        for &(r,_) in should.iter() { set.remove(&r); }
+        assert_eq!(t.extract_if(query, |r, _| r.max % 2 == 0).collect::<Vec<(Range,u64)>>(), should);
+        assert_eq!(t.iter().map(|(r,_)| r).collect::<Vec<&Range>>(), set.iter().collect::<Vec<&Range>>());
+    }
+}
+
+#[test]
+fn test_append(){
+    for &(a_size, b_size) in [(0,100), (100,0), (1000,10), (500,500), (10,1000)].iter() {
+        let mut a = interval_tree::IntervalTree::<u64>::new();
+        let mut b = interval_tree::IntervalTree::<u64>::new();
+        let mut should = interval_tree::IntervalTree::<u64>::new();
+        for _ in 0..a_size { let r = random_range(); a.insert(r, 1); should.insert(r, 1); }
+        for _ in 0..b_size { let r = random_range(); b.insert(r, 2); should.insert(r, 2); }
+        let shared = Range::new(7,9);
+        a.insert(shared, 1);
+        b.insert(shared, 2);
+        should.insert(shared, 2);
+        a.append(&mut b);
+        assert!(b.empty());
+        assert_eq!(a.iter().collect::<Vec<_>>(), should.iter().collect::<Vec<_>>());
+    }
+    let mut low = interval_tree::IntervalTree::from_sorted_iter((0..100).map(|i| (i..i+1, i)));
+    let mut high = interval_tree::IntervalTree::from_sorted_iter((100..300).map(|i| (i..i+1, i)));
+    high.append(&mut low);
+    assert_eq!(high.iter().map(|(_,v)| *v).collect::<Vec<u64>>(), (0..300).collect::<Vec<u64>>());
+}
+
+fn small_random_range() -> Range {
+    let offset = rand::random::<u64>()%1000;
+    Range::new(offset, offset + rand::random::<u64>()%50)
+}
+
+#[test]
+fn test_set_operations(){
+    let mut a = interval_tree::IntervalTree::<u64>::new();
+    let mut b = interval_tree::IntervalTree::<u64>::new();
+    for _ in 0..40 {
+        a.insert(small_random_range(), 1);
+        b.insert(small_random_range(), 2);
+    }
+    let covered = |t: &interval_tree::IntervalTree<u64>, p: u64| t.range(p..=p).next().is_some();
+    let result_covers = |m: &interval_tree::IntervalMultiMap<u64>, p: u64| m.range(p..=p).next().is_some();
+    let both = a.intersection(&b, |x, y| x+y);
+    let only_a = a.difference(&b);
+    let all = a.union(&b, |x, y| x+y);
+    for p in 0..1100 {
+        assert_eq!(result_covers(&both, p), covered(&a, p) && covered(&b, p));
+        assert_eq!(result_covers(&only_a, p), covered(&a, p) && !covered(&b, p));
+        assert_eq!(result_covers(&all, p), covered(&a, p) || covered(&b, p));
+    }
+    assert!(both.iter().all(|(_,&v)| v == 3));
+    assert!(only_a.iter().all(|(_,&v)| v == 1));
+    //one result for every overlapping pair, even if several share their intersection
+    assert_eq!(both.iter().count(), a.iter().map(|(k, _)| b.range(*k).count()).sum::<usize>());
+    let mut nested = interval_tree::IntervalTree::<u64>::new();
+    nested.insert(Range::new(0, 10), 1);
+    nested.insert(Range::new(0, 20), 2);
+    let mut holes = interval_tree::IntervalTree::<u64>::new();
+    holes.insert(Range::new(11, 30), 0);
+    assert_eq!(nested.difference(&holes).iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), vec![(Range::new(0, 10), 1), (Range::new(0, 10), 2)]);
+}
+
+#[test]
+fn test_interval_set(){
+    let mut s = interval_tree::IntervalSet::new();
+    let mut covered = [false; 1100];
+    for i in 0..300 {
+        let range = small_random_range();
+        for p in range.min..range.max+1 { covered[p as usize] = i % 3 != 0 }
+        if i % 3 == 0 { s.remove(range) } else { s.insert(range) }
+        let ranges = s.iter().cloned().collect::<Vec<Range>>();
+        for w in ranges.windows(2) { assert!(w[0].max + 1 < w[1].min) }
+    }
+    for p in 0..1100 {
+        assert_eq!(s.contains(p..=p), covered[p as usize]);
+    }
+    s.insert(..);
+    assert_eq!(s.iter().count(), 1);
+    s.remove(0..1);
+    assert_eq!(s.iter().collect::<Vec<&Range>>(), vec![&Range::new(1, 0xffff_ffff_ffff_ffff)]);
+}
+
+#[test]
+fn test_insert_replace_overlaps(){
+    let mut t = interval_tree::IntervalTree::<usize>::new();
+    let mut owner = [None; 1100];
+    for i in 0..300 {
+        let range = small_random_range();
+        for p in range.min..range.max+1 { owner[p as usize] = Some(i) }
+        t.insert_replace_overlaps(range, i);
+        let ranges = t.iter().map(|(k,_)| *k).collect::<Vec<Range>>();
+        for w in ranges.windows(2) { assert!(w[0].max < w[1].min) }
+    }
+    for p in 0..1100 {
+        assert_eq!(t.first_overlap(p..=p).map(|(_,&v)| v), owner[p as usize]);
+    }
+}
+
+#[test]
+fn test_gaps(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut covered = [false; 1100];
+    for _ in 0..40 {
+        let range = small_random_range();
+        for p in range.min..range.max+1 { covered[p as usize] = true }
+        t.insert(range, 0);
+    }
+    for _ in 0..100 {
+        let query = small_random_range();
+        let mut should = Vec::new();
+        for p in query.min..query.max+1 {
+            if covered[p as usize] { continue }
+            match should.last_mut() {
+                Some(&mut Range{min: _, ref mut max}) if *max + 1 == p => *max = p,
+                _ => should.push(Range::new(p,p))
+            }
+        }
+        assert_eq!(t.gaps(query.min..=query.max).collect::<Vec<Range>>(), should);
+    }
+    let empty = interval_tree::IntervalTree::<u64>::new();
+    assert_eq!(empty.gaps(0..=0xffff_ffff_ffff_ffff).collect::<Vec<Range>>(), vec![Range::new(0, 0xffff_ffff_ffff_ffff)]);
+    t.insert(Range::new(0, 0xffff_ffff_ffff_ffff), 0);
+    assert_eq!(t.gaps(0..=0xffff_ffff_ffff_ffff).count(), 0);
+    let mut bounded = interval_tree::IntervalTree::<u64>::with_universe(Range::new(100, 200), interval_tree::UniversePolicy::Clamp);
+    bounded.insert(Range::new(120, 150), 0);
+    assert_eq!(bounded.gaps(..).collect::<Vec<Range>>(), vec![Range::new(100, 119), Range::new(151, 200)]);
+    assert_eq!(bounded.gaps(300..400).count(), 0);
+    bounded.insert(Range::new(0, 130), 0);
+    assert!(bounded.is_covered(50..=150));
+}
+
+#[test]
+fn test_coverage(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut covered = [false; 1100];
+    for _ in 0..40 {
+        let range = small_random_range();
+        for p in range.min..range.max+1 { covered[p as usize] = true }
+        t.insert(range, 0);
+    }
+    for _ in 0..100 {
+        let query = small_random_range();
+        let should = (query.min..query.max+1).filter(|&p| covered[p as usize]).count() as u64;
+        assert_eq!(t.coverage(query), should);
+    }
+    t.insert(.., 0);
+    assert_eq!(t.coverage(..), 0xffff_ffff_ffff_ffff);
+}
+
+#[test]
+fn test_find_free_gap(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..300 {
+        let range = small_random_range();
+        if i % 4 == 0 { t.delete_range(range); } else { t.insert(range, 0); }
+        let size = rand::random::<u64>()%20 + 1;
+        let within = small_random_range();
+        let should = t.gaps(within.min..=within.max).find(|g| g.len() >= size).map(|g| Range::new(g.min, g.min+size-1));
+        assert_eq!(t.find_free_gap(size, within), should);
+    }
+    assert_eq!(t.find_free_gap(1, Range::new(0xffff_ffff_ffff_fff0, 0xffff_ffff_ffff_ffff)),
+               Some(Range::new(0xffff_ffff_ffff_fff0, 0xffff_ffff_ffff_fff0)));
+}
+
+#[test]
+fn test_range_allocator(){
+    let mut a = interval_tree::RangeAllocator::<u64>::new(Range::new(100, 10099));
+    let mut live: Vec<Range> = Vec::new();
+    for i in 0..2000 {
+        if i % 3 == 2 && !live.is_empty() {
+            let range = live.swap_remove(rand::random::<usize>() % live.len());
+            assert_eq!(a.free(range), Some(range.min));
+        } else if let Ok(range) = a.allocate(rand::random::<u64>()%20 + 1, 0) {
+            assert!(a.free(range).is_some());
+            assert!(a.allocate_at(range, range.min).is_ok());
+            live.push(range);
+        }
+        let allocations = a.iter().map(|(k,_)| *k).collect::<Vec<Range>>();
+        for w in allocations.windows(2) { assert!(w[0].max < w[1].min) }
+        assert!(allocations.iter().all(|r| r.min >= 100 && r.max <= 10099));
+    }
+    assert!(a.allocate_at(Range::new(0, 100), 0).is_err());
+}
+
+#[test]
+fn test_try_insert(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut owner = [None; 1100];
+    for i in 0..300 {
+        let range = small_random_range();
+        let first = (range.min..range.max+1).filter_map(|p| owner[p as usize]).next();
+        match t.try_insert(range, i) {
+            Ok(()) => {
+                assert_eq!(first, None);
+                for p in range.min..range.max+1 { owner[p as usize] = Some(range) }
+            }
+            Err(interval_tree::Error::Overlap(err)) => {
+                assert!(err.existing.intersect(&range));
+                assert!(owner[range.min as usize..range.max as usize +1].iter().all(|o| o.map_or(true, |r| r.min >= err.existing.min)));
+            }
+            Err(err) => panic!("unexpected error {}", err)
+        }
+    }
+    assert_eq!(t.try_insert(5..5, 0), Err(interval_tree::Error::EmptyKey));
+    assert_eq!(t.try_insert(Range{min: 9, max: 3}, 0), Err(interval_tree::Error::EmptyKey));
+    let mut bounded = interval_tree::IntervalTree::<u64>::with_universe(Range::new(10, 20), interval_tree::UniversePolicy::Reject);
+    assert_eq!(bounded.try_insert(Range::new(5, 15), 0), Err(interval_tree::Error::OutsideUniverse{key: Range::new(5, 15)}));
+    assert_eq!(bounded.try_insert(Range::new(30, 40), 0), Err(interval_tree::Error::OutsideUniverse{key: Range::new(30, 40)}));
+    assert_eq!(bounded.try_insert(Range::new(12, 15), 0), Ok(()));
+    assert_eq!(bounded.len(), 1);
+    let mut clamped = interval_tree::IntervalTree::<u64>::with_universe(Range::new(10, 20), interval_tree::UniversePolicy::Clamp);
+    assert_eq!(clamped.try_insert(Range::new(30, 40), 0), Err(interval_tree::Error::OutsideUniverse{key: Range::new(30, 40)}));
+    assert_eq!(clamped.try_insert(Range::new(0, 12), 0), Ok(()));
+    assert_eq!(clamped.try_insert(Range::new(13, 30), 1), Ok(()));
+    assert_eq!(clamped.keys().cloned().collect::<Vec<Range>>(), vec![Range::new(10, 12), Range::new(13, 20)]);
+}
+
+#[test]
+fn test_multimap(){
+    let mut m = interval_tree::IntervalMultiMap::<u64>::new();
+    let mut should = Vec::new();
+    for i in 0..500 {
+        let range = Range::new(rand::random::<u64>()%20, 20 + rand::random::<u64>()%5);
+        if i % 4 == 3 {
+            let value = rand::random::<u64>()%10;
+            let pos = should.iter().position(|&(r, v)| r == range && v == value);
+            assert_eq!(m.remove_one(range, &value), pos.map(|_| value));
+            if let Some(pos) = pos { should.remove(pos); }
+        } else {
+            let value = rand::random::<u64>()%10;
+            m.insert(range, value);
+            should.push((range, value));
+        }
+    }
+    let mut sorted = should.clone();
+    sorted.sort_by_key(|&(r, _)| r);
+    assert_eq!(m.iter().map(|(r,v)| (*r,*v)).collect::<Vec<_>>(), sorted);
+    let key = sorted[0].0;
+    assert_eq!(m.remove_all(key), sorted.iter().filter(|&&(r, _)| r == key).map(|&(_, v)| v).collect::<Vec<_>>());
+    assert!(m.get(key).is_empty());
+}
+
+#[test]
+fn test_range_bounds(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut keys = Vec::new();
+    for _ in 0..200 {
+        let range = small_random_range();
+        t.insert(range, range.min);
+        keys.push(range);
+    }
+    let max = 0xffff_ffff_ffff_ffff;
+    t.insert(Range::new(max-5, max), 0);
+    keys.push(Range::new(max-5, max));
+    keys.sort(); keys.dedup();
+    let find = |min: u64, max: u64| keys.iter().cloned().filter(|k| k.min <= max && min <= k.max).collect::<Vec<Range>>();
+    for _ in 0..100 {
+        let q = small_random_range();
+        let got = |i: interval_tree::RangePairIter<u64>| i.map(|(k,_)| *k).collect::<Vec<Range>>();
+        assert_eq!(got(t.range(q.min..q.max+1)), find(q.min, q.max));
+        assert_eq!(got(t.range(q.min..=q.max)), find(q.min, q.max));
+        assert_eq!(got(t.range(q.min..)), find(q.min, max));
+        assert_eq!(got(t.range(..q.max)), if q.max == 0 { vec![] } else { find(0, q.max-1) });
+        assert_eq!(got(t.range(q.min..q.min)), vec![]);
+    }
+    assert_eq!(t.range(max..).count(), 1);
+    assert_eq!(t.range(..).count(), keys.len());
+    assert_eq!(t.gaps(5..5).count(), 0);
+}
+
+#[test]
+fn test_point_lookup(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut keys = Vec::new();
+    for _ in 0..100 {
+        let range = small_random_range();
+        t.insert((range.min, range.max), range.min);
+        keys.push(range);
+    }
+    for p in 0..1100 {
+        let should = keys.iter().filter(|k| k.min <= p && p <= k.max).min().cloned();
+        assert_eq!(t.get_at_point(p).map(|(k,_)| *k), should);
+        assert_eq!(t.contains_point(p), should.is_some());
+    }
+    let key = keys[0];
+    assert!(t.contains((key.min, key.max)));
+    t.delete((key.min, key.max));
+    assert!(!t.contains(key));
+}
+
+#[test]
+fn test_projections(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for _ in 1..2000 {
+        let range = random_range();
+        set.insert(range);
+        t.insert(range, range.min);
+    }
+    assert_eq!(t.keys().cloned().collect::<Vec<Range>>(), set.iter().cloned().collect::<Vec<Range>>());
+    for v in t.values_mut() { *v = v.wrapping_add(1) }
+    assert_eq!(t.values().cloned().collect::<Vec<u64>>(), set.iter().map(|r| r.min.wrapping_add(1)).collect::<Vec<u64>>());
+    let mut empty = interval_tree::IntervalTree::<u64>::new();
+    assert_eq!(empty.values_mut().count(), 0);
+}
+
+#[test]
+#[should_panic]
+fn test_index_missing_key(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    t.insert(Range::new(2,5), 1);
+    let _ = t[Range::new(2,4)];
+}
+
+#[test]
+fn test_count_in_range(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut keys = BTreeSet::<Range>::new();
+    for i in 0..600 {
+        let range = small_random_range();
+        if i % 5 == 4 {
+            t.delete(range);
+            keys.remove(&range);
+        } else {
+            t.insert(range, 0);
+            keys.insert(range);
+        }
+        let query = small_random_range();
+        assert_eq!(t.count_in_range(query), keys.iter().filter(|k| k.intersect(&query)).count());
+    }
+    assert_eq!(t.count_in_range(..), keys.len());
+}
+
+#[test]
+fn test_rank_select(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..2000 {
+        let range = random_range();
+        if i % 4 == 3 {
+            let k = set.iter().next().cloned().unwrap();
+            set.remove(&k);
+            t.delete(k);
+        }
+        set.insert(range);
+        t.insert(range, 0);
+    }
+    assert_eq!(t.len(), set.len());
+    for (k, key) in set.iter().enumerate() {
+        assert_eq!(t.select(k).map(|(r,_)| r), Some(key));
+        assert_eq!(t.rank(*key), k);
+    }
+    assert_eq!(t.select(set.len()), None);
+    for _ in 0..100 {
+        let probe = random_range();
+        assert_eq!(t.rank(probe), set.range(..probe).count());
+    }
+}
+
+#[test]
+fn test_sample(){
+    let mut t = interval_tree::IntervalTree::with_aggregate(interval_tree::aggregate::LenSum);
+    assert_eq!(t.sample_weighted(rand::random::<u64>), None);
+    assert_eq!(t.sample(rand::random::<u64>), None);
+    t.insert(Range::new(0, 0xff_ff_ff_ff_ff_ff_ff_ff), 0);
+    assert_eq!(t.sample_weighted(rand::random::<u64>).map(|(_, v)| *v), Some(0));
+    t = interval_tree::IntervalTree::with_aggregate(interval_tree::aggregate::LenSum);
+    for i in 0..10 { t.insert(Range::new(i * 100, i * 100 + i * 10 + 9), i) }
+    assert_eq!(t.aggregate(..), (0..10).map(|i| i * 10 + 10).sum::<u128>());
+    //laid out one after another, key i covers the offsets from 5*i*(i+1) up to the next key
+    let mut offset = 0u64;
+    for i in 0..10u64 {
+        for &o in &[5 * i * (i + 1), 5 * (i + 1) * (i + 2) - 1] {
+            offset = o;
+            let mut draws = vec![0, o].into_iter();
+            assert_eq!(t.sample_weighted(|| draws.next().unwrap()).map(|(_, v)| *v), Some(i));
+        }
+    }
+    let mut draws = vec![0, offset + 1].into_iter();
+    assert_eq!(t.sample_weighted(|| draws.next().unwrap()).map(|(_, v)| *v), Some(0));
+    let mut counts = vec![0; 10];
+    for i in 0..1000 { counts[*t.sample(|| i).unwrap().1 as usize] += 1 }
+    assert_eq!(counts, vec![100; 10]);
+}
+
+#[test]
+fn test_find_overlaps_batch(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..2000 { t.insert(random_range(), i) }
+    t.insert(Range::new(0, 100), 0);
+    let mut queries = (0..200).map(|_| random_range()).collect::<Vec<_>>();
+    queries.push(Range::new(0, 0xff_ff_ff_ff_ff_ff_ff_ff));
+    queries.push(queries[0]);
+    queries.push(Range{min: 9, max: 3});
+    let results = t.find_overlaps_batch(&queries);
+    assert_eq!(results.len(), queries.len());
+    for (query, result) in queries.iter().zip(results.iter()) {
+        assert_eq!(result, &t.range(*query).collect::<Vec<_>>());
+    }
+    assert!(interval_tree::IntervalTree::<u64>::new().find_overlaps_batch(&queries).iter().all(|r| r.is_empty()));
+}
+
+#[test]
+fn test_extend_sorted(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut expected = std::collections::BTreeMap::new();
+    for round in 0..20u64 {
+        let mut batch = (0..round * 50).map(|_| (random_range(), round)).collect::<Vec<_>>();
+        batch.sort_by_key(|&(k, _)| k);
+        batch.dedup_by_key(|&mut (k, _)| k);
+        for &(k, v) in batch.iter() { expected.insert(k, v); }
+        t.extend_sorted(batch);
+        assert!(t.check_invariants().is_ok());
+    }
+    //runs that are small compared to the tree are inserted pair by pair
+    for round in 20..30u64 {
+        let mut batch = (0..3).map(|_| (random_range(), round)).collect::<Vec<_>>();
+        batch.sort_by_key(|&(k, _)| k);
+        batch.dedup_by_key(|&mut (k, _)| k);
+        for &(k, v) in batch.iter() { expected.insert(k, v); }
+        t.extend_sorted(batch);
+    }
+    assert!(t.check_invariants().is_ok());
+    assert!(t.iter().map(|(k, v)| (*k, *v)).eq(expected.into_iter()));
+}
+
+#[test]
+#[should_panic]
+fn test_extend_sorted_unsorted(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    t.extend_sorted(vec![(Range::new(5, 6), 0), (Range::new(1, 2), 0)]);
+}
+
+#[test]
+fn test_insert_with(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut expected = std::collections::BTreeMap::new();
+    for i in 0..2000u64 {
+        let key = Range::new(i % 97, i % 97 + i % 7);
+        t.insert_with(key, i, |old, new| old + new);
+        *expected.entry(key).or_insert(0) += i;
+    }
+    assert!(t.iter().map(|(k, v)| (*k, *v)).eq(expected.into_iter()));
+    let mut sums = interval_tree::IntervalTree::<u64, interval_tree::aggregate::Sum>::with_aggregate(interval_tree::aggregate::Sum);
+    sums.insert_with(Range::new(0, 9), 5, |old, new| old * new);
+    sums.insert_with(Range::new(0, 9), 7, |old, new| old * new);
+    assert_eq!(sums.aggregate(..), 35);
+    let mut clamped = interval_tree::IntervalTree::<u64>::with_universe(Range::new(10, 20), interval_tree::UniversePolicy::Clamp);
+    clamped.insert_with(5..=12, 1, |old, new| old + new);
+    clamped.insert_with(5..=12, 2, |old, new| old + new);
+    clamped.insert_with(10..=12, 4, |old, new| old + new);
+    clamped.insert_with(30..=40, 8, |old, new| old + new);
+    assert_eq!(clamped.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), vec![(Range::new(10, 12), 7)]);
+}
+
+#[test]
+fn test_visit(){
+    use interval_tree::VisitControl;
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..1000 { t.insert(random_range(), i) }
+    let mut inorder = Vec::new();
+    t.visit_inorder(|_, k, v| { inorder.push((*k, *v)); VisitControl::Continue });
+    assert_eq!(inorder, t.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>());
+    let (mut pre, mut post, mut max_depth) = (Vec::new(), Vec::new(), 0);
+    t.visit_preorder(|d, k, _| { pre.push(*k); max_depth = cmp::max(max_depth, d); VisitControl::Continue });
+    t.visit_postorder(|_, k, _| { post.push(*k); VisitControl::Continue });
+    assert_eq!(max_depth + 1, t.height());
+    assert_eq!(pre.len(), t.len());
+    assert_eq!(post.len(), t.len());
+    assert_eq!(pre[0], *post.last().unwrap());
+    let mut shallow = 0;
+    t.visit_preorder(|d, _, _| { shallow += 1; if d == 2 { VisitControl::Prune } else { VisitControl::Continue } });
+    assert_eq!(shallow, 7);
+    let mut visited = 0;
+    t.visit_inorder(|_, _, _| { visited += 1; if visited == 10 { VisitControl::Stop } else { VisitControl::Continue } });
+    assert_eq!(visited, 10);
+}
+
+#[test]
+fn test_try_variants(){
+    use interval_tree::{Error, IntervalTree, RangeExt};
+    fn build(pairs: Vec<(Range, u64)>, extra: Range) -> Result<IntervalTree<u64>, Error> {
+        let extra = Range::try_new(extra.min, extra.max)?;
+        let mut t = IntervalTree::try_from_sorted_iter(pairs)?;
+        t.try_insert(extra, 0)?;
+        t.shift(1)?;
+        t.check_invariants()?;
+        Ok(t)
+    }
+    let sorted = vec![(Range::new(0, 5), 1), (Range::new(10, 15), 2)];
+    assert_eq!(build(sorted.clone(), Range::new(20, 30)).unwrap().len(), 3);
+    assert_eq!(build(sorted.clone(), Range{min: 30, max: 20}).unwrap_err(), Error::InvalidRange(interval_tree::RangeError{min: 30, max: 20}));
+    assert_eq!(build(sorted.clone(), Range::new(3, 4)).unwrap_err(), Error::Overlap(interval_tree::OverlapError{existing: Range::new(0, 5)}));
+    assert!(match build(sorted.clone(), Range::new(20, 0xff_ff_ff_ff_ff_ff_ff_ff)) { Err(Error::Shift(_)) => true, _ => false });
+    let unsorted = vec![(Range::new(10, 15), 2), (Range::new(0, 5), 1)];
+    assert_eq!(build(unsorted.clone(), Range::new(20, 30)).unwrap_err(), Error::Unsorted{previous: Range::new(10, 15), key: Range::new(0, 5)});
+    let mut t = build(sorted, Range::new(20, 30)).unwrap();
+    assert!(t.try_extend_sorted(unsorted).is_err());
+    assert_eq!(t.len(), 3);
+    assert_eq!(t.try_get(Range::new(1, 6)), Ok(&1));
+    assert_eq!(t.try_get(Range::new(0, 5)), Err(Error::KeyNotFound));
+}
+
+#[test]
+fn test_find_contained(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..2000u64 { t.insert(small_random_range(), i) }
+    let e = interval_tree::IntervalTree::<u64>::new();
+    assert_eq!(e.find_contained(..).count(), 0);
+    for _ in 0..200 {
+        let q = small_random_range();
+        let expected = t.range(q).filter(|&(k, _)| q.min <= k.min && k.max <= q.max).collect::<Vec<_>>();
+        assert_eq!(t.find_contained(q).collect::<Vec<_>>(), expected);
+    }
+    assert_eq!(t.find_contained(..).count(), t.len());
+}
+
+#[test]
+fn test_find_enclosing(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..2000u64 { t.insert(small_random_range(), i) }
+    for _ in 0..200 {
+        let q = small_random_range();
+        let expected = t.range(q).filter(|&(k, _)| k.min <= q.min && q.max <= k.max).collect::<Vec<_>>();
+        assert_eq!(t.find_enclosing(q).collect::<Vec<_>>(), expected);
+        assert_eq!(t.find_enclosing(q.min..=q.min).count(), t.range(q.min..=q.min).count());
+    }
+    assert_eq!(t.find_enclosing(..).count(), 0);
+}
+
+#[test]
+fn test_span(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let hull = |t: &interval_tree::IntervalTree<u64>| t.keys().fold(None, |hull: Option<Range>, k| Some(match hull {
+        Some(h) => Range::new(std::cmp::min(h.min, k.min), std::cmp::max(h.max, k.max)),
+        None => *k
+    }));
+    assert_eq!(t.span(), None);
+    for i in 0..500u64 {
+        t.insert(random_range(), i);
+        assert_eq!(t.span(), hull(&t));
+    }
+    while let Some(key) = t.keys().nth(t.len() / 2).cloned() {
+        t.delete(key);
+        assert_eq!(t.span(), hull(&t));
+    }
+}
+
+#[test]
+fn test_covers(){
+    let mut actual = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..300u64 { actual.insert(small_random_range(), i) }
+    let empty = interval_tree::IntervalTree::<()>::new();
+    assert!(actual.covers(&empty) && empty.covers(&empty) && empty.is_covered_by(&actual));
+    assert!(!empty.covers(&actual));
+    assert!(actual.covers(&actual));
+    for _ in 0..200 {
+        let mut required = interval_tree::IntervalTree::<()>::new();
+        for _ in 0..rand::random::<u64>()%4 { required.insert(small_random_range(), ()) }
+        let expected = required.keys().all(|&key| actual.is_covered(key));
+        assert_eq!(actual.covers(&required), expected);
+        assert_eq!(required.is_covered_by(&actual), expected);
+    }
+}
+
+#[test]
+fn test_snapshot_while_writing(){
+    let mut t = interval_tree::PersistentIntervalTree::<u64>::new();
+    let mut model = std::collections::BTreeMap::<Range, u64>::new();
+    let mut snapshots = Vec::new();
+    for i in 0..3000u64 {
+        let range = small_random_range();
+        assert_eq!(t.insert_in_place(range, i), model.insert(range, i));
+        let k = small_random_range();
+        assert_eq!(t.remove_in_place(k), model.remove(&k));
+        if i % 500 == 0 { snapshots.push((t.snapshot(), model.clone())) }
+        if i % 700 == 0 { snapshots.remove(0); }
+    }
+    assert_eq!(t.len(), model.len());
+    assert!(t.iter().map(|(k, v)| (*k, *v)).eq(model.iter().map(|(k, v)| (*k, *v))));
+    for &(ref snapshot, ref contents) in snapshots.iter() {
+        assert_eq!(snapshot.len(), contents.len());
+        assert!(snapshot.iter().map(|(k, v)| (*k, *v)).eq(contents.iter().map(|(k, v)| (*k, *v))));
+    }
+}
+
+#[derive(Debug)]
+struct InOrder;
+
+impl interval_tree::Aggregate<u64> for InOrder {
+    type Value = Vec<u64>;
+    fn empty() -> Vec<u64> { Vec::new() }
+    fn lift(_: &Range, data: &u64) -> Vec<u64> { vec![*data] }
+    fn combine(left: &Vec<u64>, right: &Vec<u64>) -> Vec<u64> { left.iter().chain(right.iter()).cloned().collect() }
+}
+
+#[test]
+fn test_aggregate(){
+    let mut t = interval_tree::IntervalTree::with_aggregate(InOrder);
+    let mut sums = interval_tree::IntervalTree::with_aggregate(interval_tree::aggregate::Sum);
+    let mut should = std::collections::BTreeMap::<Range, u64>::new();
+    for i in 0..600 {
+        let range = small_random_range();
+        if i % 5 == 4 {
+            t.delete(range);
+            sums.delete(range);
+            should.remove(&range);
+        } else {
+            t.insert(range, i);
+            sums.insert(range, i);
+            should.insert(range, i);
+        }
+        let query = small_random_range();
+        let values = should.iter().filter(|&(k,_)| k.intersect(&query)).map(|(_,v)| *v).collect::<Vec<u64>>();
+        assert_eq!(sums.aggregate(query), values.iter().sum::<u64>());
+        assert_eq!(t.aggregate(query), values);
+    }
+    let mut tail = t.split_off(500);
+    tail.retain(|_, v| *v % 2 == 0);
+    t.append(&mut tail);
+    should.retain(|k, v| k.min < 500 || *v % 2 == 0);
+    assert_eq!(t.aggregate(..), should.values().cloned().collect::<Vec<u64>>());
+}
+
+#[test]
+fn test_nearest_interval(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut keys = Vec::new();
+    for _ in 0..60 {
+        let range = small_random_range();
+        t.insert(range, 0);
+        keys.push(range);
+    }
+    let distance = |k: &Range, p: u64| if p < k.min { k.min - p } else { p.saturating_sub(k.max) };
+    for p in 0..1100 {
+        let best = keys.iter().map(|k| distance(k, p)).min().unwrap();
+        let (key, _, d) = t.nearest_interval(p).unwrap();
+        assert_eq!(d, best);
+        assert_eq!(distance(key, p), best);
+    }
+    assert_eq!(interval_tree::IntervalTree::<u64>::new().nearest_interval(5), None);
+}
+
+#[test]
+fn test_k_nearest(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut keys = BTreeSet::<Range>::new();
+    for _ in 0..200 {
+        let range = small_random_range();
+        t.insert(range, 0);
+        keys.insert(range);
+    }
+    let distance = |k: &Range, p: u64| if p < k.min { k.min - p } else { p.saturating_sub(k.max) };
+    for _ in 0..50 {
+        let p = rand::random::<u64>() % 1100;
+        let mut should = keys.iter().map(|k| (distance(k, p), k.min)).collect::<Vec<_>>();
+        should.sort();
+        let got = t.nearest(p).map(|(k,_,d)| { assert_eq!(distance(k, p), d); (d, k.min) }).collect::<Vec<_>>();
+        assert_eq!(got, should);
+        assert_eq!(t.k_nearest(p, 7).count(), 7);
+        assert_eq!(t.k_nearest(p, 1).next().map(|(_,_,d)| d), t.nearest_interval(p).map(|(_,_,d)| d));
+    }
+}
+
+#[test]
+fn test_rebalance(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..3000 {
+        let range = random_range();
+        set.insert(range);
+        t.insert(range, range.min);
+        if i % 2 == 1 {
+            let k = *set.iter().next().unwrap();
+            set.remove(&k);
+            t.delete(k);
+        }
+    }
+    t.rebalance();
+    assert_eq!(t.height() as f64, (set.len() as f64 + 1.0).log2().ceil());
+    assert_eq!(t.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(), set.iter().map(|k| (*k, k.min)).collect::<Vec<_>>());
+    let query = random_range();
+    assert_eq!(t.range(query).count(), set.iter().filter(|k| k.intersect(&query)).count());
+}
+
+#[test]
+fn test_balance_policy(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    t.set_balance_policy(interval_tree::BalancePolicy::Relaxed(3));
+    for i in 0..3000 {
+        let range = small_random_range();
+        set.insert(range);
+        t.insert(range, range.min);
+        if i % 3 != 0 {
+            let k = *set.iter().next().unwrap();
+            assert_eq!(t.remove_entry(k), Some((k, k.min)));
+            set.remove(&k);
+        }
+        let minimal = (set.len() as f64 + 1.0).log2().ceil() as usize;
+        assert!(t.height() <= 3*minimal);
+        assert_eq!(t.len(), set.len());
+    }
+    assert_eq!(t.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(), set.iter().map(|k| (*k, k.min)).collect::<Vec<_>>());
+    let query = small_random_range();
+    assert_eq!(t.range(query).count(), set.iter().filter(|k| k.intersect(&query)).count());
+    t.set_balance_policy(interval_tree::BalancePolicy::Strict);
+    assert_eq!(t.height() as f64, (set.len() as f64 + 1.0).log2().ceil());
+    //factors too big for any height never rebalance
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    t.set_balance_policy(interval_tree::BalancePolicy::Relaxed(u32::max_value()));
+    for i in 0..100 { t.insert(Range::new(i, i), i); }
+    for i in 0..50 { t.delete(Range::new(i, i)); }
+    assert_eq!(t.len(), 50);
+}
+
+#[test]
+fn test_arena_tree(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::ArenaIntervalTree::<u64>::new();
+    for i in 0..3000 {
+        let range = small_random_range();
+        assert_eq!(t.insert(range, range.min).is_some(), !set.insert(range));
+        if i % 3 == 0 {
+            let k = small_random_range();
+            assert_eq!(t.remove(k), if set.remove(&k) { Some(k.min) } else { None });
+        }
+        assert_eq!(t.len(), set.len());
+    }
+    assert_eq!(t.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(), set.iter().map(|k| (*k, k.min)).collect::<Vec<_>>());
+    for _ in 0..100 {
+        let query = small_random_range();
+        assert_eq!(t.range(query).map(|(k,_)| *k).collect::<Vec<_>>(), set.iter().filter(|k| k.intersect(&query)).cloned().collect::<Vec<_>>());
+    }
+}
+
+/// a node storage with a fixed number of slots, which never allocates after construction
+struct FixedPool<T> {
+    slots: Box<[Option<T>]>,
+    len: usize,
+}
+
+impl<T> interval_tree::NodeAlloc<T> for FixedPool<T> {
+    fn len(&self) -> usize { self.len }
+    fn push(&mut self, slot: T) { self.slots[self.len] = Some(slot); self.len += 1 }
+    fn slot(&self, index: usize) -> &T { self.slots[..self.len][index].as_ref().unwrap() }
+    fn slot_mut(&mut self, index: usize) -> &mut T { self.slots[..self.len][index].as_mut().unwrap() }
+    fn clear(&mut self) { for slot in self.slots.iter_mut() { *slot = None } self.len = 0 }
+    fn capacity(&self) -> usize { self.slots.len() }
+}
+
+#[test]
+fn test_arena_tree_fixed_pool(){
+    let pool = FixedPool{slots: (0..500).map(|_| None).collect::<Vec<_>>().into_boxed_slice(), len: 0};
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::ArenaIntervalTree::<u64, _>::with_alloc(pool);
+    for i in 0..3000 {
+        let range = small_random_range();
+        if set.len() < 500 && !set.contains(&range) {
+            set.insert(range);
+            assert_eq!(t.insert(range, range.min), None);
+        }
+        if i % 2 == 0 {
+            let k = small_random_range();
+            assert_eq!(t.remove(k), if set.remove(&k) { Some(k.min) } else { None });
+        }
+    }
+    assert_eq!(t.len(), set.len());
+    assert_eq!(t.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(), set.iter().map(|k| (*k, k.min)).collect::<Vec<_>>());
+    for _ in 0..100 {
+        let query = small_random_range();
+        assert_eq!(t.range(query).map(|(k,_)| *k).collect::<Vec<_>>(), set.iter().filter(|k| k.intersect(&query)).cloned().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn test_arena_fork(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut t = interval_tree::ArenaIntervalTree::<u64, _>::with_alloc(interval_tree::SharedChunks::new());
+    for _ in 0..2000 {
+        let range = small_random_range();
+        t.insert(range, range.min);
+        set.insert(range);
+    }
+    let (mut fork, mut fork_set) = (t.fork(), set.clone());
+    for i in 0..2000 {
+        let range = small_random_range();
+        assert_eq!(fork.insert(range, range.min).is_some(), !fork_set.insert(range));
+        if i % 2 == 0 {
+            let k = small_random_range();
+            assert_eq!(fork.remove(k), if fork_set.remove(&k) { Some(k.min) } else { None });
+        }
+    }
+    for _ in 0..100 {
+        let k = small_random_range();
+        assert_eq!(t.remove(k), if set.remove(&k) { Some(k.min) } else { None });
+    }
+    assert_eq!(t.iter().map(|(k,_)| *k).collect::<Vec<_>>(), set.iter().cloned().collect::<Vec<_>>());
+    assert_eq!(fork.iter().map(|(k,_)| *k).collect::<Vec<_>>(), fork_set.iter().cloned().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_chunked_map(){
+    let mut set = BTreeSet::<Range>::new();
+    let mut m = interval_tree::ChunkedIntervalMap::<u64>::new();
+    for i in 0..5000 {
+        let range = small_random_range();
+        assert_eq!(m.insert(range, range.min).is_some(), !set.insert(range));
+        if i % 2 == 0 {
+            let k = small_random_range();
+            assert_eq!(m.remove(k), if set.remove(&k) { Some(k.min) } else { None });
+        }
+        if i % 5 == 0 {
+            let k = *set.iter().next().unwrap();
+            assert_eq!(m.remove(k), Some(k.min));
+            set.remove(&k);
+        }
+        assert_eq!(m.len(), set.len());
+    }
+    assert_eq!(m.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(), set.iter().map(|k| (*k, k.min)).collect::<Vec<_>>());
+    for _ in 0..100 {
+        let query = small_random_range();
+        assert_eq!(m.range(query).map(|(k,_)| *k).collect::<Vec<_>>(), set.iter().filter(|k| k.intersect(&query)).cloned().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn test_persistent_tree(){
+    let mut sets = vec![BTreeSet::<Range>::new()];
+    let mut versions = vec![interval_tree::PersistentIntervalTree::<u64>::new()];
+    for i in 0..1000 {
+        let (mut set, version) = (sets.last().unwrap().clone(), versions.last().unwrap().clone());
+        let range = small_random_range();
+        let version = if i % 3 == 0 { set.remove(&range); version.remove(range) } else { set.insert(range); version.insert(range, range.min) };
+        sets.push(set);
+        versions.push(version);
+    }
+    for (set, version) in sets.iter().zip(versions.iter()).step_by(50) {
+        assert_eq!(version.len(), set.len());
+        assert_eq!(version.iter().map(|(k,v)| (*k,*v)).collect::<Vec<_>>(), set.iter().map(|k| (*k, k.min)).collect::<Vec<_>>());
+        let query = small_random_range();
+        assert_eq!(version.range(query).count(), set.iter().filter(|k| k.intersect(&query)).count());
+    }
+}
+
+#[test]
+fn test_concurrent_tree(){
+    use std::sync::Arc;
+    use std::thread;
+    let t = Arc::new(interval_tree::ConcurrentIntervalTree::<u64>::with_boundaries(vec![250, 500, 750]));
+    let writers = (0..4u64).map(|w| {
+        let t = t.clone();
+        thread::spawn(move || {
+            for i in 0..500 {
+                let range = Range::new(w*250 + i % 250, w*250 + i % 250 + i % 40);
+                t.insert(range, w);
+                if i % 3 == 0 { t.remove(range); }
+            }
+        })
+    }).collect::<Vec<_>>();
+    let readers = (0..4).map(|_| {
+        let t = t.clone();
+        thread::spawn(move || {
+            for _ in 0..200 {
+                let query = small_random_range();
+                for (key, _) in t.find_overlaps(query) { assert!(key.intersect(&query)) }
+            }
+        })
+    }).collect::<Vec<_>>();
+    for handle in writers.into_iter().chain(readers) { handle.join().unwrap() }
+    let t = Arc::try_unwrap(t).ok().unwrap();
+    let query = Range::new(200, 600);
+    let found = t.find_overlaps(query);
+    let tree = t.into_tree();
+    assert_eq!(found, tree.range(query).map(|(k,v)| (*k,*v)).collect::<Vec<_>>());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_range(){
+    use rayon::prelude::*;
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for _ in 0..3000 {
+        let range = small_random_range();
+        t.insert(range, range.min);
+    }
+    assert_eq!(t.par_iter().collect::<Vec<_>>(), t.iter().collect::<Vec<_>>());
+    for _ in 0..100 {
+        let query = small_random_range();
+        assert_eq!(t.par_range(query).collect::<Vec<_>>(), t.range(query).collect::<Vec<_>>());
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_from_vec(){
+    let pairs = (0..20000).map(|i| (small_random_range(), i)).collect::<Vec<_>>();
+    let t = interval_tree::IntervalTree::par_from_vec(pairs.clone());
+    let expected = interval_tree::IntervalTree::from_vec(pairs);
+    assert_eq!(t.height(), expected.height());
+    assert_eq!(t.iter().collect::<Vec<_>>(), expected.iter().collect::<Vec<_>>());
+    let query = small_random_range();
+    assert_eq!(t.range(query).count(), expected.range(query).count());
+}
+
+#[test]
+fn test_memory_usage(){
+    let mut t = interval_tree::IntervalTree::<Vec<u8>>::new();
+    assert_eq!(t.memory_usage(), 0);
+    for i in 0..100 {
+        t.insert(Range::new(i*100, i*100+5), vec![0; i as usize]);
+    }
+    t.insert(Range::new(0, 5), vec![0; 1000]);
+    let usage = t.memory_stats(|v| v.len());
+    assert_eq!(usage.nodes, 100);
+    assert_eq!(usage.data_heap, 4950 + 1000);
+    assert!(usage.node_size >= usage.data_size + std::mem::size_of::<Range>());
+    assert_eq!(usage.total(), t.memory_usage() + usage.data_heap);
+}
+
+#[test]
+fn test_stats(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    assert_eq!(t.stats().average_depth, 0.0);
+    for i in 0..1023 { t.insert(Range::new(i,i), i) }
+    let stats = t.stats();
+    assert_eq!((stats.len, stats.height, stats.min_height), (1023, 10, 10));
+    assert_eq!(stats.average_depth, (1..11).map(|d| d * (1 << (d-1))).sum::<usize>() as f64 / 1023.0);
+    assert!(stats.rotations >= 1000);
+    t.rebalance();
+    assert_eq!(t.stats().rotations, stats.rotations);
+    for i in 0..1023 { t.delete(Range::new(i,i)) }
+    assert!(t.stats().rotations > stats.rotations);
+    assert_eq!(interval_tree::IntervalTree::from_vec(vec![(Range::new(1,1), 0)]).stats().rotations, 0);
+}
+
+#[cfg(feature = "quickcheck")]
+#[test]
+fn test_arbitrary_tree(){
+    use quickcheck::{Arbitrary, Gen};
+    let mut g = Gen::new(200);
+    for _ in 0..100 {
+        let t = interval_tree::IntervalTree::<i32>::arbitrary(&mut g);
+        assert_eq!(t.check_invariants(), Ok(()));
+        for shrunk in t.shrink().take(20) {
+            assert_eq!(shrunk.check_invariants(), Ok(()));
+            assert!(shrunk.len() <= t.len());
+        }
+    }
+}
+
+#[cfg(feature = "test_util")]
+#[test]
+fn test_random_tree(){
+    let key_space = Range::new(1000, 5000);
+    let t = interval_tree::IntervalTree::random(7, 2000, key_space);
+    assert_eq!(t.check_invariants(), Ok(()));
+    assert!(t.len() <= 2000);
+    assert!(t.keys().all(|key| key_space.min <= key.min && key.max <= key_space.max));
+    assert!(t.iter().eq(interval_tree::IntervalTree::random(7, 2000, key_space).iter()));
+    assert_eq!(interval_tree::IntervalTree::random(7, 0, key_space).len(), 0);
+    let full = interval_tree::IntervalTree::random(1, 100, ..);
+    assert_eq!(full.len(), 100);
+}
+
+#[test]
+fn test_join(){
+    let mut a = interval_tree::IntervalTree::<u64>::new();
+    let mut b = interval_tree::IntervalTree::<i32>::new();
+    for i in 0..500 {
+        a.insert(small_random_range(), i);
+        b.insert(small_random_range(), i as i32);
+    }
+    let mut expected = Vec::new();
+    for (key, data) in a.iter() {
+        for (other_key, other_data) in b.range(key) {
+            expected.push((*key, *data, *other_key, *other_data));
+        }
+    }
+    let mut joined = a.join(&b).map(|((k, d), (ok, od))| (*k, *d, *ok, *od)).collect::<Vec<_>>();
+    assert!(joined.windows(2).all(|w| cmp::max(w[0].0.min, w[0].2.min) <= cmp::max(w[1].0.min, w[1].2.min)));
+    joined.sort();
+    expected.sort();
+    assert_eq!(joined, expected);
+    assert_eq!(a.join(&interval_tree::IntervalTree::<i32>::new()).count(), 0);
+}
+
+#[test]
+fn test_diff(){
+    use interval_tree::Change;
+    let mut a = interval_tree::IntervalTree::<u64>::new();
+    let mut b = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..1000 {
+        a.insert(small_random_range(), i%3);
+        b.insert(small_random_range(), i%3);
+    }
+    assert_eq!(a.diff(&a).count(), 0);
+    let mut c = interval_tree::IntervalTree::<u64>::new();
+    for change in a.diff(&b) {
+        match change {
+            Change::OnlyInSelf(key, data) => { assert!(b.get(*key).is_none()); c.insert(*key, *data) },
+            Change::OnlyInOther(key, _) => assert!(a.get(*key).is_none()),
+            Change::Differs(key, data, other) => { assert_eq!(b.get(*key), Some(other)); assert!(data != other); c.insert(*key, *data) },
+        }
+    }
+    for (key, data) in b.iter() {
+        if a.get(*key) == Some(data) { c.insert(*key, *data) }
+    }
+    assert!(c.iter().eq(a.iter()));
+}
+
+#[test]
+fn test_events(){
+    use interval_tree::Event;
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..1000 { t.insert(small_random_range(), i) }
+    let events = t.events(..).collect::<Vec<_>>();
+    assert_eq!(events.len(), 2*t.len());
+    assert!(events.windows(2).all(|w| w[0].position() <= w[1].position()));
+    let mut depth = 0usize;
+    for event in events {
+        match event {
+            Event::Start(key, _) => { depth += 1; assert_eq!(depth, t.range(key.min..=key.min).filter(|&(k, _)| k <= key).count()) },
+            Event::End(key, _) => { assert!(depth > 0); depth -= 1 },
+        }
+    }
+    assert_eq!(depth, 0);
+    let window = Range::new(300, 400);
+    assert_eq!(t.events(window).count(), 2*t.range(window).count());
+}
+
+#[test]
+fn test_iter_coalesced(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    let mut s = interval_tree::IntervalSet::new();
+    for i in 0..300 {
+        let key = small_random_range();
+        t.insert(key, i);
+        s.insert(key);
+    }
+    t.insert(Range::new(u64::max_value()-5, u64::max_value()), 0);
+    s.insert(Range::new(u64::max_value()-5, u64::max_value()));
+    assert_eq!(t.iter_coalesced().collect::<Vec<Range>>(), s.iter().cloned().collect::<Vec<Range>>());
+    let counts = t.iter_coalesced_with(0, |n, _, _| n+1).map(|(_, n)| n).sum::<usize>();
+    assert_eq!(counts, t.len());
+}
+
+#[test]
+fn test_iter_by_end(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..1000 { t.insert(small_random_range(), i) }
+    let mut expected = t.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+    expected.sort_by_key(|&(k, _)| (k.max, k.min));
+    assert_eq!(t.iter_by_end().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), expected);
+    let window = Range::new(200, 400);
+    let within = expected.iter().cloned().filter(|&(k, _)| window.min <= k.max && k.max <= window.max).collect::<Vec<_>>();
+    assert_eq!(t.range_by_end(window).map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), within);
+    assert_eq!(t.range_by_end(5..5).count(), 0);
+}
+
+#[test]
+fn test_shift(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..1000 { t.insert(small_random_range(), i) }
+    let before = t.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+    t.shift(1 << 40).unwrap();
+    assert_eq!(t.check_invariants(), Ok(()));
+    assert_eq!(t.iter().map(|(k, v)| (Range::new(k.min - (1 << 40), k.max - (1 << 40)), *v)).collect::<Vec<_>>(), before);
+    t.insert(Range::new(u64::max_value()-10, u64::max_value()-5), 0);
+    assert_eq!(t.shift(6).unwrap_err().key, Range::new(u64::max_value()-10, u64::max_value()-5));
+    assert!(t.shift(i64::min_value()).is_err());
+    t.delete(Range::new(u64::max_value()-10, u64::max_value()-5));
+    t.shift(-(1 << 40)).unwrap();
+    assert_eq!(t.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), before);
+    let mut u = interval_tree::IntervalTree::<i32>::with_universe(Range::new(100, 200), interval_tree::UniversePolicy::Clamp);
+    u.insert(Range::new(150, 160), 1);
+    assert!(u.shift(41).is_err());
+    assert!(u.shift(-51).is_err());
+    u.shift(40).unwrap();
+    assert_eq!(u.keys().cloned().collect::<Vec<_>>(), vec![Range::new(190, 200)]);
+}
+
+#[test]
+fn test_map_keys(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..1000 { t.insert(small_random_range(), i) }
+    let before = t.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+    let mapped = t.map_keys(|k| Range::new(k.min*3 + 7, k.max*3 + 9)).unwrap();
+    assert_eq!(mapped.check_invariants(), Ok(()));
+    assert_eq!(mapped.iter().map(|(k, v)| (Range::new((k.min - 7)/3, (k.max - 9)/3), *v)).collect::<Vec<_>>(), before);
+    let moved = mapped.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+    let err = mapped.map_keys(|k| Range::new(k.min/1000, k.max/1000)).unwrap_err();
+    assert!(err.error.mapped.min <= err.error.mapped.max);
+    //nothing is lost when the transform is rejected
+    assert_eq!(err.tree.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), moved);
+    let mut u = interval_tree::IntervalTree::<u64>::new();
+    u.insert(Range::new(5, 9), 0);
+    assert_eq!(u.map_keys(|k| Range{min: k.max, max: k.min}).unwrap_err().error, interval_tree::KeyOrderError{key: Range::new(5, 9), mapped: Range{min: 9, max: 5}});
+    let mut sums = interval_tree::IntervalTree::with_aggregate(interval_tree::aggregate::Sum);
+    for i in 0..100u64 { sums.insert(Range::new(i*10, i*10+9), i) }
+    let sums = sums.map_keys(|k| Range::new(k.min + 5, k.max + 5)).unwrap();
+    assert_eq!(sums.aggregate(..), 4950);
+    assert_eq!(sums.check_invariants(), Ok(()));
+}
+
+#[test]
+fn test_map_values(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..1000 { t.insert(small_random_range(), i) }
+    let expected = t.iter().map(|(k, v)| (*k, v.to_string())).collect::<Vec<_>>();
+    let height = t.height();
+    let mut calls = Vec::new();
+    let mapped = t.map_values(|v| { calls.push(v); v.to_string() });
+    assert_eq!(mapped.check_invariants(), Ok(()));
+    assert_eq!(mapped.height(), height);
+    assert_eq!(calls.len(), expected.len());
+    assert_eq!(mapped.into_iter().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn test_into_sorted_vec(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..1000 { t.insert(random_range(), i) }
+    let copy = t.to_vec();
+    assert_eq!(copy.capacity(), t.len());
+    assert!(copy.windows(2).all(|w| w[0].0 < w[1].0));
+    let pairs = t.into_sorted_vec();
+    assert_eq!(pairs.capacity(), pairs.len());
+    assert_eq!(pairs, copy);
+    assert_eq!(interval_tree::IntervalTree::<u64>::new().into_sorted_vec(), vec![]);
+}
+
+#[test]
+fn test_is_covered(){
+    let mut t = interval_tree::IntervalTree::<u64>::new();
+    for i in 0..100 { t.insert(small_random_range(), i) }
+    for _ in 0..1000 {
+        let window = small_random_range();
+        assert_eq!(t.is_covered(window), t.coverage(window) == window.len());
+    }
+    assert!(t.is_covered(5..5));
+    t.insert(Range::new(0, u64::max_value()), 0);
+    assert!(t.is_covered(..));
+}
+
+#[test]
+fn test_multimap_order(){
+    let mut m = interval_tree::IntervalMultiMap::with_order(|a: &(u64, u64), b: &(u64, u64)| a.0.cmp(&b.0));
+    let mut expected = Vec::new();
+    for i in 0..1000 {
+        let key = Range::new(rand::random::<u64>()%20, 20 + rand::random::<u64>()%5);
+        let value = (rand::random::<u64>()%10, i);
+        m.insert(key, value);
+        expected.push((key, value));
+    }
+    expected.sort_by_key(|&(key, (id, i))| (key, id, i));
+    assert_eq!(m.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>(), expected);
+    //the order can capture state, here the rank of every feature type
+    let ranks = vec![("gene", 0), ("exon", 1), ("cds", 2)].into_iter().collect::<std::collections::HashMap<_, _>>();
+    let mut m = interval_tree::IntervalMultiMap::with_order(move |a: &&str, b: &&str| ranks[a].cmp(&ranks[b]));
+    for &kind in &["cds", "gene", "exon", "cds", "gene"] { m.insert(Range::new(10, 20), kind) }
+    assert_eq!(m.range(15..16).map(|(_, v)| *v).collect::<Vec<_>>(), vec!["gene", "gene", "exon", "cds", "cds"]);
+    assert_eq!(m.remove_all(Range::new(10, 20)), vec!["gene", "gene", "exon", "cds", "cds"]);
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+    assert_send_sync(&m);
+    assert_send_sync(&interval_tree::IntervalMultiMap::<u64>::new());
+}